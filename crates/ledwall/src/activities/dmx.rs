@@ -0,0 +1,729 @@
+//! Activity that displays pixels sent by stage lighting software as DMX over
+//! [E1.31] (sACN) or [Art-Net].
+//!
+//! Each pixel takes 3 consecutive channels (RGB). By default, pixels fill
+//! consecutive universes starting from universe 1, with 170 pixels in each
+//! universe. The mapping can be changed in `dmx.toml` (see [`DmxConfig`]).
+//!
+//! If sACN data has a synchronization address or an `ArtSync` packet was
+//! received in the last few seconds, pixels are displayed only when a sync
+//! packet arrives, so that a frame spanning several universes is displayed all
+//! at once.
+//!
+//! Press Y to toggle the status overlay, which shows the number of packets
+//! received per second and the number of universes received in the last
+//! second.
+//!
+//! [E1.31]: https://tsp.esta.org/tsp/documents/published_docs.php
+//! [Art-Net]: https://art-net.org.uk/
+
+use std::collections::HashSet;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::ActivityInfo;
+use super::net::{Listener, RETRY_INTERVAL, recv_packets};
+use crate::state::invalid_data;
+use crate::{
+    Activity, BLACK, DEFAULT_SIZE, FPS, FrameBuffer, FrameBufferRect, FullInput, Rgb, Rotation,
+    StaticImage, WHITE, Widget, storage, text,
+};
+
+pub const INFO: ActivityInfo = ActivityInfo {
+    id: "dmx",
+    name: "DMX",
+    min_players: 0,
+    attract: false,
+    enabled_by_default: false,
+};
+
+/// File name of the configuration file within [`storage::dir()`].
+pub const CONFIG_FILE_NAME: &str = "dmx.toml";
+
+/// Default UDP port for sACN.
+pub const SACN_PORT: u16 = 5568;
+/// Default UDP port for Art-Net.
+pub const ARTNET_PORT: u16 = 6454;
+
+/// Number of channels in a universe.
+const UNIVERSE_LEN: usize = 512;
+/// Number of pixels in each universe if no patches are configured.
+const PIXELS_PER_UNIVERSE: usize = UNIVERSE_LEN / 3;
+
+/// Time after the last pixels were received before showing the status overlay.
+const TIMEOUT: usize = 2 * FPS;
+/// Time after an `ArtSync` packet during which Art-Net data waits for the next
+/// `ArtSync`, as specified by Art-Net.
+const ARTNET_SYNC_TIMEOUT: usize = 4 * FPS;
+
+const STATUS_BACKGROUND_DIM: f32 = 0.75;
+const STATUS_VALUE_COLOR: Rgb = WHITE;
+const SACN_COLOR: Rgb = Rgb::from_hex(0xFF6600);
+const ARTNET_COLOR: Rgb = Rgb::from_hex(0x00FFFF);
+const SYNC_COLOR: Rgb = Rgb::from_hex(0xFFE400);
+const UNIVERSES_COLOR: Rgb = Rgb::from_hex(0x7777FF);
+
+const ACN_PACKET_ID: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const VECTOR_ROOT_E131_DATA: u32 = 0x4;
+const VECTOR_ROOT_E131_EXTENDED: u32 = 0x8;
+const VECTOR_E131_DATA_PACKET: u32 = 0x2;
+const VECTOR_E131_EXTENDED_SYNCHRONIZATION: u32 = 0x1;
+const SACN_OPTION_PREVIEW: u8 = 0x80;
+const SACN_OPTION_TERMINATED: u8 = 0x40;
+
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const ARTNET_OP_DMX: u16 = 0x5000;
+const ARTNET_OP_SYNC: u16 = 0x5200;
+
+/// Configuration for the DMX activity, loaded from [`CONFIG_FILE_NAME`].
+///
+/// ```toml
+/// rotation = 90
+/// serpentine = true
+///
+/// [[patches]]
+/// universe = 1
+/// start_channel = 1
+/// pixel_count = 170
+/// first_pixel = 0
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DmxConfig {
+    /// Address to listen for sACN on, or `off` to disable sACN. Defaults to
+    /// all interfaces.
+    pub sacn_addr: String,
+    /// Address to listen for Art-Net on, or `off` to disable Art-Net. Defaults
+    /// to all interfaces.
+    pub artnet_addr: String,
+
+    /// Clockwise rotation of the pixel order in degrees (0, 90, 180, or 270).
+    ///
+    /// With no rotation, pixels are numbered in row-major order starting from
+    /// the top left. With a rotation of 90 degrees, they are numbered in
+    /// column-major order starting from the top right.
+    pub rotation: Rotation,
+    /// Whether every other row (or column, if rotated) of pixels runs in the
+    /// opposite direction.
+    pub serpentine: bool,
+
+    /// Universe of the first pixel if `patches` is empty.
+    pub first_universe: u16,
+    /// Ranges of channels to map onto ranges of pixels. If this is empty,
+    /// pixels fill consecutive universes starting from `first_universe`.
+    pub patches: Vec<Patch>,
+}
+
+impl Default for DmxConfig {
+    fn default() -> Self {
+        Self {
+            sacn_addr: format!("0.0.0.0:{SACN_PORT}"),
+            artnet_addr: format!("0.0.0.0:{ARTNET_PORT}"),
+
+            rotation: Rotation::R0,
+            serpentine: false,
+
+            first_universe: 1,
+            patches: vec![],
+        }
+    }
+}
+
+impl DmxConfig {
+    /// Returns the patches, or the default patches covering a wall of `size`
+    /// if none are configured.
+    fn patches(&self, [width, height]: [usize; 2]) -> Vec<Patch> {
+        if !self.patches.is_empty() {
+            return self.patches.clone();
+        }
+        (0..(width * height).div_ceil(PIXELS_PER_UNIVERSE))
+            .map(|i| Patch {
+                universe: self.first_universe.saturating_add(i as u16),
+                start_channel: 1,
+                pixel_count: PIXELS_PER_UNIVERSE,
+                first_pixel: i * PIXELS_PER_UNIVERSE,
+            })
+            .collect()
+    }
+
+    /// Returns the position on a wall of `size` of the pixel at index `i` in
+    /// the pixel order.
+    fn pixel_position(&self, [width, height]: [usize; 2], i: usize) -> Option<[usize; 2]> {
+        let [w, h] = self.rotation.rotate_size([width, height]);
+        if i >= w * h {
+            return None;
+        }
+        let y = i / w;
+        let mut x = i % w;
+        if self.serpentine && y % 2 == 1 {
+            x = w - 1 - x;
+        }
+        Some(self.rotation.rotate([w, h], [x, y]))
+    }
+}
+
+/// Range of channels in a universe mapped onto a range of pixels.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct Patch {
+    /// Universe number. For Art-Net, this is the 15-bit port-address.
+    pub universe: u16,
+    /// First channel, starting from 1.
+    #[serde(default = "default_start_channel")]
+    pub start_channel: u16,
+    /// Number of pixels.
+    pub pixel_count: usize,
+    /// Index of the first pixel in the pixel order.
+    pub first_pixel: usize,
+}
+
+fn default_start_channel() -> u16 {
+    1
+}
+
+/// Message parsed from an sACN or Art-Net packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum DmxPacket<'a> {
+    /// Channel values for a universe, starting from channel 1.
+    Data {
+        universe: u16,
+        /// Whether to wait for a sync packet before displaying the data.
+        wait_for_sync: bool,
+        data: &'a [u8],
+    },
+    /// Display all data received so far.
+    Sync,
+}
+
+/// Parses an sACN packet, returning `None` for packets that should be ignored.
+fn parse_sacn(packet: &[u8]) -> Result<Option<DmxPacket<'_>>, String> {
+    if packet.len() < 44 || packet[4..16] != *ACN_PACKET_ID {
+        return Err("not an sACN packet".to_string());
+    }
+    let u16_at = |i: usize| u16::from_be_bytes([packet[i], packet[i + 1]]);
+    let u32_at = |i: usize| u32::from_be_bytes([0, 1, 2, 3].map(|j| packet[i + j]));
+
+    match (u32_at(18), u32_at(40)) {
+        (VECTOR_ROOT_E131_DATA, VECTOR_E131_DATA_PACKET) => {
+            if packet.len() < 126 {
+                return Err("packet too short".to_string());
+            }
+            let options = packet[112];
+            if options & (SACN_OPTION_PREVIEW | SACN_OPTION_TERMINATED) != 0 {
+                return Ok(None);
+            }
+            let property_count = u16_at(123) as usize;
+            if property_count == 0 || packet.len() < 125 + property_count {
+                return Err("wrong property value count".to_string());
+            }
+            if packet[125] != 0 {
+                return Ok(None); // not DMX data
+            }
+            Ok(Some(DmxPacket::Data {
+                universe: u16_at(113),
+                wait_for_sync: u16_at(109) != 0,
+                data: &packet[126..125 + property_count],
+            }))
+        }
+        (VECTOR_ROOT_E131_EXTENDED, VECTOR_E131_EXTENDED_SYNCHRONIZATION) => {
+            Ok(Some(DmxPacket::Sync))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Parses an Art-Net packet, returning `None` for packets that should be
+/// ignored.
+fn parse_artnet(packet: &[u8]) -> Result<Option<DmxPacket<'_>>, String> {
+    if packet.len() < 12 || packet[..8] != *ARTNET_ID {
+        return Err("not an Art-Net packet".to_string());
+    }
+    match u16::from_le_bytes([packet[8], packet[9]]) {
+        ARTNET_OP_DMX => {
+            if packet.len() < 18 {
+                return Err("packet too short".to_string());
+            }
+            let len = u16::from_be_bytes([packet[16], packet[17]]) as usize;
+            if packet.len() < 18 + len {
+                return Err("wrong data length".to_string());
+            }
+            Ok(Some(DmxPacket::Data {
+                universe: u16::from_le_bytes([packet[14], packet[15]]) & 0x7FFF,
+                wait_for_sync: false,
+                data: &packet[18..18 + len],
+            }))
+        }
+        ARTNET_OP_SYNC => Ok(Some(DmxPacket::Sync)),
+        _ => Ok(None),
+    }
+}
+
+/// Number of packets received in some time.
+#[derive(Debug, Default, Clone)]
+struct PacketCounts {
+    sacn: usize,
+    artnet: usize,
+    sync: usize,
+    universes: HashSet<u16>,
+}
+
+pub struct Dmx {
+    config: DmxConfig,
+    patches: Vec<Patch>,
+
+    sacn: Listener<UdpSocket>,
+    artnet: Listener<UdpSocket>,
+    /// Universes whose sACN multicast group the socket has joined.
+    sacn_groups: HashSet<u16>,
+
+    /// Pixels received since the last sync.
+    pending: FrameBuffer,
+    /// Pixels to display.
+    pixels: FrameBuffer,
+
+    frame: usize,
+    last_received_frame: Option<usize>,
+    last_artnet_sync_frame: Option<usize>,
+
+    show_status: bool,
+    /// Packets received in the current second.
+    counts: PacketCounts,
+    /// Packets received in the previous second.
+    rates: PacketCounts,
+}
+
+impl Default for Dmx {
+    fn default() -> Self {
+        Self::new(DmxConfig::default())
+    }
+}
+
+impl Dmx {
+    pub fn new(config: DmxConfig) -> Self {
+        let addr = |s: &str| (s != "off" && !s.is_empty()).then(|| s.to_string());
+        Self {
+            patches: config.patches(DEFAULT_SIZE),
+
+            sacn: Listener::new("sACN", addr(&config.sacn_addr)),
+            artnet: Listener::new("Art-Net", addr(&config.artnet_addr)),
+            sacn_groups: HashSet::new(),
+
+            pending: FrameBuffer::new(DEFAULT_SIZE),
+            pixels: FrameBuffer::new(DEFAULT_SIZE),
+
+            frame: 0,
+            last_received_frame: None,
+            last_artnet_sync_frame: None,
+
+            show_status: false,
+            counts: PacketCounts::default(),
+            rates: PacketCounts::default(),
+
+            config,
+        }
+    }
+
+    fn is_receiving(&self) -> bool {
+        self.last_received_frame
+            .is_some_and(|f| self.frame - f < TIMEOUT)
+    }
+
+    fn receive(&mut self) {
+        let patches = &self.patches;
+        let groups = &mut self.sacn_groups;
+        let sacn_packets = self
+            .sacn
+            .get(self.frame, |addr| {
+                let socket = bind_udp(addr)?;
+                groups.clear();
+                update_sacn_groups(&socket, patches, groups);
+                Ok(socket)
+            })
+            .map(|socket| recv_packets(socket, "sACN"))
+            .unwrap_or_default();
+        // Retry multicast groups that couldn't be joined.
+        if self.frame.is_multiple_of(RETRY_INTERVAL)
+            && let Some(socket) = &self.sacn.socket
+        {
+            update_sacn_groups(socket, &self.patches, &mut self.sacn_groups);
+        }
+        for packet in sacn_packets {
+            match parse_sacn(&packet) {
+                Ok(Some(packet)) => {
+                    self.counts.sacn += 1;
+                    self.handle_packet(packet);
+                }
+                Ok(None) => (),
+                Err(e) => eprintln!("Invalid sACN packet: {e}"),
+            }
+        }
+
+        let artnet_packets = self
+            .artnet
+            .get(self.frame, bind_udp)
+            .map(|socket| recv_packets(socket, "Art-Net"))
+            .unwrap_or_default();
+        for packet in artnet_packets {
+            match parse_artnet(&packet) {
+                Ok(Some(mut packet)) => {
+                    self.counts.artnet += 1;
+                    match &mut packet {
+                        DmxPacket::Data { wait_for_sync, .. } => {
+                            *wait_for_sync = self
+                                .last_artnet_sync_frame
+                                .is_some_and(|f| self.frame - f < ARTNET_SYNC_TIMEOUT);
+                        }
+                        DmxPacket::Sync => self.last_artnet_sync_frame = Some(self.frame),
+                    }
+                    self.handle_packet(packet);
+                }
+                Ok(None) => (),
+                Err(e) => eprintln!("Invalid Art-Net packet: {e}"),
+            }
+        }
+    }
+
+    fn handle_packet(&mut self, packet: DmxPacket<'_>) {
+        match packet {
+            DmxPacket::Data {
+                universe,
+                wait_for_sync,
+                data,
+            } => {
+                self.counts.universes.insert(universe);
+                let size = self.pending.size();
+                for patch in self.patches.iter().filter(|p| p.universe == universe) {
+                    let start = (patch.start_channel as usize).saturating_sub(1);
+                    let channels = data.get(start..).unwrap_or_default();
+                    let colors = channels.as_chunks().0.iter().take(patch.pixel_count);
+                    for (i, &[r, g, b]) in colors.enumerate() {
+                        if let Some([x, y]) =
+                            self.config.pixel_position(size, patch.first_pixel + i)
+                        {
+                            self.pending[y][x] = Rgb([r, g, b]);
+                        }
+                    }
+                }
+                self.last_received_frame = Some(self.frame);
+                if !wait_for_sync {
+                    self.pixels.clone_from(&self.pending);
+                }
+            }
+            DmxPacket::Sync => {
+                self.counts.sync += 1;
+                self.pixels.clone_from(&self.pending);
+            }
+        }
+    }
+
+    fn draw_status(&self, fb: &mut FrameBufferRect<'_>) {
+        let lines = [
+            ("sacn", SACN_COLOR, self.rates.sacn),
+            ("artn", ARTNET_COLOR, self.rates.artnet),
+            ("sync", SYNC_COLOR, self.rates.sync),
+            ("univ", UNIVERSES_COLOR, self.rates.universes.len()),
+        ];
+        fb.with_size([fb.width(), lines.len() * 6 + 1])
+            .fill_with_fn(|_, c| c.darken(STATUS_BACKGROUND_DIM));
+        for (i, (label, color, value)) in lines.into_iter().enumerate() {
+            let y = 1 + i as isize * 6;
+            text::draw(label, &mut fb.with_offset([1, y]), color);
+            let value = value.min(999).to_string();
+            let x = fb.width().saturating_sub(1 + text::width(&value)) as isize;
+            text::draw(&value, &mut fb.with_offset([x, y]), STATUS_VALUE_COLOR);
+        }
+    }
+}
+
+/// Joins the sACN multicast group of each universe in `patches` and leaves
+/// the groups of other universes, if `socket` listens on all interfaces.
+/// `joined` only changes for groups that were joined or left successfully, so
+/// calling this again retries the rest.
+fn update_sacn_groups(socket: &UdpSocket, patches: &[Patch], joined: &mut HashSet<u16>) {
+    let unspecified = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+    if !socket
+        .local_addr()
+        .is_ok_and(|addr| addr.ip() == unspecified)
+    {
+        return;
+    }
+    let universes: HashSet<u16> = patches.iter().map(|p| p.universe).collect();
+    let group = |universe: u16| {
+        let [hi, lo] = universe.to_be_bytes();
+        Ipv4Addr::new(239, 255, hi, lo)
+    };
+    joined.retain(|&universe| {
+        if universes.contains(&universe) {
+            return true;
+        }
+        let group = group(universe);
+        let result = socket.leave_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED);
+        if let Err(e) = &result {
+            eprintln!("Error leaving sACN multicast group {group}: {e}");
+        }
+        result.is_err()
+    });
+    for universe in universes {
+        if joined.contains(&universe) {
+            continue;
+        }
+        let group = group(universe);
+        match socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED) {
+            Ok(()) => _ = joined.insert(universe),
+            Err(e) => eprintln!("Error joining sACN multicast group {group}: {e}"),
+        }
+    }
+}
+
+fn bind_udp(addr: &str) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(addr)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+impl Widget<FullInput> for Dmx {
+    fn step(&mut self, input: FullInput) {
+        self.frame += 1;
+        if input.any().pressed().y {
+            self.show_status ^= true;
+        }
+        self.receive();
+        if self.frame.is_multiple_of(FPS) {
+            self.rates = std::mem::take(&mut self.counts);
+        }
+    }
+
+    fn draw(&self, fb: &mut FrameBufferRect<'_>) {
+        let is_receiving = self.is_receiving();
+        if is_receiving {
+            fb.fill_with_fn(|[x, y], _| self.pixels[y][x]);
+        } else {
+            let image = self.menu_image();
+            let x = (fb.width().saturating_sub(image.width()) / 2) as isize;
+            let y = fb.height().saturating_sub(image.height()) as isize;
+            image.draw(&mut fb.with_offset([x, y]));
+        }
+        if self.show_status || !is_receiving {
+            self.draw_status(fb);
+        }
+    }
+}
+
+impl Activity for Dmx {
+    fn info(&self) -> &'static ActivityInfo {
+        &INFO
+    }
+
+    fn menu_image(&self) -> StaticImage {
+        include_rgba_image!("activities/dmx.rgba")
+    }
+
+    /// Clears the pixels but keeps listening.
+    fn reset(&mut self) {
+        self.pending.as_flattened_mut().fill(BLACK);
+        self.pixels.as_flattened_mut().fill(BLACK);
+        self.last_received_frame = None;
+    }
+
+    fn load_config(&mut self, dir: &Path) -> io::Result<()> {
+        let config: DmxConfig = storage::load_toml(dir, CONFIG_FILE_NAME)?;
+        if let Some(patch) = config
+            .patches
+            .iter()
+            .find(|p| !(1..=UNIVERSE_LEN).contains(&(p.start_channel as usize)))
+        {
+            return Err(invalid_data(format!(
+                "invalid start channel {} for universe {}",
+                patch.start_channel, patch.universe,
+            )));
+        }
+        let size = self.pixels.size();
+        *self = Self::new(config);
+        self.set_size(size);
+        Ok(())
+    }
+
+    fn set_size(&mut self, size: [usize; 2]) {
+        self.patches = self.config.patches(size);
+        if let Some(socket) = &self.sacn.socket {
+            update_sacn_groups(socket, &self.patches, &mut self.sacn_groups);
+        }
+        self.pending = FrameBuffer::new(size);
+        self.pixels = FrameBuffer::new(size);
+    }
+
+    fn stay_awake(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const WIDTH: usize = DEFAULT_SIZE[0];
+    const HEIGHT: usize = DEFAULT_SIZE[1];
+
+    fn sacn_data_packet(universe: u16, sync_address: u16, data: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0; 126];
+        packet[4..16].copy_from_slice(ACN_PACKET_ID);
+        packet[18..22].copy_from_slice(&VECTOR_ROOT_E131_DATA.to_be_bytes());
+        packet[40..44].copy_from_slice(&VECTOR_E131_DATA_PACKET.to_be_bytes());
+        packet[109..111].copy_from_slice(&sync_address.to_be_bytes());
+        packet[113..115].copy_from_slice(&universe.to_be_bytes());
+        packet[123..125].copy_from_slice(&(data.len() as u16 + 1).to_be_bytes());
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    fn sacn_sync_packet() -> Vec<u8> {
+        let mut packet = vec![0; 49];
+        packet[4..16].copy_from_slice(ACN_PACKET_ID);
+        packet[18..22].copy_from_slice(&VECTOR_ROOT_E131_EXTENDED.to_be_bytes());
+        packet[40..44].copy_from_slice(&VECTOR_E131_EXTENDED_SYNCHRONIZATION.to_be_bytes());
+        packet
+    }
+
+    fn artnet_dmx_packet(universe: u16, data: &[u8]) -> Vec<u8> {
+        let mut packet = ARTNET_ID.to_vec();
+        packet.extend_from_slice(&ARTNET_OP_DMX.to_le_bytes());
+        packet.extend_from_slice(&[0, 14, 0, 0]); // version, sequence, physical
+        packet.extend_from_slice(&universe.to_le_bytes());
+        packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    #[test]
+    fn pixel_mapping() {
+        let position = |rotation, serpentine, i| {
+            let config = DmxConfig {
+                rotation,
+                serpentine,
+                ..Default::default()
+            };
+            config.pixel_position(DEFAULT_SIZE, i).unwrap()
+        };
+        assert_eq!(position(Rotation::R0, false, WIDTH + 1), [1, 1]);
+        assert_eq!(position(Rotation::R0, true, WIDTH + 1), [WIDTH - 2, 1]);
+        assert_eq!(position(Rotation::R90, false, 1), [WIDTH - 1, 1]);
+        assert_eq!(
+            position(Rotation::R90, true, HEIGHT),
+            [WIDTH - 2, HEIGHT - 1]
+        );
+        assert_eq!(position(Rotation::R180, false, 1), [WIDTH - 2, HEIGHT - 1]);
+        assert_eq!(position(Rotation::R270, false, 1), [0, HEIGHT - 2]);
+        assert_eq!(
+            DmxConfig::default().pixel_position(DEFAULT_SIZE, WIDTH * HEIGHT),
+            None
+        );
+
+        assert!(toml::from_str::<DmxConfig>("rotation = 45").is_err());
+    }
+
+    #[test]
+    fn invalid_packets() {
+        let packet = sacn_data_packet(1, 0, &[1, 2, 3]);
+        for len in 0..packet.len() {
+            assert!(parse_sacn(&packet[..len]).is_err());
+        }
+        let packet = artnet_dmx_packet(1, &[1, 2, 3]);
+        for len in 0..packet.len() {
+            assert!(parse_artnet(&packet[..len]).is_err());
+        }
+    }
+
+    /// Sends `packet` to one of `dmx`'s sockets, waits for it to arrive, and
+    /// steps `dmx` to receive it.
+    fn send(dmx: &mut Dmx, socket: fn(&Dmx) -> &UdpSocket, packet: &[u8]) {
+        let socket = socket(dmx);
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender
+            .send_to(packet, socket.local_addr().unwrap())
+            .unwrap();
+        socket.set_nonblocking(false).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        socket
+            .peek_from(&mut vec![0; 1 << 16])
+            .expect("packet should arrive");
+        socket.set_nonblocking(true).unwrap();
+        dmx.step(FullInput::default());
+    }
+
+    fn sacn(dmx: &Dmx) -> &UdpSocket {
+        dmx.sacn.socket.as_ref().unwrap()
+    }
+
+    fn artnet(dmx: &Dmx) -> &UdpSocket {
+        dmx.artnet.socket.as_ref().unwrap()
+    }
+
+    #[test]
+    fn loopback() {
+        let mut dmx = Dmx::new(DmxConfig {
+            sacn_addr: "127.0.0.1:0".to_string(),
+            artnet_addr: "127.0.0.1:0".to_string(),
+            ..Default::default()
+        });
+        dmx.step(FullInput::default());
+
+        // sACN across two universes, waiting for sync
+        let second_universe_pixel = [PIXELS_PER_UNIVERSE % WIDTH, PIXELS_PER_UNIVERSE / WIDTH];
+        for (universe, color) in [(1, 10), (2, 20)] {
+            send(&mut dmx, sacn, &sacn_data_packet(universe, 1, &[color; 6]));
+        }
+        assert!(dmx.is_receiving());
+        assert_eq!(dmx.pixels[0][0], BLACK);
+        send(&mut dmx, sacn, &sacn_sync_packet());
+        assert_eq!(dmx.pixels[0][..3], [Rgb([10; 3]), Rgb([10; 3]), BLACK]);
+        let [x, y] = second_universe_pixel;
+        assert_eq!(dmx.pixels[y][x], Rgb([20; 3]));
+        assert_eq!(dmx.counts.universes.len(), 2);
+
+        // Art-Net without sync
+        send(&mut dmx, artnet, &artnet_dmx_packet(1, &[1, 2, 3]));
+        assert_eq!(dmx.pixels[0][0], Rgb([1, 2, 3]));
+
+        // Art-Net with sync
+        let mut sync = ARTNET_ID.to_vec();
+        sync.extend_from_slice(&ARTNET_OP_SYNC.to_le_bytes());
+        sync.extend_from_slice(&[0, 14, 0, 0]);
+        send(&mut dmx, artnet, &sync);
+        send(&mut dmx, artnet, &artnet_dmx_packet(1, &[4, 5, 6]));
+        assert_eq!(dmx.pixels[0][0], Rgb([1, 2, 3]));
+        send(&mut dmx, artnet, &sync);
+        assert_eq!(dmx.pixels[0][0], Rgb([4, 5, 6]));
+    }
+
+    #[test]
+    fn multicast_groups() {
+        let mut dmx = Dmx::new(DmxConfig {
+            sacn_addr: "0.0.0.0:0".to_string(),
+            artnet_addr: "off".to_string(),
+            ..Default::default()
+        });
+        dmx.step(FullInput::default());
+        // The OS may refuse to join groups (for example, if there is no
+        // multicast route), so only the joins that succeeded are recorded.
+        let universes = |dmx: &Dmx| dmx.patches.iter().map(|p| p.universe).collect();
+        assert!(dmx.sacn_groups.is_subset(&universes(&dmx)));
+
+        // Resizing the wall joins the groups for the new patches.
+        let small = dmx.sacn_groups.clone();
+        dmx.set_size([WIDTH * 2, HEIGHT * 2]);
+        assert!(small.is_subset(&dmx.sacn_groups));
+        if !small.is_empty() {
+            assert!(dmx.sacn_groups.len() > small.len());
+        }
+        assert!(dmx.sacn_groups.is_subset(&universes(&dmx)));
+        dmx.set_size([WIDTH, HEIGHT]);
+        assert_eq!(dmx.sacn_groups, small);
+    }
+}
@@ -47,12 +47,31 @@ impl RedFlashAnimation {
 #[derive(Debug)]
 pub struct TurnAnimation {
     frame: u32,
+    /// Duration in seconds, which is chosen in the menu.
+    duration: f32,
     pub turn: Turn,
 }
-impl_animation_frame!(TurnAnimation, constants::animations::turn::DURATION);
+impl AnimationFrame for TurnAnimation {
+    const DURATION: f32 = constants::animations::turn::DURATION;
+
+    fn frame_number(&self) -> u32 {
+        self.frame
+    }
+    fn frame_number_mut(&mut self) -> &mut u32 {
+        &mut self.frame
+    }
+
+    fn t(&self) -> f32 {
+        self.frame as f32 / (self.duration * crate::FPS as f32)
+    }
+}
 impl TurnAnimation {
-    pub fn new(turn: Turn) -> Self {
-        Self { frame: 0, turn }
+    pub fn new(turn: Turn, duration: f32) -> Self {
+        Self {
+            frame: 0,
+            duration,
+            turn,
+        }
     }
 
     pub fn modify(&self, pos: Pos4) -> FloatPos4 {
@@ -1,3 +1,11 @@
+pub mod layout {
+    /// Size of the layout, which is centered on larger walls.
+    pub const SIZE: [usize; 2] = crate::DEFAULT_SIZE;
+    /// Width and height of the square at the top that the puzzle is projected
+    /// into. The filters are shown below it.
+    pub const PUZZLE_SIZE: usize = 32;
+}
+
 pub mod colors {
     use crate::color::*;
 
@@ -38,5 +46,38 @@ pub mod animations {
 
     pub mod turn {
         pub const DURATION: f32 = 0.5; // seconds
+
+        /// Speeds that can be chosen in the menu, and their durations.
+        pub const SPEED_NAMES: &[&str] = &["slow", "normal", "fast"];
+        pub const SPEED_DURATIONS: [f32; 3] = [1.0, DURATION, 0.25]; // seconds
     }
 }
+
+pub mod sounds {
+    use crate::sound::{Note, Sound, Wave};
+
+    /// Played when a facet turns.
+    pub const TURN: Sound = Sound(&[
+        Note::new(Wave::Noise, 6000.0, 0.04).volume(0.3).fade(0.0),
+        Note::new(Wave::Triangle, 330.0, 0.08)
+            .slide(440.0)
+            .fade(0.0),
+    ]);
+    /// Played when the whole puzzle rotates.
+    pub const ROTATE: Sound = Sound(&[Note::new(Wave::Triangle, 220.0, 0.15)
+        .slide(330.0)
+        .fade(0.0)]);
+    pub const INVALID: Sound = Sound(&[Note::new(Wave::Square, 110.0, 0.08).volume(0.3)]);
+    pub const SCRAMBLE: Sound = Sound(&[Note::new(Wave::Noise, 8000.0, 0.4)
+        .slide(1000.0)
+        .volume(0.4)
+        .fade(0.0)]);
+    pub const SOLVED: Sound = Sound(&[
+        Note::new(Wave::Square, 523.0, 0.1),
+        Note::new(Wave::Square, 659.0, 0.1),
+        Note::new(Wave::Square, 784.0, 0.1),
+        Note::new(Wave::Square, 1047.0, 0.1),
+        Note::new(Wave::Square, 784.0, 0.1),
+        Note::new(Wave::Square, 1047.0, 0.4).fade(0.0),
+    ]);
+}
@@ -1,17 +1,31 @@
-use std::time::Instant;
+use std::io;
+use std::time::{Duration, Instant};
 
 use flat_hypercube_logic::{Facet, Piece, Pos4, Puzzle, Turn};
-use rand::SeedableRng;
 
 mod animations;
 mod constants;
 mod input;
 
+const STATE_VERSION: u16 = 1;
+
+use crate::activities::ActivityInfo;
+use crate::activities::setting::{Setting, SettingValue};
+use crate::sound::Sound;
+use crate::state::{StateReader, StateWriter, invalid_data};
 use crate::{
     Activity, AnimationFrame, ArrayVec, BLACK, FrameBufferRect, FullInput, Rgb, WHITE, Widget,
     step_opt_animation,
 };
 
+pub const INFO: ActivityInfo = ActivityInfo {
+    id: "flat_hypercube",
+    name: "Flat Hypercube",
+    min_players: 1,
+    attract: false,
+    enabled_by_default: true,
+};
+
 #[derive(Debug, Default)]
 pub struct FlatHypercube {
     puzzle: Puzzle,
@@ -25,6 +39,8 @@ pub struct FlatHypercube {
     was_scrambled: bool,
     timer_start: Option<Instant>,
     timer_end: Option<Instant>,
+    /// Time of the last turn while the timer was running.
+    timer_last_turn: Option<Instant>,
     show_timer: bool,
 
     enable_filters: bool,
@@ -33,14 +49,48 @@ pub struct FlatHypercube {
 
     blink_anim: animations::BlinkAnimation,
     turn_anim: Option<animations::TurnAnimation>,
+
+    /// Sound effects started since they were last taken by the shell.
+    sounds: Vec<Sound>,
+    /// Notifications posted since they were last taken by the shell.
+    toasts: Vec<String>,
+
+    turn_speed: TurnSpeed,
+}
+
+/// Index into [`constants::animations::turn::SPEED_DURATIONS`].
+#[derive(Debug, Copy, Clone)]
+struct TurnSpeed(usize);
+impl Default for TurnSpeed {
+    fn default() -> Self {
+        Self(1)
+    }
 }
 
 impl FlatHypercube {
     fn do_turn_with_animation(&mut self, turn: Turn) {
+        let was_solved = self.puzzle.is_solved();
         if self.puzzle.do_turn(turn) {
-            self.turn_anim = Some(animations::TurnAnimation::new(turn));
-            if self.timer_start.is_some() && self.timer_end.is_none() && self.puzzle.is_solved() {
-                self.timer_end = Some(Instant::now());
+            if self.timer_start.is_some() && self.timer_end.is_none() {
+                self.timer_last_turn = Some(Instant::now());
+            }
+            let duration = constants::animations::turn::SPEED_DURATIONS[self.turn_speed.0];
+            self.turn_anim = Some(animations::TurnAnimation::new(turn, duration));
+            self.sounds.push(match turn.facet {
+                Some(_) => constants::sounds::TURN,
+                None => constants::sounds::ROTATE,
+            });
+            if self.puzzle.is_solved() && !was_solved {
+                self.sounds.push(constants::sounds::SOLVED);
+            }
+            if let Some(timer_start) = self.timer_start
+                && self.timer_end.is_none()
+                && self.puzzle.is_solved()
+            {
+                let now = Instant::now();
+                self.timer_end = Some(now);
+                let time = format_duration(now.saturating_duration_since(timer_start));
+                self.toasts.push(format!("solved {time}"));
             }
         }
     }
@@ -99,9 +149,9 @@ impl Widget<FullInput> for FlatHypercube {
             // Scramble
             if keys_pressed.y {
                 self.reset();
-                self.puzzle
-                    .scramble(&mut rand::rngs::SmallRng::from_os_rng());
+                self.puzzle.scramble(&mut crate::rng::new_rng());
                 self.was_scrambled = true;
+                self.sounds.push(constants::sounds::SCRAMBLE);
             }
         }
 
@@ -201,6 +251,9 @@ impl Widget<FullInput> for FlatHypercube {
                 _ if keys_pressed.b => self.input.input_facet(Facet::I),
                 _ => None,
             };
+            if (self.input.red_flash_anim.as_ref()).is_some_and(|anim| anim.frame_number() == 0) {
+                self.sounds.push(constants::sounds::INVALID);
+            }
             if let Some(turn) = maybe_turn {
                 self.do_turn_with_animation(turn);
                 self.undo_stack.push(turn);
@@ -259,13 +312,16 @@ impl Widget<FullInput> for FlatHypercube {
 
     fn draw(&self, fb: &mut FrameBufferRect<'_>) {
         use constants::colors;
+        use constants::layout::PUZZLE_SIZE;
+
+        let fb = &mut fb.centered(constants::layout::SIZE);
 
         // center
-        const CX: i8 = 16;
-        const CY: i8 = 16;
+        const CX: i8 = PUZZLE_SIZE as i8 / 2;
+        const CY: i8 = PUZZLE_SIZE as i8 / 2;
 
         const MAX_OVERLAPS: usize = 3;
-        let mut overlaps_buffer = [[ArrayVec::<u8, MAX_OVERLAPS>::new(); 32]; 32];
+        let mut overlaps_buffer = [[ArrayVec::<u8, MAX_OVERLAPS>::new(); PUZZLE_SIZE]; PUZZLE_SIZE];
 
         // Draw pieces and record stickers to `overlaps_buffer`
         for piece in Piece::iter_all() {
@@ -345,7 +401,7 @@ impl Widget<FullInput> for FlatHypercube {
 
         // Draw filters UI
         if let Some(editing_filters) = &self.editing_filters {
-            let mut fb = fb.with_offset([0, 32]);
+            let mut fb = fb.with_offset([0, PUZZLE_SIZE as isize]);
             let cx = 7;
             for (i, rule) in self.filters.rules.iter().enumerate() {
                 let y = i * 3;
@@ -399,11 +455,7 @@ impl Widget<FullInput> for FlatHypercube {
         if self.show_timer {
             let timer_end = self.timer_end.unwrap_or_else(Instant::now);
             let timer_start = self.timer_start.unwrap_or(timer_end);
-            let duration = timer_end.saturating_duration_since(timer_start);
-            let centis = duration.subsec_millis() / 10;
-            let seconds = duration.as_secs() % 60;
-            let minutes = duration.as_secs() / 60;
-            let text = format!("{minutes}:{seconds:02}.{centis:02}");
+            let text = format_duration(timer_end.saturating_duration_since(timer_start));
             let text_width = crate::text::width(&text);
             crate::text::draw(
                 &text,
@@ -421,14 +473,147 @@ impl Widget<FullInput> for FlatHypercube {
     }
 }
 
+/// Formats a solve time as `m:ss.cc`.
+fn format_duration(duration: Duration) -> String {
+    let centis = duration.subsec_millis() / 10;
+    let seconds = duration.as_secs() % 60;
+    let minutes = duration.as_secs() / 60;
+    format!("{minutes}:{seconds:02}.{centis:02}")
+}
+
 impl Activity for FlatHypercube {
+    fn info(&self) -> &'static ActivityInfo {
+        &INFO
+    }
+
     fn reset(&mut self) {
-        *self = Self::default();
+        *self = Self {
+            turn_speed: self.turn_speed,
+            ..Self::default()
+        };
     }
 
     fn menu_image(&self) -> crate::StaticImage {
         include_rgba_image!("activities/flat_hypercube.rgba")
     }
+
+    fn take_sounds(&mut self) -> Vec<Sound> {
+        std::mem::take(&mut self.sounds)
+    }
+
+    fn take_toasts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.toasts)
+    }
+
+    fn settings(&self) -> Vec<Setting> {
+        let names = constants::animations::turn::SPEED_NAMES;
+        vec![Setting::choice("turns", self.turn_speed.0, names)]
+    }
+
+    fn set_setting(&mut self, _index: usize, value: SettingValue) {
+        self.turn_speed = TurnSpeed(value.get() as usize);
+    }
+
+    /// Saves the puzzle, move history, timer, and filters, but not partial
+    /// input or the filter editor.
+    fn save_state(&self) -> Option<Vec<u8>> {
+        let mut w = StateWriter::new(STATE_VERSION);
+
+        for facet in self.puzzle.stickers {
+            w.u8(facet.id());
+        }
+
+        for stack in [&self.undo_stack, &self.redo_stack] {
+            w.len(stack.len());
+            for &turn in stack {
+                write_turn(&mut w, turn);
+            }
+        }
+        match self.last_move {
+            None => w.bool(false),
+            Some(turn) => {
+                w.bool(true);
+                write_turn(&mut w, turn);
+            }
+        }
+
+        w.bool(self.was_scrambled);
+        // The timer is stored as elapsed time, since `Instant`s are meaningless
+        // after a restart. A running timer is stored as of the last turn so
+        // that the state only changes when the puzzle does.
+        w.u8(match (self.timer_start, self.timer_end) {
+            (None, _) => 0,
+            (Some(_), None) => 1,
+            (Some(_), Some(_)) => 2,
+        });
+        let elapsed = match self.timer_start {
+            Some(start) => (self.timer_end.or(self.timer_last_turn))
+                .unwrap_or(start)
+                .saturating_duration_since(start),
+            None => Duration::ZERO,
+        };
+        w.u64(elapsed.as_millis() as u64);
+        w.bool(self.show_timer);
+
+        w.len(self.filters.rules.len());
+        for rule in self.filters.rules.iter() {
+            w.u8(rule.must_have);
+            w.u8(rule.must_not_have);
+            w.bool(rule.enabled);
+        }
+
+        Some(w.finish())
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> io::Result<()> {
+        let mut r = StateReader::new(state, STATE_VERSION)?;
+        self.reset();
+
+        for facet in &mut self.puzzle.stickers {
+            *facet = read_facet(&mut r)?;
+        }
+
+        for stack in [&mut self.undo_stack, &mut self.redo_stack] {
+            *stack = (0..r.len()?)
+                .map(|_| read_turn(&mut r))
+                .collect::<io::Result<_>>()?;
+        }
+        self.last_move = match r.bool()? {
+            false => None,
+            true => Some(read_turn(&mut r)?),
+        };
+
+        self.was_scrambled = r.bool()?;
+        let timer_state = r.u8()?;
+        let elapsed = Duration::from_millis(r.u64()?);
+        let now = Instant::now();
+        let start = now.checked_sub(elapsed).unwrap_or(now);
+        (self.timer_start, self.timer_end, self.timer_last_turn) = match timer_state {
+            0 => (None, None, None),
+            1 => (Some(start), None, Some(now)),
+            2 => (Some(start), Some(now), None),
+            other => return Err(invalid_data(format!("invalid timer state {other}"))),
+        };
+        self.show_timer = r.bool()?;
+
+        let rule_count = r.len()?;
+        if rule_count == 0 {
+            return Err(invalid_data("no filter rules"));
+        }
+        self.filters.rules = ArrayVec::new();
+        for _ in 0..rule_count {
+            let rule = FilterRule {
+                must_have: r.u8()?,
+                must_not_have: r.u8()?,
+                enabled: r.bool()?,
+            };
+            if self.filters.rules.try_push(rule).is_err() {
+                return Err(invalid_data("too many filter rules"));
+            }
+        }
+
+        r.finish()
+    }
 }
 
 #[derive(Debug, Default)]
@@ -583,6 +768,32 @@ pub struct EditingFilters {
     pub index: usize,
 }
 
+fn write_turn(w: &mut StateWriter, turn: Turn) {
+    w.u8(turn.facet.map_or(u8::MAX, |f| f.id()));
+    w.u8(turn.from.id());
+    w.u8(turn.to.id());
+}
+fn read_turn(r: &mut StateReader<'_>) -> io::Result<Turn> {
+    let facet = match r.u8()? {
+        u8::MAX => None,
+        id => Some(facet_from_id(id)?),
+    };
+    Ok(Turn {
+        facet,
+        from: read_facet(r)?,
+        to: read_facet(r)?,
+    })
+}
+fn read_facet(r: &mut StateReader<'_>) -> io::Result<Facet> {
+    facet_from_id(r.u8()?)
+}
+fn facet_from_id(id: u8) -> io::Result<Facet> {
+    match Facet::ALL.get(id as usize) {
+        Some(&facet) => Ok(facet),
+        None => Err(invalid_data(format!("invalid facet {id}"))),
+    }
+}
+
 fn draw_compass(
     cx: usize,
     cy: usize,
@@ -1,8 +1,20 @@
 use std::collections::HashSet;
+use std::io;
 
-use rand::{Rng, SeedableRng};
+use rand::Rng;
 
-use crate::{Activity, BLACK, FPS, FullInput, HEIGHT, Rgb, WHITE, WIDTH, Widget};
+use crate::activities::ActivityInfo;
+use crate::activities::setting::{Setting, SettingValue};
+use crate::state::{self, StateReader, StateWriter};
+use crate::{Activity, BLACK, DEFAULT_SIZE, FPS, FullInput, Rgb, WHITE, Widget};
+
+pub const INFO: ActivityInfo = ActivityInfo {
+    id: "life",
+    name: "Life",
+    min_players: 0,
+    attract: true,
+    enabled_by_default: true,
+};
 
 const TRAIL_BRIGHTNESS: f32 = 0.625;
 const TRAIL_LIMIT: u8 = 30;
@@ -11,47 +23,122 @@ const FAST_RATE: usize = 1;
 const NORMAL_RATE: usize = 3;
 const SLOW_RATE: usize = 10;
 
+/// Speeds that can be chosen in the menu, and their rates.
+const SPEED_NAMES: &[&str] = &["slow", "normal", "fast"];
+const SPEED_RATES: [usize; 3] = [SLOW_RATE, NORMAL_RATE, FAST_RATE];
+
 const RESET_TIME: usize = FPS / NORMAL_RATE * 2; // 2 seconds
 
+const STATE_VERSION: u16 = 2;
+
 pub struct Life {
-    /// State of each cell
+    /// Width and height of the board, which covers the whole frame.
+    size: [usize; 2],
+    /// State of each cell, in row-major order
     ///
     /// - 0 = dead
     /// - 1 = alive
     /// - 2.. = frames since last alive
-    cells: [[u8; WIDTH]; HEIGHT],
+    cells: Vec<u8>,
 
     frame: usize,
 
     rainbow: super::rainbow::Rainbow,
 
-    history: HashSet<[[u8; WIDTH]; HEIGHT]>,
+    history: HashSet<Vec<u8>>,
     reset_timer: Option<usize>,
+
+    /// Index into [`SPEED_RATES`] of the rate when neither L nor R is held.
+    speed: usize,
+    /// Whether dead cells leave a fading trail.
+    trails: bool,
 }
 
 impl Default for Life {
     fn default() -> Self {
-        Self {
-            cells: [[0; WIDTH]; HEIGHT],
-
-            frame: 0,
-
-            rainbow: super::rainbow::Rainbow::default(),
-
-            history: HashSet::new(),
-            reset_timer: None,
-        }
+        Self::with_size(DEFAULT_SIZE)
     }
 }
 
 impl Activity for Life {
+    fn info(&self) -> &'static ActivityInfo {
+        &INFO
+    }
+
     fn reset(&mut self) {
-        *self = Self::default();
+        *self = Self {
+            speed: self.speed,
+            trails: self.trails,
+            ..Self::with_size(self.size)
+        };
+    }
+
+    fn set_size(&mut self, size: [usize; 2]) {
+        self.size = size;
+        Activity::reset(self);
     }
 
     fn menu_image(&self) -> crate::StaticImage {
         include_rgba_image!("activities/life.rgba")
     }
+
+    fn settings(&self) -> Vec<Setting> {
+        vec![
+            Setting::choice("speed", self.speed, SPEED_NAMES),
+            Setting::toggle("trails", self.trails),
+        ]
+    }
+
+    fn set_setting(&mut self, index: usize, value: SettingValue) {
+        match index {
+            0 => self.speed = value.get() as usize,
+            _ => self.trails = value.get() != 0,
+        }
+    }
+
+    /// Starts from a random board, which is randomized again whenever it
+    /// settles into a cycle.
+    fn start_attract_mode(&mut self) {
+        self.reset_random();
+    }
+
+    /// Saves everything except the history used for cycle detection, which
+    /// can grow to several megabytes. Without it, a cycle is detected one
+    /// period later than it would have been.
+    fn save_state(&self) -> Option<Vec<u8>> {
+        let mut w = StateWriter::new(STATE_VERSION);
+        w.u16(self.size[0] as u16);
+        w.u16(self.size[1] as u16);
+        w.bytes(&self.cells);
+        w.u32(self.frame as u32);
+        w.u32(self.rainbow.frame as u32);
+        match self.reset_timer {
+            None => w.bool(false),
+            Some(t) => {
+                w.bool(true);
+                w.u32(t as u32);
+            }
+        }
+        Some(w.finish())
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> io::Result<()> {
+        let mut r = StateReader::new(state, STATE_VERSION)?;
+        let size = [r.u16()? as usize, r.u16()? as usize];
+        if size != self.size {
+            return Err(state::invalid_data("board was saved at a different size"));
+        }
+        let cells = r.bytes(self.cells.len())?;
+        self.cells.copy_from_slice(cells);
+        self.frame = r.u32()? as usize;
+        self.rainbow.frame = r.u32()? as usize % super::rainbow::duration_frames();
+        self.reset_timer = match r.bool()? {
+            false => None,
+            true => Some(r.u32()? as usize),
+        };
+        self.history.clear();
+        r.finish()
+    }
 }
 
 impl Widget<FullInput> for Life {
@@ -72,7 +159,7 @@ impl Widget<FullInput> for Life {
         let frames_per_step = match (held.l, held.r) {
             (true, false) => SLOW_RATE,
             (false, true) => FAST_RATE,
-            _ => NORMAL_RATE,
+            _ => SPEED_RATES[self.speed],
         };
 
         self.frame += 1;
@@ -81,9 +168,10 @@ impl Widget<FullInput> for Life {
         }
         self.frame = 0;
 
-        let mut new_cells = self.cells;
-        for y in 0..64_i8 {
-            for x in 0..32_i8 {
+        let mut new_cells = self.cells.clone();
+        let [width, height] = self.size.map(|n| n as isize);
+        for y in 0..height {
+            for x in 0..width {
                 let center = self.cell(x, y);
                 let new_state = transition(center, {
                     (-1..=1)
@@ -92,7 +180,7 @@ impl Widget<FullInput> for Life {
                         .count()
                         - center as usize
                 });
-                let state = &mut new_cells[y as usize][x as usize];
+                let state = &mut new_cells[(y * width + x) as usize];
                 if new_state {
                     *state = 1;
                 } else if *state != 0 {
@@ -109,7 +197,7 @@ impl Widget<FullInput> for Life {
                 if *reset_timer == 0 {
                     self.reset_random();
                 }
-            } else if !self.history.insert(self.cells) {
+            } else if !self.history.insert(self.cells.clone()) {
                 *reset_timer = RESET_TIME;
             }
         }
@@ -117,25 +205,51 @@ impl Widget<FullInput> for Life {
 
     fn draw(&self, fb: &mut crate::FrameBufferRect<'_>) {
         self.rainbow.draw(fb);
-        fb.fill_with_fn(|[x, y], rainbow_color| get_color(self.cells[y][x], rainbow_color));
+        let [width, height] = self.size;
+        fb.fill_with_fn(|[x, y], rainbow_color| {
+            if x >= width || y >= height {
+                return BLACK;
+            }
+            match self.cells[y * width + x] {
+                2.. if !self.trails => BLACK,
+                state => get_color(state, rainbow_color),
+            }
+        });
     }
 }
 
 impl Life {
-    fn cell(&self, x: i8, y: i8) -> bool {
-        self.cells[y.rem_euclid(HEIGHT as i8) as usize][x.rem_euclid(WIDTH as i8) as usize] == 1
+    fn with_size(size: [usize; 2]) -> Self {
+        Self {
+            size,
+            cells: vec![0; size[0] * size[1]],
+
+            frame: 0,
+
+            rainbow: super::rainbow::Rainbow::default(),
+
+            history: HashSet::new(),
+            reset_timer: None,
+
+            speed: 1,
+            trails: true,
+        }
+    }
+
+    fn cell(&self, x: isize, y: isize) -> bool {
+        let [width, height] = self.size.map(|n| n as isize);
+        self.cells[(y.rem_euclid(height) * width + x.rem_euclid(width)) as usize] == 1
     }
 
     fn reset(&mut self) {
         self.history.clear();
-        self.cells = [[0; WIDTH]; HEIGHT];
+        self.cells.fill(0);
         self.reset_timer = None;
     }
     fn reset_random(&mut self) {
         self.history.clear();
-        self.cells = rand::rngs::SmallRng::from_os_rng()
-            .random::<[[bool; WIDTH]; HEIGHT]>()
-            .map(|row| row.map(|cell| cell as u8));
+        let mut rng = crate::rng::new_rng();
+        self.cells.fill_with(|| rng.random::<bool>() as u8);
         self.reset_timer = Some(0);
     }
 }
@@ -1,15 +1,294 @@
-use crate::Activity;
+//! Activities and the registry that lists them.
+//!
+//! Besides the built-in activities in [`REGISTRY`], there are activities
+//! written as scripts (see [`script`]). Which activities appear in the menu,
+//! and in what order, is configured in `activities.toml` within
+//! [`storage::dir()`]:
+//!
+//! ```toml
+//! order = ["tetris", "life"] # first in the menu, followed by the rest
+//! hidden = ["flat_hypercube"] # not in the menu
+//! shown = ["pixel_receiver"] # in the menu even if disabled by default
+//! pinned = ["rainbow"] # first in the menu, even if there aren't enough players
+//! ```
+//!
+//! [`storage::dir()`]: crate::storage::dir
 
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Activity, storage};
+
+pub mod dmx;
 pub mod flat_hypercube;
 pub mod life;
+mod net;
+pub mod pixel_receiver;
 pub mod rainbow;
+pub mod script;
+pub mod setting;
 pub mod tetris;
 
+/// File name of the menu configuration within [`storage::dir()`].
+pub const FILE_NAME: &str = "activities.toml";
+
+/// Metadata that an activity declares about itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivityInfo {
+    /// Unique name for the activity, used to identify its saved state and to
+    /// refer to it in configuration files.
+    pub id: &'static str,
+    /// Human-readable name.
+    pub name: &'static str,
+    /// Number of players needed for the activity to be selectable in the
+    /// menu.
+    pub min_players: usize,
+    /// Whether the activity can run unattended in attract mode (see
+    /// [`Activity::start_attract_mode()`]).
+    pub attract: bool,
+    /// Whether the activity is in the menu unless configured otherwise.
+    pub enabled_by_default: bool,
+}
+
+/// Activity in the registry.
+pub struct Registration {
+    pub info: &'static ActivityInfo,
+    pub new: fn() -> Box<dyn Activity>,
+}
+
+/// Every activity, in their default menu order.
+pub const REGISTRY: &[Registration] = &[
+    Registration {
+        info: &rainbow::INFO,
+        new: || Box::new(rainbow::Rainbow::default()),
+    },
+    Registration {
+        info: &tetris::INFO,
+        new: || Box::new(tetris::Tetris::default()),
+    },
+    Registration {
+        info: &life::INFO,
+        new: || Box::new(life::Life::default()),
+    },
+    Registration {
+        info: &flat_hypercube::INFO,
+        new: || Box::new(flat_hypercube::FlatHypercube::default()),
+    },
+    Registration {
+        info: &pixel_receiver::INFO,
+        new: || Box::new(pixel_receiver::PixelReceiver::default()),
+    },
+    Registration {
+        info: &dmx::INFO,
+        new: || Box::new(dmx::Dmx::default()),
+    },
+];
+
+/// Returns the registration of the activity with ID `id`.
+pub fn get(id: &str) -> Option<&'static Registration> {
+    REGISTRY.iter().find(|r| r.info.id == id)
+}
+
+/// Constructs every activity in the registry, in their default order.
 pub fn init_activities() -> Vec<Box<dyn Activity>> {
-    vec![
-        Box::new(rainbow::Rainbow::default()),
-        Box::new(tetris::Tetris::default()),
-        Box::new(life::Life::default()),
-        Box::new(flat_hypercube::FlatHypercube::default()),
-    ]
+    REGISTRY.iter().map(|r| (r.new)()).collect()
+}
+
+/// Constructs the activities in the menu, including scripts from
+/// `script_dir`. If the configuration leaves the menu empty, the default menu
+/// is used instead.
+pub fn init_menu(config: &MenuConfig, script_dir: &Path) -> Vec<Box<dyn Activity>> {
+    let mut scripts = script::load_dir(script_dir);
+    let available: Vec<&'static ActivityInfo> = (REGISTRY.iter().map(|r| r.info))
+        .chain(scripts.iter().map(|s| s.info()))
+        .collect();
+    for id in config.ids() {
+        if !available.iter().any(|info| info.id == id) {
+            eprintln!("Unknown activity {id:?} in menu configuration");
+        }
+    }
+    let mut menu = config.menu(&available);
+    if menu.is_empty() {
+        eprintln!("Menu configuration hides every activity; using the default menu");
+        menu = MenuConfig::default().menu(&available);
+    }
+    menu.into_iter()
+        .filter_map(|info| match get(info.id) {
+            Some(r) => Some((r.new)()),
+            None => {
+                let i = scripts.iter().position(|s| s.info().id == info.id)?;
+                Some(Box::new(scripts.swap_remove(i)) as Box<dyn Activity>)
+            }
+        })
+        .collect()
+}
+
+/// Contents of the menu configuration file.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MenuConfig {
+    /// IDs of activities to put first in the menu, in order. The rest follow
+    /// in their default order.
+    pub order: Vec<String>,
+    /// IDs of activities to leave out of the menu.
+    pub hidden: Vec<String>,
+    /// IDs of activities to put in the menu even if they are disabled by
+    /// default.
+    pub shown: Vec<String>,
+    /// IDs of activities to put at the very start of the menu and never skip
+    /// for lack of players. Pinned activities cannot be hidden.
+    pub pinned: Vec<String>,
+}
+
+impl MenuConfig {
+    /// Loads the configuration from `dir`, returning the default configuration
+    /// if the file does not exist.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        storage::load_toml(dir, FILE_NAME)
+    }
+
+    /// Returns every activity ID mentioned in the configuration.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        [&self.order, &self.hidden, &self.shown, &self.pinned]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Returns whether the activity with ID `id` is pinned.
+    pub fn is_pinned(&self, id: &str) -> bool {
+        self.pinned.iter().any(|p| p == id)
+    }
+
+    /// Returns the activities in the menu, in order, given the activities
+    /// that are available in their default order.
+    pub fn menu(&self, available: &[&'static ActivityInfo]) -> Vec<&'static ActivityInfo> {
+        let is_shown = |info: &ActivityInfo| {
+            let contains = |list: &[String]| list.iter().any(|s| s == info.id);
+            self.is_pinned(info.id)
+                || (!contains(&self.hidden) && (info.enabled_by_default || contains(&self.shown)))
+        };
+        let get = |id: &String| available.iter().copied().find(|info| info.id == id);
+        let listed = (self.pinned.iter().chain(&self.order)).filter_map(get);
+        let mut ret: Vec<&ActivityInfo> = vec![];
+        for info in listed.chain(available.iter().copied()) {
+            if is_shown(info) && !ret.iter().any(|i| i.id == info.id) {
+                ret.push(info);
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu_ids(config: &MenuConfig) -> Vec<&'static str> {
+        let available: Vec<_> = REGISTRY.iter().map(|r| r.info).collect();
+        config.menu(&available).iter().map(|info| info.id).collect()
+    }
+
+    #[test]
+    fn registry() {
+        for (i, r) in REGISTRY.iter().enumerate() {
+            assert_eq!((r.new)().info(), r.info);
+            assert!(
+                REGISTRY[..i].iter().all(|other| other.info.id != r.info.id),
+                "duplicate ID {}",
+                r.info.id,
+            );
+        }
+    }
+
+    #[test]
+    fn small_sizes() {
+        use crate::{Buttons, FPS, FrameBuffer, FrameBufferRect, FullInput, MAX_PLAYERS};
+
+        let sizes = [
+            [1, 1],
+            [4, 4],
+            [8, 8],
+            [16, 16],
+            [20, 10],
+            [100, 20],
+            [64, 32],
+        ];
+        for r in REGISTRY {
+            // Activities that bind network sockets when stepped are only drawn.
+            let steps = match r.info.id {
+                "dmx" | "pixel_receiver" => 0,
+                _ => 3 * FPS,
+            };
+            for size in sizes {
+                let mut activity = (r.new)();
+                activity.set_size(size);
+                let mut fb = FrameBuffer::new(size);
+                for i in 0..steps {
+                    // Press an arbitrary combination of buttons every other
+                    // frame.
+                    let bits = if i % 2 == 0 {
+                        (i / 2 * 7919 % 0x1000) as u16
+                    } else {
+                        0
+                    };
+                    let input = Some(Buttons::from_bits(bits).into());
+                    let prev = Some(Buttons::default().into());
+                    activity.step(FullInput::new([input; MAX_PLAYERS], [prev; MAX_PLAYERS]));
+                    activity.draw(&mut FrameBufferRect::new(&mut fb));
+                }
+                activity.draw(&mut FrameBufferRect::new(&mut fb));
+            }
+        }
+    }
+
+    #[test]
+    fn menu() {
+        let default_menu = menu_ids(&MenuConfig::default());
+        let all: Vec<_> = REGISTRY.iter().map(|r| r.info.id).collect();
+        let enabled: Vec<_> = (REGISTRY.iter())
+            .filter(|r| r.info.enabled_by_default)
+            .map(|r| r.info.id)
+            .collect();
+        assert_eq!(default_menu, enabled);
+        assert!(!default_menu.contains(&"pixel_receiver"));
+        assert!(!default_menu.contains(&"dmx"));
+
+        let config: MenuConfig = toml::from_str(
+            r#"
+            order = ["life", "tetris"]
+            hidden = ["dmx", "rainbow", "tetris"]
+            pinned = ["flat_hypercube", "rainbow"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(menu_ids(&config), ["flat_hypercube", "rainbow", "life"]);
+        assert!(config.is_pinned("rainbow"));
+        assert!(!config.is_pinned("life"));
+
+        // Activities that are disabled by default can be shown, but hiding
+        // takes precedence.
+        let config: MenuConfig = toml::from_str(
+            r#"
+            hidden = ["tetris", "dmx"]
+            shown = ["pixel_receiver", "dmx"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            menu_ids(&config),
+            ["rainbow", "life", "flat_hypercube", "pixel_receiver"],
+        );
+
+        let all: Vec<_> = all.iter().map(|id| id.to_string()).collect();
+        let config = MenuConfig {
+            hidden: all,
+            ..MenuConfig::default()
+        };
+        assert!(menu_ids(&config).is_empty());
+        let activities = init_menu(&config, Path::new("/nonexistent"));
+        assert_eq!(activities.len(), default_menu.len());
+    }
 }
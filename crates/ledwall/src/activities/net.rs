@@ -0,0 +1,86 @@
+//! Sockets shared by activities that receive data over the network.
+
+use std::io;
+use std::net::UdpSocket;
+
+use crate::FPS;
+
+/// Time between attempts to listen on an address that failed.
+pub(super) const RETRY_INTERVAL: usize = 5 * FPS;
+
+const MAX_PACKET_LEN: usize = 1 << 16;
+
+/// Returns `$<var>` if set, otherwise all interfaces on `default_port`, or
+/// `None` if the variable is `off`.
+///
+/// Unlike [`crate::remote`], pixel protocols are on by default because the
+/// activities that use them are useless without them, and senders can only
+/// change what those activities display.
+pub(super) fn addr_from_env(var: &str, default_port: u16) -> Option<String> {
+    match std::env::var(var) {
+        Ok(addr) if addr.is_empty() || addr == "off" => None,
+        Ok(addr) => Some(addr),
+        Err(_) => Some(format!("0.0.0.0:{default_port}")),
+    }
+}
+
+/// Socket that is bound the first time it is needed.
+pub(super) struct Listener<T> {
+    protocol: &'static str,
+    addr: Option<String>,
+    pub(super) socket: Option<T>,
+    /// Frame on which to retry binding after an error.
+    retry_frame: usize,
+}
+
+impl<T> Listener<T> {
+    pub(super) fn new(protocol: &'static str, addr: Option<String>) -> Self {
+        Self {
+            protocol,
+            addr,
+            socket: None,
+            retry_frame: 0,
+        }
+    }
+
+    /// Returns the socket, binding it using `bind` if it isn't already bound.
+    pub(super) fn get(
+        &mut self,
+        frame: usize,
+        bind: impl FnOnce(&str) -> io::Result<T>,
+    ) -> Option<&mut T> {
+        if self.socket.is_none() && frame >= self.retry_frame {
+            let addr = self.addr.as_deref()?;
+            match bind(addr) {
+                Ok(socket) => {
+                    println!("Listening for {} on {addr}", self.protocol);
+                    self.socket = Some(socket);
+                }
+                Err(e) => {
+                    eprintln!("Error listening for {} on {addr}: {e}", self.protocol);
+                    self.retry_frame = frame + RETRY_INTERVAL;
+                }
+            }
+        }
+        self.socket.as_mut()
+    }
+}
+
+/// Receives all pending packets from a nonblocking socket.
+pub(super) fn recv_packets(socket: &UdpSocket, protocol: &str) -> Vec<Vec<u8>> {
+    let mut packets = vec![];
+    let mut buf = vec![0; MAX_PACKET_LEN];
+    loop {
+        match socket.recv_from(&mut buf) {
+            Ok((len, _addr)) => packets.push(buf[..len].to_vec()),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            // On some platforms, ICMP errors from earlier sends show up here.
+            Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+            Err(e) => {
+                eprintln!("Error receiving {protocol} packet: {e}");
+                break;
+            }
+        }
+    }
+    packets
+}
@@ -0,0 +1,366 @@
+//! Activity that displays pixels sent over the network using [Open Pixel
+//! Control] (OPC) or [TPM2.net], so that external lighting software can drive
+//! the LED wall.
+//!
+//! Pixels are numbered in row-major order starting from the top left, so pixel
+//! `x + y * width` is at `(x, y)`, where `width` is the width of the whole
+//! wall (32 for a single panel). Each pixel is 3 bytes (RGB).
+//!
+//! OPC messages on channel 0 (broadcast) and channel 1 are accepted. TPM2.net
+//! frames may be split across multiple packets, numbered starting from 1, in
+//! which case every packet except the last must be the same size.
+//!
+//! [Open Pixel Control]: http://openpixelcontrol.org/
+//! [TPM2.net]: https://gist.github.com/jblang/89e24e2655be6c463c56
+
+use std::io::{self, Read};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+
+use super::ActivityInfo;
+use super::net::{Listener, addr_from_env, recv_packets};
+use crate::{
+    Activity, BLACK, DEFAULT_SIZE, FPS, FrameBuffer, FrameBufferRect, FullInput, Rgb, StaticImage,
+    Widget,
+};
+
+pub const INFO: ActivityInfo = ActivityInfo {
+    id: "pixel_receiver",
+    name: "Pixel Receiver",
+    min_players: 0,
+    attract: false,
+    enabled_by_default: false,
+};
+
+/// Environment variable that sets the address to listen for OPC connections
+/// on, or `off` to disable OPC.
+pub const OPC_ADDR_ENV_VAR: &str = "LEDWALL_OPC";
+/// Default TCP port for OPC.
+pub const OPC_DEFAULT_PORT: u16 = 7890;
+
+/// Environment variable that sets the address to listen for TPM2.net packets
+/// on, or `off` to disable TPM2.net.
+pub const TPM2_ADDR_ENV_VAR: &str = "LEDWALL_TPM2";
+/// Default UDP port for TPM2.net.
+pub const TPM2_DEFAULT_PORT: u16 = 65506;
+
+/// Time after the last pixels were received before showing the fallback image.
+const TIMEOUT: usize = 2 * FPS;
+/// Duration of one pulse of the fallback image.
+const FALLBACK_PULSE_DURATION: f32 = 2.0; // seconds
+
+const OPC_CMD_SET_PIXELS: u8 = 0;
+
+const TPM2_START: u8 = 0x9C;
+const TPM2_DATA: u8 = 0xDA;
+const TPM2_END: u8 = 0x36;
+
+pub struct PixelReceiver {
+    opc: Listener<OpcServer>,
+    tpm2: Listener<UdpSocket>,
+
+    pixels: FrameBuffer,
+    /// Payload length of the first packet of the current TPM2.net frame, used
+    /// to find the position of later packets.
+    tpm2_packet_len: usize,
+
+    frame: usize,
+    last_received_frame: Option<usize>,
+}
+
+impl Default for PixelReceiver {
+    fn default() -> Self {
+        Self::new(
+            addr_from_env(OPC_ADDR_ENV_VAR, OPC_DEFAULT_PORT),
+            addr_from_env(TPM2_ADDR_ENV_VAR, TPM2_DEFAULT_PORT),
+        )
+    }
+}
+
+impl PixelReceiver {
+    /// Constructs a receiver that listens for OPC and TPM2.net on the given
+    /// addresses, or not at all for `None`.
+    ///
+    /// Nothing is bound until the activity is first stepped.
+    pub fn new(opc_addr: Option<String>, tpm2_addr: Option<String>) -> Self {
+        Self {
+            opc: Listener::new("OPC", opc_addr),
+            tpm2: Listener::new("TPM2.net", tpm2_addr),
+
+            pixels: FrameBuffer::new(DEFAULT_SIZE),
+            tpm2_packet_len: 0,
+
+            frame: 0,
+            last_received_frame: None,
+        }
+    }
+
+    fn is_receiving(&self) -> bool {
+        self.last_received_frame
+            .is_some_and(|f| self.frame - f < TIMEOUT)
+    }
+
+    /// Sets pixels starting from pixel `start`.
+    fn set_pixels(&mut self, start: usize, data: &[u8]) {
+        let pixels = self.pixels.as_flattened_mut();
+        for (pixel, &[r, g, b]) in std::iter::zip(pixels.iter_mut().skip(start), data.as_chunks().0)
+        {
+            *pixel = Rgb([r, g, b]);
+        }
+        self.last_received_frame = Some(self.frame);
+    }
+
+    fn receive_opc(&mut self) {
+        let Some(server) = self.opc.get(self.frame, |addr| {
+            let listener = TcpListener::bind(addr)?;
+            listener.set_nonblocking(true)?;
+            Ok(OpcServer {
+                listener,
+                clients: vec![],
+            })
+        }) else {
+            return;
+        };
+
+        loop {
+            match server.listener.accept() {
+                Ok((stream, _addr)) => match stream.set_nonblocking(true) {
+                    Ok(()) => server.clients.push(OpcClient {
+                        stream,
+                        buf: vec![],
+                    }),
+                    Err(e) => eprintln!("Error configuring OPC connection: {e}"),
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    eprintln!("Error accepting OPC connection: {e}");
+                    break;
+                }
+            }
+        }
+
+        let mut messages = vec![];
+        server.clients.retain_mut(|client| {
+            let connected = client.read();
+            while let Some(message) = client.next_message() {
+                messages.push(message);
+            }
+            connected
+        });
+        for (channel, command, data) in messages {
+            if matches!(channel, 0 | 1) && command == OPC_CMD_SET_PIXELS {
+                self.set_pixels(0, &data);
+            }
+        }
+    }
+
+    fn receive_tpm2(&mut self) {
+        let Some(socket) = self.tpm2.get(self.frame, |addr| {
+            let socket = UdpSocket::bind(addr)?;
+            socket.set_nonblocking(true)?;
+            Ok(socket)
+        }) else {
+            return;
+        };
+
+        for packet in recv_packets(socket, "TPM2.net") {
+            if let Err(e) = self.handle_tpm2_packet(&packet) {
+                eprintln!("Invalid TPM2.net packet: {e}");
+            }
+        }
+    }
+
+    fn handle_tpm2_packet(&mut self, packet: &[u8]) -> Result<(), String> {
+        let Some((header, rest)) = packet.split_first_chunk::<6>() else {
+            return Err("packet too short".to_string());
+        };
+        let [start, packet_type, l0, l1, packet_number, _packet_count] = *header;
+        if start != TPM2_START {
+            return Err("not a TPM2.net packet".to_string());
+        }
+        if packet_type != TPM2_DATA {
+            return Ok(()); // ignore commands
+        }
+        let len = u16::from_be_bytes([l0, l1]) as usize;
+        if rest.len() != len + 1 || rest[len] != TPM2_END {
+            return Err("wrong packet length".to_string());
+        }
+
+        if packet_number <= 1 {
+            self.tpm2_packet_len = len;
+        }
+        let start = packet_number.saturating_sub(1) as usize * self.tpm2_packet_len / 3;
+        self.set_pixels(start, &rest[..len]);
+        Ok(())
+    }
+}
+
+impl Widget<FullInput> for PixelReceiver {
+    fn step(&mut self, _input: FullInput) {
+        self.frame += 1;
+        self.receive_opc();
+        self.receive_tpm2();
+    }
+
+    fn draw(&self, fb: &mut FrameBufferRect<'_>) {
+        if self.is_receiving() {
+            fb.fill_with_fn(|[x, y], _| self.pixels[y][x]);
+        } else {
+            // Pulse the menu image while waiting for a sender.
+            let t = self.frame as f32 / FPS as f32 / FALLBACK_PULSE_DURATION;
+            let darken = (1.0 - (t * std::f32::consts::TAU).cos()) / 4.0;
+            let image = self.menu_image();
+            let mut fb = fb.centered(image.size());
+            image.draw_with_color_fn(&mut fb, |c| c.darken(darken));
+        }
+    }
+}
+
+impl Activity for PixelReceiver {
+    fn info(&self) -> &'static ActivityInfo {
+        &INFO
+    }
+
+    fn menu_image(&self) -> StaticImage {
+        include_rgba_image!("activities/pixel_receiver.rgba")
+    }
+
+    fn set_size(&mut self, size: [usize; 2]) {
+        self.pixels = FrameBuffer::new(size);
+    }
+
+    /// Clears the pixels but keeps listening.
+    fn reset(&mut self) {
+        self.pixels.as_flattened_mut().fill(BLACK);
+        self.last_received_frame = None;
+    }
+
+    fn stay_awake(&self) -> bool {
+        true
+    }
+}
+
+struct OpcServer {
+    listener: TcpListener,
+    clients: Vec<OpcClient>,
+}
+
+struct OpcClient {
+    stream: TcpStream,
+    /// Bytes received but not yet processed.
+    buf: Vec<u8>,
+}
+
+impl OpcClient {
+    /// Reads all available data and returns whether the client is still
+    /// connected.
+    fn read(&mut self) -> bool {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return false,
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    eprintln!("Error reading OPC connection: {e}");
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Removes and returns the next complete message as `(channel, command,
+    /// data)`.
+    fn next_message(&mut self) -> Option<(u8, u8, Vec<u8>)> {
+        let &[channel, command, l0, l1] = self.buf.first_chunk::<4>()?;
+        let len = u16::from_be_bytes([l0, l1]) as usize;
+        if self.buf.len() < 4 + len {
+            return None;
+        }
+        let data = self.buf[4..4 + len].to_vec();
+        self.buf.drain(..4 + len);
+        Some((channel, command, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use super::*;
+
+    const WIDTH: usize = DEFAULT_SIZE[0];
+
+    fn step(receiver: &mut PixelReceiver) {
+        // Wait for data to arrive on the loopback interface.
+        std::thread::sleep(Duration::from_millis(20));
+        receiver.step(FullInput::default());
+    }
+
+    #[test]
+    fn loopback() {
+        let localhost = Some("127.0.0.1:0".to_string());
+        let mut receiver = PixelReceiver::new(localhost.clone(), localhost);
+        step(&mut receiver);
+        assert!(!receiver.is_receiving());
+        let opc_addr = receiver
+            .opc
+            .socket
+            .as_ref()
+            .unwrap()
+            .listener
+            .local_addr()
+            .unwrap();
+        let tpm2_addr = receiver.tpm2.socket.as_ref().unwrap().local_addr().unwrap();
+
+        // OPC, with one message split across multiple reads
+        let mut opc = TcpStream::connect(opc_addr).unwrap();
+        opc.write_all(&[1, 0, 0, 6, 10, 20, 30, 40, 50, 60])
+            .unwrap();
+        opc.write_all(&[0, 0, 0, 3, 1]).unwrap();
+        step(&mut receiver);
+        assert!(receiver.is_receiving());
+        assert_eq!(
+            receiver.pixels[0][..2],
+            [Rgb([10, 20, 30]), Rgb([40, 50, 60])]
+        );
+        opc.write_all(&[2, 3]).unwrap();
+        step(&mut receiver);
+        assert_eq!(receiver.pixels[0][..2], [Rgb([1, 2, 3]), Rgb([40, 50, 60])]);
+
+        // Messages on other channels are ignored.
+        opc.write_all(&[2, 0, 0, 3, 9, 9, 9]).unwrap();
+        step(&mut receiver);
+        assert_eq!(receiver.pixels[0][0], Rgb([1, 2, 3]));
+
+        // TPM2.net, with one frame split across two packets
+        let tpm2 = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let first_row = [7; WIDTH * 3];
+        let mut packet = vec![TPM2_START, TPM2_DATA, 0, WIDTH as u8 * 3, 1, 2];
+        packet.extend_from_slice(&first_row);
+        packet.push(TPM2_END);
+        tpm2.send_to(&packet, tpm2_addr).unwrap();
+        tpm2.send_to(
+            &[TPM2_START, TPM2_DATA, 0, 3, 2, 2, 4, 5, 6, TPM2_END],
+            tpm2_addr,
+        )
+        .unwrap();
+        step(&mut receiver);
+        assert_eq!(receiver.pixels[0], [Rgb([7, 7, 7]); WIDTH]);
+        assert_eq!(receiver.pixels[1][0], Rgb([4, 5, 6]));
+
+        assert!(
+            receiver
+                .handle_tpm2_packet(&packet[..packet.len() - 1])
+                .is_err()
+        );
+        assert!(receiver.handle_tpm2_packet(&[0; 7]).is_err());
+
+        // Fall back to the menu image after a timeout.
+        for _ in 0..TIMEOUT {
+            receiver.step(FullInput::default());
+        }
+        assert!(!receiver.is_receiving());
+    }
+}
@@ -1,16 +1,30 @@
+use crate::activities::ActivityInfo;
 use crate::{Activity, FPS, FrameBufferRect, FullInput, Rgb, Widget};
 
+pub const INFO: ActivityInfo = ActivityInfo {
+    id: "rainbow",
+    name: "Rainbow",
+    min_players: 0,
+    attract: true,
+    enabled_by_default: true,
+};
+
 pub const DURATION: f32 = 2.0; // seconds
 
+/// Returns the number of frames in one cycle of the rainbow.
+pub fn duration_frames() -> usize {
+    (DURATION * FPS as f32) as usize
+}
+
 #[derive(Debug, Default)]
 pub struct Rainbow {
-    frame: usize,
+    pub(super) frame: usize,
 }
 
 impl Widget<FullInput> for Rainbow {
     fn step(&mut self, _input: FullInput) {
         self.frame += 1;
-        if self.frame == (DURATION * FPS as f32) as usize {
+        if self.frame == duration_frames() {
             self.frame = 0;
         }
     }
@@ -26,6 +40,10 @@ impl Widget<FullInput> for Rainbow {
 }
 
 impl Activity for Rainbow {
+    fn info(&self) -> &'static ActivityInfo {
+        &INFO
+    }
+
     fn reset(&mut self) {
         *self = Self::default();
     }
@@ -0,0 +1,727 @@
+//! Activities written in [Rhai](https://rhai.rs/) and loaded at runtime.
+//!
+//! Each `*.rhai` file in the `scripts` directory within [`storage::dir()`]
+//! becomes an activity whose ID is the file name without the extension. A
+//! script defines any of these functions:
+//!
+//! ```rhai
+//! // Metadata for the menu. All fields are optional.
+//! fn info() { #{ name: "Bounce", min_players: 1 } }
+//!
+//! // Returns the initial state, which the other functions access as `this`.
+//! fn init() { #{ x: 0, color: rgb(0xFF7777) } }
+//!
+//! // Called every frame. See below for the contents of `input`.
+//! fn step(input) {
+//!     if input.pressed.right { this.x += 1; }
+//! }
+//!
+//! // Called every frame after `step()`.
+//! fn draw(fb) {
+//!     fb.fill(black());
+//!     fb.set(this.x, 10, this.color);
+//! }
+//! ```
+//!
+//! `input` has `held`, `pressed`, and `released` maps from button name (see
+//! [`Buttons::NAMES`]) to `bool`, `left_stick` and `right_stick` arrays of
+//! `[x, y]`, and `lt` and `rt` floats, for all controllers combined. Its
+//! `players` array has the same for each player, or `()` if the player's
+//! controller isn't connected.
+//!
+//! `fb` has `width` and `height` and these methods:
+//!
+//! - `fb.set(x, y, color)` and `fb.get(x, y)`
+//! - `fb.fill(color)`
+//! - `fb.rect(x, y, w, h, color)`
+//! - `fb.text(s, x, y, color)`, along with `text_width(s)`
+//! - `fb.image(name, x, y)`, which draws `<name>.rgba` from the scripts
+//!   directory (see the `preprocess_image` crate), for up to 32 different
+//!   names
+//!
+//! Colors are made with `rgb(r, g, b)`, `rgb(0xRRGGBB)`, `black()`, or
+//! `white()`, and have `r`, `g`, and `b` and the methods `a.mix(b, t)` and
+//! `a.darken(t)`.
+//!
+//! Scripts are reloaded when they change, keeping their state. An error is
+//! shown on the panel until the script is fixed. Pressing X in the menu calls
+//! `init()` again.
+//!
+//! [`storage::dir()`]: crate::storage::dir
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use rhai::{
+    AST, Array, CallFnOptions, Dynamic, Engine, EvalAltResult, FLOAT, FuncArgs, INT, Map, Scope,
+};
+
+use super::ActivityInfo;
+use crate::{
+    Activity, BLACK, Buttons, ControllerInput, DEFAULT_SIZE, FPS, FrameBuffer, FrameBufferRect,
+    FullInput, Rgb, StaticImage, WHITE, Widget, text,
+};
+
+/// Name of the directory within [`storage::dir()`] that contains scripts.
+///
+/// [`storage::dir()`]: crate::storage::dir
+pub const DIR_NAME: &str = "scripts";
+
+/// Maximum number of operations in one call to a script function, so that an
+/// infinite loop shows an error instead of freezing the panel.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Maximum number of images that a script can load, since loaded images are
+/// never freed.
+const MAX_IMAGES: usize = 32;
+
+/// Number of frames between checks for changes to the script.
+const RELOAD_CHECK_INTERVAL: usize = FPS;
+
+const ERROR_COLOR: Rgb = Rgb::from_hex(0xFF4444);
+const ERROR_MESSAGE_COLOR: Rgb = WHITE;
+/// Number of frames per pixel when scrolling a long error message.
+const ERROR_SCROLL_FRAMES: usize = FPS / 8;
+const LINE_HEIGHT: usize = 6;
+
+/// Loads every script in `dir`, skipping scripts whose names clash with other
+/// activities.
+pub fn load_dir(dir: &Path) -> Vec<ScriptActivity> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return vec![],
+        Err(e) => {
+            eprintln!("Error reading scripts directory: {e}");
+            return vec![];
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let id = path.file_stem()?.to_str()?;
+            if super::get(id).is_some() {
+                eprintln!("Ignoring script {id:?} because an activity has the same ID");
+                return None;
+            }
+            Some(ScriptActivity::load(path.clone()))
+        })
+        .collect()
+}
+
+/// Activity that runs a script.
+pub struct ScriptActivity {
+    info: &'static ActivityInfo,
+    menu_image: StaticImage,
+    path: PathBuf,
+    /// Modification time of the script when it was last loaded.
+    modified: Option<SystemTime>,
+
+    engine: Engine,
+    ast: Option<AST>,
+    /// Value of `this` in the script.
+    state: Dynamic,
+    canvas: Canvas,
+
+    error: Option<String>,
+    /// Number of frames since the activity was loaded.
+    frame: usize,
+    /// Frame on which the current error occurred.
+    error_frame: usize,
+}
+
+impl ScriptActivity {
+    /// Loads the script at `path`. If there is an error, the activity shows it
+    /// until the script is fixed.
+    pub fn load(path: PathBuf) -> Self {
+        let id = path.file_stem().unwrap_or_default().to_string_lossy();
+        let id: &'static str = Box::leak(id.into_owned().into_boxed_str());
+        let dir = path.parent().unwrap_or(Path::new(".")).to_owned();
+        let canvas = Canvas::new(dir.clone());
+        let mut ret = Self {
+            info: Box::leak(Box::new(ActivityInfo {
+                id,
+                name: id,
+                min_players: 0,
+                attract: false,
+                enabled_by_default: true,
+            })),
+            menu_image: load_image(&dir, id)
+                .unwrap_or(include_rgba_image!("activities/script.rgba")),
+            path,
+            modified: None,
+
+            engine: new_engine(),
+            ast: None,
+            state: Dynamic::UNIT,
+            canvas,
+
+            error: None,
+            frame: 0,
+            error_frame: 0,
+        };
+        ret.reload();
+        if let Some(info) = ret.call("info", ()) {
+            ret.info = Box::leak(Box::new(info_from_map(ret.info, info)));
+        }
+        ret.reset();
+        ret
+    }
+
+    /// Recompiles the script, keeping its state.
+    fn reload(&mut self) {
+        self.modified = modified_time(&self.path);
+        let result = std::fs::read_to_string(&self.path)
+            .map_err(|e| e.to_string())
+            .and_then(|src| self.engine.compile(src).map_err(|e| e.to_string()));
+        match result {
+            Ok(ast) => {
+                self.ast = Some(ast);
+                self.error = None;
+            }
+            Err(e) => self.set_error(e),
+        }
+    }
+
+    fn set_error(&mut self, error: String) {
+        eprintln!("Error in script {}: {error}", self.info.id);
+        self.error = Some(error);
+        self.error_frame = self.frame;
+    }
+
+    /// Calls a function in the script, if it exists, with `this` bound to the
+    /// script's state. Returns `None` if there is no such function or it
+    /// returned an error or panicked.
+    fn call(&mut self, name: &str, args: impl FuncArgs) -> Option<Dynamic> {
+        let ast = self.ast.as_ref()?;
+        if self.error.is_some() || !ast.iter_functions().any(|f| f.name == name) {
+            return None;
+        }
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.state);
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            self.engine
+                .call_fn_with_options::<Dynamic>(options, &mut Scope::new(), ast, name, args)
+        }));
+        match result {
+            Ok(Ok(value)) => Some(value),
+            Ok(Err(e)) => {
+                self.set_error(format!("{name}: {e}"));
+                None
+            }
+            Err(panic) => {
+                let message = (panic.downcast_ref::<&str>().copied())
+                    .or(panic.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("unknown error");
+                self.set_error(format!("{name}: panicked: {message}"));
+                None
+            }
+        }
+    }
+
+    /// Draws the current state of the script to the canvas.
+    fn render(&mut self) {
+        self.canvas
+            .0
+            .borrow_mut()
+            .pixels
+            .as_flattened_mut()
+            .fill(BLACK);
+        self.call("draw", (self.canvas.clone(),));
+    }
+
+    fn draw_error(&self, fb: &mut FrameBufferRect<'_>, error: &str) {
+        fb.fill(BLACK);
+        text::draw("error", &mut fb.with_offset([1, 1]), ERROR_COLOR);
+        let lines = wrap(error, fb.width().saturating_sub(1));
+        let top = 1 + LINE_HEIGHT as isize;
+        let overflow =
+            (lines.len() * LINE_HEIGHT).saturating_sub(fb.height().saturating_sub(top as usize));
+        // Scroll to the end, then pause before starting over.
+        let ticks = (self.frame - self.error_frame) / ERROR_SCROLL_FRAMES;
+        let scroll = (ticks % (overflow + fb.height())).min(overflow);
+        let mut fb = fb.with_offset([0, top]);
+        for (i, line) in lines.iter().enumerate() {
+            let y = (i * LINE_HEIGHT) as isize - scroll as isize;
+            text::draw(line, &mut fb.with_offset([1, y]), ERROR_MESSAGE_COLOR);
+        }
+    }
+}
+
+impl Activity for ScriptActivity {
+    fn info(&self) -> &'static ActivityInfo {
+        self.info
+    }
+
+    fn menu_image(&self) -> StaticImage {
+        self.menu_image
+    }
+
+    fn reset(&mut self) {
+        self.state = self.call("init", ()).unwrap_or_else(|| Map::new().into());
+        self.render();
+    }
+
+    fn set_size(&mut self, size: [usize; 2]) {
+        self.canvas.0.borrow_mut().pixels = FrameBuffer::new(size);
+        self.render();
+    }
+}
+
+impl Widget<FullInput> for ScriptActivity {
+    fn step(&mut self, input: FullInput) {
+        self.frame += 1;
+        if self.frame.is_multiple_of(RELOAD_CHECK_INTERVAL)
+            && modified_time(&self.path) != self.modified
+        {
+            self.reload();
+        }
+        self.call("step", (input_map(input),));
+        self.render();
+    }
+
+    fn draw(&self, fb: &mut FrameBufferRect<'_>) {
+        match &self.error {
+            Some(error) => self.draw_error(fb, error),
+            None => {
+                let canvas = self.canvas.0.borrow();
+                fb.fill_with_fn(|[x, y], _| canvas.pixels.get(y).map_or(BLACK, |row| row[x]));
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Loads `<name>.rgba` from `dir`. The image is leaked, so this should only
+/// be called once per image.
+fn load_image(dir: &Path, name: &str) -> Option<StaticImage> {
+    let data = std::fs::read(dir.join(format!("{name}.rgba"))).ok()?;
+    let image = StaticImage(Box::leak(data.into_boxed_slice()));
+    let valid = image.0.len() >= 8 && image.0.len() == 8 + image.width() * image.height() * 4;
+    valid.then_some(image)
+}
+
+/// Fills in `info` from the map returned by the script's `info()` function.
+fn info_from_map(info: &ActivityInfo, map: Dynamic) -> ActivityInfo {
+    let map = map.try_cast::<Map>().unwrap_or_default();
+    let int = |key: &str, default: usize| {
+        (map.get(key).and_then(|v| v.as_int().ok())).map_or(default, |i| i.max(0) as usize)
+    };
+    let name = map.get("name").and_then(|v| v.clone().into_string().ok());
+    ActivityInfo {
+        name: name.map_or(info.name, |name| Box::leak(name.into_boxed_str())),
+        min_players: int("min_players", info.min_players),
+        ..info.clone()
+    }
+}
+
+/// Splits `s` into lines no wider than `width` pixels, breaking at spaces
+/// where possible.
+fn wrap(s: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in s.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = match line.is_empty() {
+                true => word.to_owned(),
+                false => format!("{line} {word}"),
+            };
+            if text::width(&candidate) <= width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if text::width(&line) > width {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+fn buttons_map(buttons: Buttons) -> Map {
+    std::iter::zip(Buttons::NAMES, buttons.to_array())
+        .map(|(name, is_set)| (name.into(), is_set.into()))
+        .collect()
+}
+
+fn controller_map(input: ControllerInput) -> Map {
+    let pair = |[x, y]: [f32; 2]| -> Dynamic {
+        Array::from([
+            Dynamic::from_float(x as FLOAT),
+            Dynamic::from_float(y as FLOAT),
+        ])
+        .into()
+    };
+    let mut map = Map::new();
+    map.insert("held".into(), buttons_map(input.current).into());
+    map.insert("pressed".into(), buttons_map(input.pressed()).into());
+    map.insert("released".into(), buttons_map(input.released()).into());
+    map.insert("left_stick".into(), pair(input.axes.left_stick));
+    map.insert("right_stick".into(), pair(input.axes.right_stick));
+    map.insert("lt".into(), (input.axes.lt as FLOAT).into());
+    map.insert("rt".into(), (input.axes.rt as FLOAT).into());
+    map
+}
+
+fn input_map(input: FullInput) -> Map {
+    let players: Array = (input.players.iter())
+        .map(|p| p.map_or(Dynamic::UNIT, |p| controller_map(p).into()))
+        .collect();
+    let mut map = controller_map(input.any());
+    map.insert("players".into(), players.into());
+    map
+}
+
+/// Frame buffer that scripts draw to.
+#[derive(Clone)]
+struct Canvas(Rc<RefCell<CanvasData>>);
+
+struct CanvasData {
+    pixels: FrameBuffer,
+    /// Directory to load images from.
+    dir: PathBuf,
+    /// Images loaded so far by name, or `None` for names that aren't valid
+    /// images. This holds at most [`MAX_IMAGES`] names.
+    images: HashMap<String, Option<StaticImage>>,
+}
+
+impl Canvas {
+    fn new(dir: PathBuf) -> Self {
+        Self(Rc::new(RefCell::new(CanvasData {
+            pixels: FrameBuffer::new(DEFAULT_SIZE),
+            dir,
+            images: HashMap::new(),
+        })))
+    }
+
+    /// Calls `f` with the region of the canvas at `[x, y]`, unless a region of
+    /// `size` there would be entirely off the canvas.
+    fn with_rect(
+        &mut self,
+        x: INT,
+        y: INT,
+        size: [usize; 2],
+        f: impl FnOnce(&mut FrameBufferRect<'_>),
+    ) {
+        let mut data = self.0.borrow_mut();
+        let on_canvas = |pos: INT, len: usize, max: usize| {
+            pos < max as INT && pos.saturating_add(len as INT) > 0
+        };
+        if on_canvas(x, size[0], data.pixels.width()) && on_canvas(y, size[1], data.pixels.height())
+        {
+            let mut fb = FrameBufferRect::new(&mut data.pixels);
+            f(&mut fb.with_offset([x as isize, y as isize]));
+        }
+    }
+
+    fn set(&mut self, x: INT, y: INT, color: Rgb) {
+        let mut data = self.0.borrow_mut();
+        let pixel = (usize::try_from(y).ok())
+            .zip(usize::try_from(x).ok())
+            .and_then(|(y, x)| data.pixels.get_mut(y)?.get_mut(x));
+        if let Some(pixel) = pixel {
+            *pixel = color;
+        }
+    }
+
+    fn get(&mut self, x: INT, y: INT) -> Rgb {
+        let data = self.0.borrow();
+        let pixel = (usize::try_from(y).ok())
+            .zip(usize::try_from(x).ok())
+            .and_then(|(y, x)| data.pixels.get(y)?.get(x));
+        pixel.copied().unwrap_or(BLACK)
+    }
+
+    fn fill(&mut self, color: Rgb) {
+        self.0.borrow_mut().pixels.as_flattened_mut().fill(color);
+    }
+
+    fn rect(&mut self, x: INT, y: INT, w: INT, h: INT, color: Rgb) {
+        // Clip to the canvas, since regions can't extend past its top left.
+        let mut data = self.0.borrow_mut();
+        let [width, height] = data.pixels.size().map(|len| len as INT);
+        let [x0, x1] = [x, x.saturating_add(w)].map(|x| x.clamp(0, width) as usize);
+        let [y0, y1] = [y, y.saturating_add(h)].map(|y| y.clamp(0, height) as usize);
+        if x0 < x1 && y0 < y1 {
+            let mut fb = FrameBufferRect::new(&mut data.pixels);
+            (fb.with_offset([x0 as isize, y0 as isize]))
+                .with_size([x1 - x0, y1 - y0])
+                .fill(color);
+        }
+    }
+
+    fn text(&mut self, s: &str, x: INT, y: INT, color: Rgb) {
+        let height = text::layout(s).map(|img| img.height()).max().unwrap_or(0);
+        let size = [text::width(s), height];
+        self.with_rect(x, y, size, |fb| text::draw(s, fb, color));
+    }
+
+    fn image(&mut self, name: &str, x: INT, y: INT) -> Result<(), Box<EvalAltResult>> {
+        // Images must be directly within the scripts directory.
+        if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+            return Err(format!("invalid image name {name:?}").into());
+        }
+        let image = {
+            let mut data = self.0.borrow_mut();
+            match data.images.get(name) {
+                Some(&image) => image,
+                None if data.images.len() >= MAX_IMAGES => {
+                    return Err(format!("too many images; the limit is {MAX_IMAGES}").into());
+                }
+                None => {
+                    let image = load_image(&data.dir, name);
+                    data.images.insert(name.to_owned(), image);
+                    image
+                }
+            }
+        };
+        let image = image.ok_or_else(|| format!("no valid image {name}.rgba"))?;
+        self.with_rect(x, y, image.size(), |fb| image.draw(fb));
+        Ok(())
+    }
+}
+
+fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(64);
+    engine.set_max_string_size(10_000);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+
+    engine
+        .register_type_with_name::<Rgb>("Rgb")
+        .register_fn("rgb", |r: INT, g: INT, b: INT| {
+            Rgb([r, g, b].map(|c| c.clamp(0, 255) as u8))
+        })
+        .register_fn("rgb", |hex: INT| Rgb::from_hex(hex as u32 & 0xFFFFFF))
+        .register_fn("black", || BLACK)
+        .register_fn("white", || WHITE)
+        .register_get("r", |c: &mut Rgb| c.0[0] as INT)
+        .register_get("g", |c: &mut Rgb| c.0[1] as INT)
+        .register_get("b", |c: &mut Rgb| c.0[2] as INT)
+        .register_fn("mix", |a: Rgb, b: Rgb, t: FLOAT| a.mix(b, t as f32))
+        .register_fn("darken", |c: Rgb, t: FLOAT| c.darken(t as f32))
+        .register_fn("to_string", |c: &mut Rgb| {
+            format!("rgb({}, {}, {})", c.0[0], c.0[1], c.0[2])
+        })
+        .register_fn("==", |a: Rgb, b: Rgb| a == b)
+        .register_fn("!=", |a: Rgb, b: Rgb| a != b);
+
+    engine
+        .register_type_with_name::<Canvas>("FrameBuffer")
+        .register_get("width", |fb: &mut Canvas| {
+            fb.0.borrow().pixels.width() as INT
+        })
+        .register_get("height", |fb: &mut Canvas| {
+            fb.0.borrow().pixels.height() as INT
+        })
+        .register_fn("set", Canvas::set)
+        .register_fn("get", Canvas::get)
+        .register_fn("fill", Canvas::fill)
+        .register_fn("rect", Canvas::rect)
+        .register_fn(
+            "text",
+            |fb: &mut Canvas, s: &str, x: INT, y: INT, color: Rgb| {
+                fb.text(s, x, y, color);
+            },
+        )
+        .register_fn("image", |fb: &mut Canvas, name: &str, x: INT, y: INT| {
+            fb.image(name, x, y)
+        })
+        .register_fn("text_width", |s: &str| text::width(s) as INT);
+
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_PLAYERS;
+    use crate::storage::TempDir;
+
+    /// Writes a script to a fresh temporary directory and loads it. The
+    /// directory is removed when the returned [`TempDir`] is dropped.
+    fn load(name: &str, src: &str) -> (TempDir, ScriptActivity) {
+        let dir = TempDir::new(name);
+        let path = dir.path().join(format!("{name}.rhai"));
+        std::fs::write(&path, src).unwrap();
+        (dir, ScriptActivity::load(path))
+    }
+
+    fn draw(activity: &ScriptActivity) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(DEFAULT_SIZE);
+        activity.draw(&mut FrameBufferRect::new(&mut fb));
+        fb
+    }
+
+    #[test]
+    fn step_and_draw() {
+        let (_dir, mut activity) = load(
+            "counter",
+            r#"
+            fn info() { #{ name: "Counter", min_players: 1 } }
+            fn init() { #{ x: 0 } }
+            fn step(input) {
+                if input.pressed.right { this.x += 1; }
+            }
+            fn draw(fb) {
+                fb.fill(rgb(0, 0, 1));
+                fb.set(this.x, 0, white());
+                fb.rect(0, 10, 2, 2, rgb(0xFF0000).mix(black(), 0.0));
+                fb.set(-1, 1000, white()); // out of bounds
+            }
+            "#,
+        );
+        assert_eq!(activity.info().id, "counter");
+        assert_eq!(activity.info().name, "Counter");
+        assert_eq!(activity.info().min_players, 1);
+
+        let right = crate::Buttons {
+            right: true,
+            ..Default::default()
+        };
+        activity.step(FullInput::new(
+            [Some(right.into()), None, None, None],
+            [None; MAX_PLAYERS],
+        ));
+        let fb = draw(&activity);
+        assert_eq!(activity.error, None);
+        assert_eq!(fb[0][1], WHITE);
+        assert_eq!(fb[0][0], Rgb([0, 0, 1]));
+        assert_eq!(fb[11][1], Rgb::from_hex(0xFF0000));
+        assert_eq!(fb[11][2], Rgb([0, 0, 1]));
+
+        activity.reset();
+        assert_eq!(draw(&activity)[0][0], WHITE);
+    }
+
+    #[test]
+    fn clipping() {
+        let (_dir, mut activity) = load(
+            "clipping",
+            r#"
+            fn draw(fb) {
+                fb.rect(-5, 0, 7, 1, white());
+                fb.rect(fb.width - 1, 1, 40, 1, white());
+                fb.rect(-20, 2, 5, 5, white()); // off the canvas
+                fb.rect(0, 3, -5, 5, white()); // empty
+                fb.rect(0, fb.height, 1, 1000, white()); // off the canvas
+                fb.text("ab", -1000000000000, -1000000000000, white());
+                fb.text("", 0, 10, white());
+                if text_width("") != 0 { throw "text_width"; }
+            }
+            "#,
+        );
+        activity.step(FullInput::default());
+        assert_eq!(activity.error, None);
+        let fb = draw(&activity);
+        assert_eq!(fb[0][..3], [WHITE, WHITE, BLACK]);
+        assert_eq!(fb[1][DEFAULT_SIZE[0] - 2..], [BLACK, WHITE]);
+        let white = fb.as_flattened().iter().filter(|&&c| c == WHITE).count();
+        assert_eq!(white, 3);
+    }
+
+    #[test]
+    fn image_names() {
+        for name in ["../clipping", "a/b", "", "a\\b"] {
+            let (_dir, mut activity) = load(
+                "image_name",
+                &format!("fn draw(fb) {{ fb.image({name:?}, 0, 0); }}"),
+            );
+            activity.step(FullInput::default());
+            let error = activity.error.unwrap();
+            assert!(error.contains("invalid image name"), "{error}");
+        }
+
+        let (_dir, mut activity) = load(
+            "many_images",
+            r#"
+            fn draw(fb) {
+                for i in 0..100 {
+                    try { fb.image("missing" + i, 0, 0); } catch {}
+                }
+            }
+            "#,
+        );
+        activity.step(FullInput::default());
+        assert_eq!(activity.error, None);
+        assert_eq!(activity.canvas.0.borrow().images.len(), MAX_IMAGES);
+    }
+
+    #[test]
+    fn errors() {
+        let (_dir, mut activity) = load("broken", "fn step(input) { this.x += }");
+        assert!(activity.error.is_some());
+        activity.step(FullInput::default());
+        assert_ne!(draw(&activity)[2][1], BLACK, "error should be drawn");
+        // Frames too small for the message still show what fits.
+        for size in [[32, 4], [1, 6], [1, 1]] {
+            let mut fb = FrameBuffer::new(size);
+            activity.draw(&mut FrameBufferRect::new(&mut fb));
+        }
+
+        let (_dir, mut activity) = load("runtime_error", "fn step(input) { while true {} }");
+        assert_eq!(activity.error, None);
+        activity.step(FullInput::default());
+        assert!(activity.error.as_ref().unwrap().contains("step"));
+
+        // Fixing the script clears the error.
+        std::fs::write(&activity.path, "fn draw(fb) { fb.fill(white()); }").unwrap();
+        activity.modified = None;
+        for _ in 0..RELOAD_CHECK_INTERVAL {
+            activity.step(FullInput::default());
+        }
+        assert_eq!(activity.error, None);
+        assert_eq!(draw(&activity)[0][0], WHITE);
+    }
+
+    #[test]
+    fn example() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scripts/bounce.rhai");
+        let mut activity = ScriptActivity::load(path);
+        assert_eq!(activity.info().name, "Bounce");
+        let a = crate::Buttons {
+            a: true,
+            ..Default::default()
+        };
+        for i in 0..10 * FPS {
+            let buttons = if i % 2 == 0 { a } else { Default::default() };
+            activity.step(FullInput::new(
+                [Some(buttons.into()), None, None, None],
+                [None; MAX_PLAYERS],
+            ));
+            assert_eq!(activity.error, None);
+        }
+        assert!(draw(&activity).as_flattened().contains(&WHITE));
+    }
+
+    #[test]
+    fn wrap_text() {
+        assert_eq!(wrap("a bb ccc", 15), ["a bb", "ccc"]);
+        assert_eq!(wrap("abcdefghij", 15), ["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("a\nb", 31), ["a", "b"]);
+    }
+}
@@ -0,0 +1,178 @@
+//! Settings that activities expose on a page in the menu (see
+//! [`Activity::settings()`]).
+//!
+//! The chosen values are saved in `settings.toml` under the activity's ID:
+//!
+//! ```toml
+//! [activities.life]
+//! speed = "fast"
+//! trails = false
+//! ```
+//!
+//! [`Activity::settings()`]: crate::Activity::settings
+
+use std::ops::RangeInclusive;
+
+/// Setting and its current value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    /// Short lowercase name, which should leave room on the panel for the
+    /// value.
+    pub name: &'static str,
+    pub value: SettingValue,
+}
+
+impl Setting {
+    pub fn int(name: &'static str, value: i32, range: RangeInclusive<i32>) -> Self {
+        let value = SettingValue::Int { value, range };
+        Self { name, value }
+    }
+    pub fn toggle(name: &'static str, value: bool) -> Self {
+        let value = SettingValue::Toggle(value);
+        Self { name, value }
+    }
+    pub fn choice(name: &'static str, index: usize, options: &'static [&'static str]) -> Self {
+        let value = SettingValue::Choice { index, options };
+        Self { name, value }
+    }
+}
+
+/// Typed value of a setting.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingValue {
+    /// Integer within `range`, which spans at most 255 steps.
+    Int {
+        value: i32,
+        range: RangeInclusive<i32>,
+    },
+    Toggle(bool),
+    /// Index into `options`, which are short lowercase names.
+    Choice {
+        index: usize,
+        options: &'static [&'static str],
+    },
+}
+
+impl SettingValue {
+    /// Returns the integer, `0` or `1` for a toggle, or the index of a
+    /// choice.
+    pub fn get(&self) -> i32 {
+        match self {
+            Self::Int { value, .. } => *value,
+            Self::Toggle(on) => *on as i32,
+            Self::Choice { index, .. } => *index as i32,
+        }
+    }
+
+    /// Returns the text shown for the value.
+    pub fn label(&self) -> String {
+        match self {
+            Self::Int { value, .. } => value.to_string(),
+            Self::Toggle(true) => "on".to_owned(),
+            Self::Toggle(false) => "off".to_owned(),
+            Self::Choice { index, options } => options[*index].to_owned(),
+        }
+    }
+
+    /// Returns the number of steps from the lowest value to the highest.
+    pub fn max_position(&self) -> u8 {
+        match self {
+            Self::Int { range, .. } => (range.end() - range.start()).clamp(0, 255) as u8,
+            Self::Toggle(_) => 1,
+            Self::Choice { options, .. } => options.len().saturating_sub(1) as u8,
+        }
+    }
+
+    /// Returns the number of steps from the lowest value to this one.
+    pub fn position(&self) -> u8 {
+        match self {
+            Self::Int { value, range } => (value - range.start()).clamp(0, 255) as u8,
+            Self::Toggle(on) => *on as u8,
+            Self::Choice { index, .. } => *index as u8,
+        }
+    }
+
+    /// Returns the value `position` steps from the lowest value, clamped to
+    /// the highest.
+    pub fn with_position(&self, position: u8) -> Self {
+        let position = position.min(self.max_position());
+        match self {
+            Self::Int { range, .. } => Self::Int {
+                value: range.start() + position as i32,
+                range: range.clone(),
+            },
+            Self::Toggle(_) => Self::Toggle(position != 0),
+            Self::Choice { options, .. } => Self::Choice {
+                index: position as usize,
+                options,
+            },
+        }
+    }
+
+    /// Returns the value as it is saved in `settings.toml`.
+    pub fn to_toml(&self) -> toml::Value {
+        match self {
+            Self::Int { value, .. } => toml::Value::Integer(*value as i64),
+            Self::Toggle(on) => toml::Value::Boolean(*on),
+            Self::Choice { index, options } => toml::Value::String(options[*index].to_owned()),
+        }
+    }
+
+    /// Returns the value of the same type that was saved as `saved`, or
+    /// `None` if it has the wrong type or is out of range.
+    pub fn parse_toml(&self, saved: &toml::Value) -> Option<Self> {
+        match (self, saved) {
+            (Self::Int { range, .. }, toml::Value::Integer(i)) => {
+                let value = i32::try_from(*i).ok().filter(|i| range.contains(i))?;
+                let range = range.clone();
+                Some(Self::Int { value, range })
+            }
+            (Self::Toggle(_), toml::Value::Boolean(on)) => Some(Self::Toggle(*on)),
+            (Self::Choice { options, .. }, toml::Value::String(s)) => {
+                let index = options.iter().position(|o| o == s)?;
+                Some(Self::Choice { index, options })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let int = Setting::int("das", 5, 2..=9).value;
+        assert_eq!((int.position(), int.max_position()), (3, 7));
+        assert_eq!(int.with_position(0).get(), 2);
+        assert_eq!(int.with_position(100).get(), 9);
+
+        let toggle = Setting::toggle("trails", true).value;
+        assert_eq!((toggle.position(), toggle.max_position()), (1, 1));
+        assert_eq!(toggle.with_position(0), SettingValue::Toggle(false));
+        assert_eq!(toggle.label(), "on");
+
+        let choice = Setting::choice("speed", 1, &["slow", "normal", "fast"]).value;
+        assert_eq!((choice.position(), choice.max_position()), (1, 2));
+        assert_eq!(choice.with_position(2).label(), "fast");
+    }
+
+    #[test]
+    fn toml() {
+        let int = Setting::int("das", 5, 2..=9).value;
+        let choice = Setting::choice("speed", 1, &["slow", "normal", "fast"]).value;
+        for value in [
+            int.with_position(4),
+            SettingValue::Toggle(true),
+            choice.clone(),
+        ] {
+            assert_eq!(value.parse_toml(&value.to_toml()), Some(value));
+        }
+
+        assert_eq!(int.parse_toml(&toml::Value::Integer(10)), None);
+        assert_eq!(int.parse_toml(&toml::Value::Boolean(true)), None);
+        assert_eq!(choice.parse_toml(&"slow".into()).map(|v| v.get()), Some(0));
+        assert_eq!(choice.parse_toml(&"ludicrous".into()), None);
+    }
+}
@@ -108,8 +108,9 @@ impl Animation<Transform> for HardDropAnimation {
                     let trail_pos = (by - fby) as f32 / (self.trail_len as f32 * tf.scale as f32);
                     let t = global_t + trail_pos;
                     let opacity = (1.0 - t) * TRAIL_OPACITY;
-                    let pixel = &mut fb[[fbx, fby]];
-                    *pixel = pixel.mix(color, opacity);
+                    if let Some(pixel) = fb.get_mut(fbx, fby) {
+                        *pixel = pixel.mix(color, opacity);
+                    }
                 }
             }
         }
@@ -145,8 +146,9 @@ impl Animation<Transform> for ClearAnimation {
                     let fade_t = ((swipe_t - dx as f32 / pixel_width as f32) * SWIPE_DURATION
                         / FADE_DURATION)
                         .clamp(0.0, 1.0);
-                    if fade_t > 0.0 {
-                        let pixel = &mut fb[[fbx + dx, fby - dy]];
+                    if fade_t > 0.0
+                        && let Some(pixel) = fb.get_mut(fbx + dx, fby - dy)
+                    {
                         *pixel = pixel.lighten(1.0 - fade_t).darken(fade_t);
                     }
                 }
@@ -4,10 +4,21 @@ pub const DAS: tetris_logic::Das<u64> = tetris_logic::Das {
     delay: crate::FPS as u64 / 6,
     arr: crate::FPS as u64 / 20,
 };
+/// Range of DAS delays, in frames, that can be chosen in the menu.
+pub const DAS_DELAY_RANGE: std::ops::RangeInclusive<i32> = 1..=60;
+/// Range of auto-repeat rates, in frames, that can be chosen in the menu.
+pub const DAS_ARR_RANGE: std::ops::RangeInclusive<i32> = 1..=30;
 
 pub mod coordinates {
     use super::Transform;
 
+    /// Size of the layout, which is centered on larger walls.
+    pub const SIZE: [usize; 2] = crate::DEFAULT_SIZE;
+
+    pub fn is_in_layout([x, y]: [usize; 2]) -> bool {
+        x < SIZE[0] && y < SIZE[1]
+    }
+
     pub const PLAYFIELD_3X: Transform = Transform::huge([1, 3], [10, 20]);
 
     pub const PLAYFIELD: Transform = Transform::big([1, 23], [10, 20]);
@@ -70,3 +81,74 @@ pub mod animations {
         pub const DURATION: f32 = SWIPE_DURATION + FADE_DURATION;
     }
 }
+
+pub mod sounds {
+    use crate::sound::{Note, Part, Sound, Tune, Wave};
+
+    pub const MOVE: Sound = Sound(&[Note::new(Wave::Pulse, 220.0, 0.015).volume(0.3)]);
+    pub const ROTATE: Sound =
+        Sound(&[Note::new(Wave::Pulse, 440.0, 0.03).slide(660.0).volume(0.4)]);
+    pub const BLOCKED: Sound = Sound(&[Note::new(Wave::Square, 110.0, 0.04).volume(0.3)]);
+    pub const HOLD: Sound = Sound(&[
+        Note::new(Wave::Triangle, 523.0, 0.04),
+        Note::new(Wave::Triangle, 784.0, 0.06).fade(0.0),
+    ]);
+    pub const LOCK: Sound = Sound(&[Note::new(Wave::Noise, 2000.0, 0.05).volume(0.4).fade(0.0)]);
+    pub const HARD_DROP: Sound = Sound(&[Note::new(Wave::Noise, 4000.0, 0.12)
+        .slide(500.0)
+        .volume(0.6)
+        .fade(0.0)]);
+    pub const LINE_CLEAR: Sound = Sound(&[
+        Note::new(Wave::Square, 523.0, 0.06),
+        Note::new(Wave::Square, 659.0, 0.06),
+        Note::new(Wave::Square, 784.0, 0.12).fade(0.0),
+    ]);
+    /// Played when four lines are cleared at once.
+    pub const TETRIS: Sound = Sound(&[
+        Note::new(Wave::Square, 523.0, 0.06),
+        Note::new(Wave::Square, 659.0, 0.06),
+        Note::new(Wave::Square, 784.0, 0.06),
+        Note::new(Wave::Square, 1047.0, 0.3).fade(0.0),
+    ]);
+    pub const GAME_OVER: Sound = Sound(&[
+        Note::new(Wave::Triangle, 392.0, 0.2),
+        Note::new(Wave::Triangle, 311.0, 0.2),
+        Note::new(Wave::Triangle, 262.0, 0.6).slide(196.0).fade(0.0),
+    ]);
+
+    /// Korobeiniki, the traditional Tetris theme.
+    #[rustfmt::skip] // one measure per line
+    pub const MUSIC: Tune = Tune {
+        beat_seconds: 0.4,
+        parts: &[
+            Part {
+                wave: Wave::Pulse,
+                volume: 0.3,
+                notes: &[
+                    (76, 1.0), (71, 0.5), (72, 0.5), (74, 1.0), (72, 0.5), (71, 0.5),
+                    (69, 1.0), (69, 0.5), (72, 0.5), (76, 1.0), (74, 0.5), (72, 0.5),
+                    (71, 1.5), (72, 0.5), (74, 1.0), (76, 1.0),
+                    (72, 1.0), (69, 1.0), (69, 1.0), (0, 1.0),
+                    (0, 0.5), (74, 1.0), (77, 0.5), (81, 1.0), (79, 0.5), (77, 0.5),
+                    (76, 1.5), (72, 0.5), (76, 1.0), (74, 0.5), (72, 0.5),
+                    (71, 1.0), (71, 0.5), (72, 0.5), (74, 1.0), (76, 1.0),
+                    (72, 1.0), (69, 1.0), (69, 1.0), (0, 1.0),
+                ],
+            },
+            Part {
+                wave: Wave::Triangle,
+                volume: 0.4,
+                notes: &[
+                    (40, 0.5), (52, 0.5), (40, 0.5), (52, 0.5), (40, 0.5), (52, 0.5), (40, 0.5), (52, 0.5),
+                    (45, 0.5), (57, 0.5), (45, 0.5), (57, 0.5), (45, 0.5), (57, 0.5), (45, 0.5), (57, 0.5),
+                    (44, 0.5), (56, 0.5), (44, 0.5), (56, 0.5), (40, 0.5), (52, 0.5), (40, 0.5), (52, 0.5),
+                    (45, 0.5), (57, 0.5), (45, 0.5), (57, 0.5), (45, 0.5), (57, 0.5), (45, 0.5), (57, 0.5),
+                    (38, 0.5), (50, 0.5), (38, 0.5), (50, 0.5), (38, 0.5), (50, 0.5), (38, 0.5), (50, 0.5),
+                    (36, 0.5), (48, 0.5), (36, 0.5), (48, 0.5), (36, 0.5), (48, 0.5), (36, 0.5), (48, 0.5),
+                    (35, 0.5), (47, 0.5), (35, 0.5), (47, 0.5), (40, 0.5), (52, 0.5), (40, 0.5), (52, 0.5),
+                    (45, 0.5), (57, 0.5), (45, 0.5), (57, 0.5), (45, 0.5), (57, 0.5), (45, 0.5), (57, 0.5),
+                ],
+            },
+        ],
+    };
+}
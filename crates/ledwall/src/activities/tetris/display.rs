@@ -1,5 +1,6 @@
 use tetris_logic::Pos;
 
+use super::constants::coordinates;
 use crate::{FrameBufferRect, Rgb};
 
 /// Tetris block coordinate transform to display on the screen.
@@ -47,7 +48,7 @@ impl Transform {
         ((0..sx).contains(&x) && (0..sy).contains(&y))
             .then(|| [x, y].map(|coordinate| coordinate * self.scale as i32))
             .map(|[x, y]| [bx + x, by - y - 1].map(|coordinate| coordinate as usize))
-            .filter(|&xy| crate::xy_is_in_frame(xy))
+            .filter(|&xy| coordinates::is_in_layout(xy))
     }
 
     pub fn pixels_of(self, pos: Pos) -> impl Iterator<Item = [usize; 2]> {
@@ -56,7 +57,7 @@ impl Transform {
             .flat_map(move |dy| {
                 (0..self.scale).filter_map(move |dx| base_pixel.map(|[x, y]| [x + dx, y - dy]))
             })
-            .filter(|&xy| crate::xy_is_in_frame(xy))
+            .filter(|&xy| coordinates::is_in_layout(xy))
     }
 
     pub fn border_pixels(self) -> impl Iterator<Item = [usize; 2]> {
@@ -76,17 +77,17 @@ impl Transform {
             (top + 1..=bottom - 1).flat_map(move |y| [[left, y], [right, y]]),
         )
         .filter_map(|[x, y]| Some([x.try_into().ok()?, y.try_into().ok()?]))
-        .filter(|&xy| crate::xy_is_in_frame(xy))
+        .filter(|&xy| coordinates::is_in_layout(xy))
     }
 
     pub fn fill_block(self, frame_buffer: &mut FrameBufferRect<'_>, pos: Pos, color: Rgb) {
-        for pos in self.pixels_of(pos) {
-            frame_buffer[pos] = color;
+        for [x, y] in self.pixels_of(pos) {
+            frame_buffer.set(x, y, color);
         }
     }
     pub fn fill_border(self, frame_buffer: &mut FrameBufferRect<'_>, color: Rgb) {
-        for pos in self.border_pixels() {
-            frame_buffer[pos] = color;
+        for [x, y] in self.border_pixels() {
+            frame_buffer.set(x, y, color);
         }
     }
 }
@@ -1,18 +1,35 @@
-use rand::SeedableRng;
-use tetris_logic::{FrameInput, Pos, Tetromino};
+use std::io;
+
+use tetris_logic::{
+    DasState, FallingPiece, FrameInput, FrameOutput, GameState, Pos, Rot, Tetromino,
+};
 
 mod animations;
 mod constants;
 mod display;
 
+use crate::activities::ActivityInfo;
+use crate::activities::setting::{Setting, SettingValue};
+use crate::sound::{Sound, Tune};
+use crate::state::{StateReader, StateWriter, invalid_data};
 use crate::{
     Activity, FrameBufferRect, FullInput, StaticImage, Widget, draw_opt_animation,
     step_opt_animation,
 };
 use animations::*;
-use constants::{colors, coordinates};
+use constants::{colors, coordinates, sounds};
 use display::Transform;
 
+pub const INFO: ActivityInfo = ActivityInfo {
+    id: "tetris",
+    name: "Tetris",
+    min_players: 1,
+    attract: false,
+    enabled_by_default: true,
+};
+
+const STATE_VERSION: u16 = 1;
+
 pub struct Tetris {
     game: tetris_logic::Game<u64>,
     queue: [Option<Tetromino>; 4],
@@ -22,20 +39,16 @@ pub struct Tetris {
     hard_drop_anim: Option<HardDropAnimation>,
     clear_anim: Option<ClearAnimation>,
 
+    /// Sound effects started since they were last taken by the shell.
+    sounds: Vec<Sound>,
+
     big: bool,
 }
 
 impl Default for Tetris {
     fn default() -> Self {
         Self {
-            game: tetris_logic::Game::new(
-                tetris_logic::Config {
-                    das: Some(constants::DAS),
-                    ..Default::default()
-                },
-                0,
-                Box::new(rand::rngs::SmallRng::from_os_rng()),
-            ),
+            game: tetris_logic::Game::new(config(), 0, Box::new(crate::rng::new_rng())),
             queue: [None; 4],
 
             soon_to_lock_anim: SoonToLockAnimation::default(),
@@ -43,19 +56,234 @@ impl Default for Tetris {
             hard_drop_anim: None,
             clear_anim: None,
 
+            sounds: vec![],
+
             big: false,
         }
     }
 }
 
 impl Activity for Tetris {
+    fn info(&self) -> &'static ActivityInfo {
+        &INFO
+    }
+
     fn reset(&mut self) {
+        let das = self.game.config().das;
         *self = Self::default();
+        self.game.set_das(das);
     }
 
     fn menu_image(&self) -> StaticImage {
         include_rgba_image!("activities/tetris.rgba")
     }
+
+    fn take_sounds(&mut self) -> Vec<Sound> {
+        std::mem::take(&mut self.sounds)
+    }
+
+    fn music(&self) -> Option<&'static Tune> {
+        (!self.game.is_game_over()).then_some(&sounds::MUSIC)
+    }
+
+    /// DAS delay and auto-repeat rate, in frames.
+    fn settings(&self) -> Vec<Setting> {
+        let das = self.game.config().das.unwrap_or(constants::DAS);
+        vec![
+            Setting::int("das", das.delay as i32, constants::DAS_DELAY_RANGE),
+            Setting::int("arr", das.arr as i32, constants::DAS_ARR_RANGE),
+        ]
+    }
+
+    fn set_setting(&mut self, index: usize, value: SettingValue) {
+        let mut das = self.game.config().das.unwrap_or(constants::DAS);
+        match index {
+            0 => das.delay = value.get() as u64,
+            _ => das.arr = value.get() as u64,
+        }
+        self.game.set_das(Some(das));
+    }
+
+    /// Saves the game, but not animations.
+    fn save_state(&self) -> Option<Vec<u8>> {
+        let state = self.game.state();
+        let mut w = StateWriter::new(STATE_VERSION);
+
+        w.bool(self.big);
+
+        let playfield = &state.playfield;
+        w.u8(playfield.width());
+        w.u8(playfield.height());
+        for y in 0..playfield.height() as i8 {
+            for x in 0..playfield.width() as i8 {
+                write_opt_piece(&mut w, playfield.get(Pos { x, y }).flatten());
+            }
+        }
+
+        w.len(state.queue.len());
+        for &piece in &state.queue {
+            write_opt_piece(&mut w, Some(piece));
+        }
+
+        let input = &state.input_state;
+        let keys = input.keys_down;
+        w.u8([
+            keys.left,
+            keys.right,
+            keys.soft_drop,
+            keys.hard_drop,
+            keys.rot_cw,
+            keys.rot_ccw,
+            keys.rot_180,
+            keys.hold,
+        ]
+        .iter()
+        .rev()
+        .fold(0, |bits, &is_set| (bits << 1) | is_set as u8));
+        for das in [&input.left, &input.right, &input.soft_drop] {
+            match das {
+                DasState::Released => w.u8(0),
+                DasState::Pressed { repeat_at: None } => w.u8(1),
+                DasState::Pressed { repeat_at: Some(t) } => {
+                    w.u8(2);
+                    w.u64(*t);
+                }
+            }
+        }
+
+        w.u64(state.frame);
+
+        let falling = state.falling_piece;
+        write_opt_piece(&mut w, Some(falling.piece));
+        w.u8(falling.rot.index() as u8);
+        w.i8(falling.pos.x);
+        w.i8(falling.pos.y);
+        w.u64(falling.frame_of_last_move);
+
+        write_opt_piece(&mut w, state.held_piece);
+        w.bool(state.hold_used);
+
+        w.len(state.rows_to_clear.len());
+        for &row in &state.rows_to_clear {
+            w.i8(row);
+        }
+
+        w.bool(state.game_over);
+
+        Some(w.finish())
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> io::Result<()> {
+        let mut r = StateReader::new(state, STATE_VERSION)?;
+
+        let big = r.bool()?;
+
+        let width = r.u8()?;
+        let height = r.u8()?;
+        if width > i8::MAX as u8 || height > i8::MAX as u8 {
+            return Err(invalid_data("playfield too large"));
+        }
+        let mut playfield = tetris_logic::Playfield::new(width, height);
+        for y in 0..height as i8 {
+            for x in 0..width as i8 {
+                playfield.set(Pos { x, y }, read_opt_piece(&mut r)?);
+            }
+        }
+
+        let queue = (0..r.len()?)
+            .map(|_| read_piece(&mut r))
+            .collect::<io::Result<_>>()?;
+
+        let keys = r.u8()?;
+        let key = |i: u8| keys & (1 << i) != 0;
+        let keys_down = FrameInput {
+            left: key(0),
+            right: key(1),
+            soft_drop: key(2),
+            hard_drop: key(3),
+            rot_cw: key(4),
+            rot_ccw: key(5),
+            rot_180: key(6),
+            hold: key(7),
+        };
+        let mut read_das = || -> io::Result<DasState<u64>> {
+            match r.u8()? {
+                0 => Ok(DasState::Released),
+                1 => Ok(DasState::Pressed { repeat_at: None }),
+                2 => Ok(DasState::Pressed {
+                    repeat_at: Some(r.u64()?),
+                }),
+                other => Err(invalid_data(format!("invalid DAS state {other}"))),
+            }
+        };
+        let input_state = tetris_logic::InputState {
+            left: read_das()?,
+            right: read_das()?,
+            soft_drop: read_das()?,
+            keys_down,
+        };
+
+        let frame = r.u64()?;
+
+        let falling_piece = FallingPiece {
+            piece: read_piece(&mut r)?,
+            rot: Rot::from_index(r.u8()? as usize),
+            pos: Pos::new(r.i8()?, r.i8()?),
+            frame_of_last_move: r.u64()?,
+        };
+        // Check each block with checked arithmetic, since a corrupt position
+        // could overflow in `coordinates_at()`.
+        let in_playfield = falling_piece.piece.coordinates().iter().all(|&offset| {
+            let offset = falling_piece.rot.apply(offset);
+            let x = falling_piece.pos.x.checked_add(offset.dx);
+            let y = falling_piece.pos.y.checked_add(offset.dy);
+            x.zip(y)
+                .is_some_and(|(x, y)| playfield.get(Pos { x, y }).is_some())
+        });
+        if !in_playfield {
+            return Err(invalid_data("falling piece is outside the playfield"));
+        }
+
+        let held_piece = read_opt_piece(&mut r)?;
+        let hold_used = r.bool()?;
+
+        let rows_to_clear = (0..r.len()?)
+            .map(|_| match r.i8()? {
+                row if (0..height as i8).contains(&row) => Ok(row),
+                row => Err(invalid_data(format!("row {row} is outside the playfield"))),
+            })
+            .collect::<io::Result<_>>()?;
+
+        let game_over = r.bool()?;
+
+        r.finish()?;
+
+        let state = GameState {
+            playfield,
+            queue,
+            input_state,
+            frame,
+            falling_piece,
+            held_piece,
+            hold_used,
+            rows_to_clear,
+            game_over,
+        };
+        let das = self.game.config().das;
+        self.game =
+            tetris_logic::Game::from_state(config(), state, Box::new(crate::rng::new_rng()))
+                .ok_or_else(|| invalid_data("playfield size does not match config"))?;
+        self.game.set_das(das);
+        self.big = big;
+        self.soon_to_lock_anim.reset();
+        self.locked_anim = None;
+        self.hard_drop_anim = None;
+        self.clear_anim = None;
+        let mut queue_iter = self.game.queue().next_pieces();
+        self.queue.fill_with(|| queue_iter.next());
+
+        Ok(())
+    }
 }
 
 impl Widget<FullInput> for Tetris {
@@ -74,6 +302,7 @@ impl Widget<FullInput> for Tetris {
             return; // freeze game
         }
 
+        let was_game_over = self.game.is_game_over();
         let result = self.game.step(
             1,
             FrameInput {
@@ -89,6 +318,7 @@ impl Widget<FullInput> for Tetris {
         );
 
         if let Ok(output) = &result {
+            self.sounds.extend(output_sounds(output));
             if let Some(rows_cleared) = &output.rows_cleared {
                 self.clear_anim = Some(ClearAnimation::new(rows_cleared.clone()));
             }
@@ -104,6 +334,10 @@ impl Widget<FullInput> for Tetris {
             }
         }
 
+        if self.game.is_game_over() && !was_game_over {
+            self.sounds.push(sounds::GAME_OVER);
+        }
+
         if self.game.can_soft_drop() {
             self.soon_to_lock_anim.reset();
         }
@@ -124,6 +358,7 @@ impl Widget<FullInput> for Tetris {
 
         // Draw background
         fb.fill(colors::BACKGROUND);
+        let fb = &mut fb.centered(coordinates::SIZE);
 
         // Draw static blocks
         for y in 0..height as i8 {
@@ -200,6 +435,61 @@ impl Widget<FullInput> for Tetris {
     }
 }
 
+fn config() -> tetris_logic::Config<u64> {
+    tetris_logic::Config {
+        das: Some(constants::DAS),
+        ..Default::default()
+    }
+}
+
+/// Returns the sound effects for what happened on a frame.
+fn output_sounds(output: &FrameOutput<u64>) -> impl Iterator<Item = Sound> {
+    let succeeded = |r: &Option<Result<(), _>>| matches!(r, Some(Ok(())));
+    let rotations = [&output.rot_cw, &output.rot_ccw, &output.rot_180];
+    let moved = succeeded(&output.left) || succeeded(&output.right);
+    let rotated = rotations.iter().any(|r| succeeded(r));
+    let blocked = rotations.iter().any(|r| matches!(r, Some(Err(_))));
+    let held = matches!(output.hold, Some(Ok(())));
+    let locked = output.locked_piece.map(|_| match output.hard_drop {
+        Some(Ok(_)) => sounds::HARD_DROP,
+        _ => sounds::LOCK,
+    });
+    let cleared = (output.rows_cleared.as_ref()).map(|rows| match rows.len() {
+        4.. => sounds::TETRIS,
+        _ => sounds::LINE_CLEAR,
+    });
+    [
+        moved.then_some(sounds::MOVE),
+        rotated.then_some(sounds::ROTATE),
+        blocked.then_some(sounds::BLOCKED),
+        held.then_some(sounds::HOLD),
+        locked,
+        cleared,
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Writes a piece as `0` for `None` or its index in [`Tetromino::ALL`] plus
+/// one.
+fn write_opt_piece(w: &mut StateWriter, piece: Option<Tetromino>) {
+    w.u8(piece.map_or(0, |p| {
+        Tetromino::ALL.iter().position(|&q| q == p).unwrap_or(0) as u8 + 1
+    }));
+}
+fn read_opt_piece(r: &mut StateReader<'_>) -> io::Result<Option<Tetromino>> {
+    match r.u8()? {
+        0 => Ok(None),
+        i => match Tetromino::ALL.get(i as usize - 1) {
+            Some(&piece) => Ok(Some(piece)),
+            None => Err(invalid_data(format!("invalid piece {i}"))),
+        },
+    }
+}
+fn read_piece(r: &mut StateReader<'_>) -> io::Result<Tetromino> {
+    read_opt_piece(r)?.ok_or_else(|| invalid_data("missing piece"))
+}
+
 fn fill_piece_preview(transform: Transform, fb: &mut FrameBufferRect<'_>, piece: Tetromino) {
     fill_darkened_piece_preview(transform, fb, piece, 0.0);
 }
@@ -42,6 +42,7 @@ impl<T, const CAP: usize> ArrayVec<T, CAP> {
         self.len += 1;
     }
 
+    #[allow(clippy::result_unit_err)]
     pub fn try_push(&mut self, element: T) -> Result<(), ()> {
         if (self.len as usize) < CAP {
             self.push(element);
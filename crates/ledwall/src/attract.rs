@@ -0,0 +1,218 @@
+//! What the shell shows while nobody is playing.
+//!
+//! The shell goes idle when all controllers disconnect, or when nobody has
+//! touched a controller for a while. What happens then is configured in
+//! `attract.toml` within [`storage::dir()`]:
+//!
+//! ```toml
+//! mode = "attract" # "blank", "keep", or "attract"
+//! idle_minutes = 5.0 # 0 to only go idle when all controllers disconnect
+//! activity_seconds = 60.0
+//! transition_seconds = 2.0
+//! activities = ["life", "rainbow"] # all that support attract mode if empty
+//! ```
+//!
+//! Pressing any button, or connecting a controller, returns to the activity
+//! that was selected before. Activities that ask to
+//! [stay awake](crate::Activity::stay_awake) keep running regardless of the
+//! mode.
+//!
+//! [`storage::dir()`]: crate::storage::dir
+
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::activities::{self, Registration};
+use crate::{Activity, BLACK, FPS, FrameBuffer, FrameBufferRect, FullInput, storage};
+
+/// File name of the attract mode configuration within [`storage::dir()`].
+pub const FILE_NAME: &str = "attract.toml";
+
+/// Contents of the attract mode configuration file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AttractConfig {
+    /// What to show while idle.
+    pub mode: IdleMode,
+    /// Number of minutes without input after which the shell goes idle, or 0
+    /// to only go idle when all controllers disconnect.
+    pub idle_minutes: f32,
+    /// Number of seconds to show each activity in attract mode.
+    pub activity_seconds: f32,
+    /// Number of seconds to crossfade between activities in attract mode.
+    pub transition_seconds: f32,
+    /// IDs of the activities to cycle through in attract mode, in order, or
+    /// empty for all activities that support it. Activities that don't
+    /// support attract mode are skipped.
+    pub activities: Vec<String>,
+}
+
+impl Default for AttractConfig {
+    fn default() -> Self {
+        Self {
+            mode: IdleMode::Blank,
+            idle_minutes: 0.0,
+            activity_seconds: 60.0,
+            transition_seconds: 2.0,
+            activities: vec![],
+        }
+    }
+}
+
+impl AttractConfig {
+    /// Loads the configuration from `dir`, returning the default configuration
+    /// if the file does not exist.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        storage::load_toml(dir, FILE_NAME)
+    }
+
+    /// Returns the number of frames without input after which the shell goes
+    /// idle, or `None` if it only goes idle when all controllers disconnect.
+    pub fn idle_frames(&self) -> Option<u64> {
+        let frames = (self.idle_minutes * 60.0 * FPS as f32) as u64;
+        (frames > 0).then_some(frames)
+    }
+}
+
+/// What the shell shows while idle.
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleMode {
+    /// Turn off the panel.
+    #[default]
+    Blank,
+    /// Keep running the selected activity without input.
+    Keep,
+    /// Cycle through activities that support attract mode.
+    Attract,
+}
+
+/// Attract mode, which cycles through activities with a crossfade between
+/// them.
+pub struct Attract {
+    activities: Vec<Box<dyn Activity>>,
+    current: usize,
+    /// Number of frames that the current activity has been shown.
+    frame: u32,
+    activity_frames: u32,
+    transition_frames: u32,
+    /// Last frame shown before the current activity, to crossfade from.
+    fade_from: FrameBuffer,
+}
+
+impl Attract {
+    /// Starts attract mode with fresh instances of the activities chosen by
+    /// `config`, crossfading from `fb`. Returns `None` if none of the
+    /// activities support attract mode.
+    pub fn new(config: &AttractConfig, fb: &FrameBuffer) -> Option<Self> {
+        let registrations: Vec<&Registration> = if config.activities.is_empty() {
+            activities::REGISTRY.iter().collect()
+        } else {
+            config
+                .activities
+                .iter()
+                .filter_map(|id| activities::get(id))
+                .collect()
+        };
+        let activities: Vec<Box<dyn Activity>> = (registrations.into_iter())
+            .filter(|r| r.info.attract)
+            .map(|r| {
+                let mut activity = (r.new)();
+                activity.set_size(fb.size());
+                activity.start_attract_mode();
+                activity
+            })
+            .collect();
+        (!activities.is_empty()).then(|| Self {
+            activities,
+            current: 0,
+            frame: 0,
+            activity_frames: ((config.activity_seconds * FPS as f32) as u32).max(1),
+            transition_frames: (config.transition_seconds * FPS as f32) as u32,
+            fade_from: fb.clone(),
+        })
+    }
+
+    /// Steps the current activity, switching to the next one if it has been
+    /// shown long enough, and draws it to `fb`.
+    pub fn step_and_draw(&mut self, fb: &mut FrameBuffer) {
+        self.frame += 1;
+        if self.frame > self.activity_frames && self.activities.len() > 1 {
+            self.fade_from.clone_from(fb);
+            self.current = (self.current + 1) % self.activities.len();
+            self.frame = 1;
+        }
+
+        let activity = &mut self.activities[self.current];
+        activity.step(FullInput::default());
+        activity.take_sounds(); // attract mode is silent
+        activity.take_toasts(); // nobody is there to read them
+        let mut rect = FrameBufferRect::new(fb);
+        rect.fill(BLACK);
+        activity.draw(&mut rect);
+
+        if self.frame < self.transition_frames {
+            let t = self.frame as f32 / self.transition_frames as f32;
+            for (color, from) in
+                std::iter::zip(fb.as_flattened_mut(), self.fade_from.as_flattened())
+            {
+                *color = from.mix(*color, t);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_SIZE;
+
+    #[test]
+    fn parse_config() {
+        let config: AttractConfig = toml::from_str(
+            r#"
+            mode = "attract"
+            idle_minutes = 0.5
+            activities = ["life", "tetris", "rainbow"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.mode, IdleMode::Attract);
+        assert_eq!(config.idle_frames(), Some(30 * FPS as u64));
+        assert_eq!(config.activity_seconds, 60.0);
+
+        assert!(toml::from_str::<AttractConfig>("mode = \"sleep\"").is_err());
+        assert_eq!(AttractConfig::default().idle_frames(), None);
+    }
+
+    #[test]
+    fn cycle() {
+        let mut fb = FrameBuffer::new(DEFAULT_SIZE);
+        let mut config = AttractConfig {
+            mode: IdleMode::Attract,
+            activity_seconds: 1.0,
+            activities: vec!["life".into(), "tetris".into(), "rainbow".into()],
+            ..AttractConfig::default()
+        };
+
+        // Tetris can't play by itself, so it is skipped.
+        let mut attract = Attract::new(&config, &fb).unwrap();
+        let mut ids = vec![];
+        for _ in 0..3 * FPS {
+            attract.step_and_draw(&mut fb);
+            let id = attract.activities[attract.current].id();
+            if ids.last() != Some(&id) {
+                ids.push(id);
+            }
+        }
+        assert_eq!(ids, ["life", "rainbow", "life"]);
+
+        config.activities = vec!["tetris".into()];
+        assert!(Attract::new(&config, &fb).is_none());
+        config.activities = vec![];
+        let attract = Attract::new(&config, &fb).unwrap();
+        assert_eq!(attract.activities[0].id(), "rainbow");
+    }
+}
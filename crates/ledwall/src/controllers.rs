@@ -0,0 +1,574 @@
+//! Assignment of physical controllers to players.
+//!
+//! Controllers are identified by their UUID and assigned to players in
+//! `controllers.toml` within [`storage::dir()`]:
+//!
+//! ```toml
+//! [colors]
+//! blue = "7777ff"
+//! green = "88dd88"
+//! red = "ff7777"
+//! yellow = "eedd55"
+//!
+//! [deadzones]
+//! stick = 0.1
+//! trigger = 0.05
+//!
+//! [[controller]]
+//! uuid = "05000000c82d00002090000000010000"
+//! player = "blue"
+//! remap = { a = "b", b = "a" } # swap A and B
+//! deadzones = { stick = 0.2 } # worn out stick
+//! ```
+//!
+//! A controller that isn't in the file doesn't control anything until someone
+//! presses A on it, which claims the first player with no controller
+//! connected and adds the controller to the file.
+//!
+//! [`storage::dir()`]: crate::storage::dir
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Buttons, ControllerState, Deadzones, FrameInputs, MAX_PLAYERS, Player, Rgb, storage};
+
+/// File name of the controller assignments within [`storage::dir()`].
+pub const FILE_NAME: &str = "controllers.toml";
+
+/// UUIDs of the controllers that the LED wall was built with.
+const DEFAULT_BLUE_UUID: Uuid = Uuid([5, 0, 0, 0, 200, 45, 0, 0, 32, 144, 0, 0, 0, 1, 0, 0]);
+const DEFAULT_GREEN_UUID: Uuid = Uuid([3, 0, 0, 0, 200, 45, 0, 0, 32, 144, 0, 0, 0, 1, 0, 0]);
+
+const DEFAULT_BLUE_COLOR: Rgb = Rgb::from_hex(0x7777FF);
+const DEFAULT_GREEN_COLOR: Rgb = Rgb::from_hex(0x88DD88);
+const DEFAULT_RED_COLOR: Rgb = Rgb::from_hex(0xFF7777);
+const DEFAULT_YELLOW_COLOR: Rgb = Rgb::from_hex(0xEEDD55);
+
+/// Contents of the controller assignment file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControllerMap {
+    /// Color of each player in the menu.
+    pub colors: PlayerColors,
+    /// Deadzones for controllers that don't override them.
+    pub deadzones: Deadzones,
+    /// Controllers assigned to players. Multiple controllers may be assigned
+    /// to the same player.
+    #[serde(rename = "controller")]
+    pub controllers: Vec<ControllerConfig>,
+}
+
+impl Default for ControllerMap {
+    fn default() -> Self {
+        Self {
+            colors: PlayerColors::default(),
+            deadzones: Deadzones::default(),
+            controllers: vec![
+                ControllerConfig {
+                    uuid: DEFAULT_BLUE_UUID,
+                    player: Player::Blue,
+                    remap: Remap::default(),
+                    deadzones: None,
+                },
+                ControllerConfig {
+                    uuid: DEFAULT_GREEN_UUID,
+                    player: Player::Green,
+                    remap: Remap::default(),
+                    deadzones: None,
+                },
+            ],
+        }
+    }
+}
+
+impl ControllerMap {
+    /// Loads controller assignments from `dir`, returning the default
+    /// assignments if the file does not exist.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        storage::load_toml(dir, FILE_NAME)
+    }
+
+    /// Saves controller assignments to `dir`.
+    #[cfg_attr(not(feature = "gilrs"), allow(dead_code))] // only claimed using gilrs
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let s = toml::to_string(self).map_err(io::Error::other)?;
+        storage::write_atomic(&dir.join(FILE_NAME), s.as_bytes())
+    }
+
+    /// Returns the configuration for the controller with UUID `uuid`.
+    pub fn get(&self, uuid: Uuid) -> Option<&ControllerConfig> {
+        self.controllers.iter().find(|c| c.uuid == uuid)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlayerColors {
+    pub blue: HexColor,
+    pub green: HexColor,
+    pub red: HexColor,
+    pub yellow: HexColor,
+}
+
+impl Default for PlayerColors {
+    fn default() -> Self {
+        Self {
+            blue: HexColor(DEFAULT_BLUE_COLOR),
+            green: HexColor(DEFAULT_GREEN_COLOR),
+            red: HexColor(DEFAULT_RED_COLOR),
+            yellow: HexColor(DEFAULT_YELLOW_COLOR),
+        }
+    }
+}
+
+impl PlayerColors {
+    pub fn get(self, player: Player) -> Rgb {
+        match player {
+            Player::Blue => self.blue.0,
+            Player::Green => self.green.0,
+            Player::Red => self.red.0,
+            Player::Yellow => self.yellow.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ControllerConfig {
+    pub uuid: Uuid,
+    pub player: Player,
+    #[serde(default, skip_serializing_if = "Remap::is_empty")]
+    pub remap: Remap,
+    /// Deadzones for this controller, overriding [`ControllerMap::deadzones`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadzones: Option<Deadzones>,
+}
+
+/// Controller UUID, written as 32 hexadecimal digits.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Uuid(pub [u8; 16]);
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+/// Parses a UUID from hexadecimal digits, ignoring dashes.
+impl FromStr for Uuid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits: Vec<u8> = s.bytes().filter(|&c| c != b'-').collect();
+        let (chunks, []) = digits.as_chunks::<2>() else {
+            return Err(format!("invalid UUID {s:?}"));
+        };
+        let bytes: Vec<u8> = chunks
+            .iter()
+            .map(|&[hi, lo]| u8::from_str_radix(std::str::from_utf8(&[hi, lo]).ok()?, 16).ok())
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("invalid UUID {s:?}"))?;
+        Ok(Self(bytes.try_into().map_err(|_| {
+            format!("UUID {s:?} must have 32 hexadecimal digits")
+        })?))
+    }
+}
+
+impl TryFrom<String> for Uuid {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Uuid> for String {
+    fn from(uuid: Uuid) -> Self {
+        uuid.to_string()
+    }
+}
+
+/// Color written as 6 hexadecimal digits, optionally preceded by `#`.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct HexColor(pub Rgb);
+
+impl TryFrom<String> for HexColor {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let digits = s.strip_prefix('#').unwrap_or(&s);
+        match u32::from_str_radix(digits, 16) {
+            Ok(hex) if digits.len() == 6 => Ok(Self(Rgb::from_hex(hex))),
+            _ => Err(format!("invalid color {s:?}")),
+        }
+    }
+}
+
+impl From<HexColor> for String {
+    fn from(HexColor(Rgb([r, g, b])): HexColor) -> Self {
+        format!("{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+/// Buttons that act as other buttons, applied after the default mapping from
+/// gamepad buttons to [`Buttons`]. Buttons that aren't remapped act as
+/// themselves.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(
+    try_from = "BTreeMap<String, String>",
+    into = "BTreeMap<String, String>"
+)]
+pub struct Remap(Vec<(usize, usize)>);
+
+impl Remap {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the buttons that `buttons` act as.
+    pub fn apply(&self, buttons: Buttons) -> Buttons {
+        let held = buttons.to_array();
+        let mut ret = held;
+        for &(from, _to) in &self.0 {
+            ret[from] = false;
+        }
+        for &(from, to) in &self.0 {
+            ret[to] |= held[from];
+        }
+        Buttons::from_array(ret)
+    }
+}
+
+impl TryFrom<BTreeMap<String, String>> for Remap {
+    type Error = String;
+
+    fn try_from(map: BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        let button_index = |name: &str| {
+            Buttons::NAMES
+                .iter()
+                .position(|n| n.eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("unknown button {name:?}"))
+        };
+        let mut pairs = map
+            .iter()
+            .map(|(from, to)| Ok((button_index(from)?, button_index(to)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        pairs.sort_unstable();
+        if pairs.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err("button remapped more than once".to_string());
+        }
+        Ok(Self(pairs))
+    }
+}
+
+impl From<Remap> for BTreeMap<String, String> {
+    fn from(remap: Remap) -> Self {
+        remap
+            .0
+            .into_iter()
+            .map(|(from, to)| {
+                (
+                    Buttons::NAMES[from].to_owned(),
+                    Buttons::NAMES[to].to_owned(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Tracks which players have a controller connected and lets unassigned
+/// controllers claim a player.
+#[derive(Debug, Default)]
+pub struct Controllers {
+    pub map: ControllerMap,
+    /// Whether the map has changed since it was last saved.
+    unsaved: bool,
+
+    /// Number of unassigned controllers connected on the last update.
+    unassigned: usize,
+    /// Buttons that were held when each controller claimed a player, which are
+    /// ignored until they are released.
+    held_since_claim: Vec<(Uuid, Buttons)>,
+}
+
+impl Controllers {
+    pub fn new(map: ControllerMap) -> Self {
+        Self {
+            map,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the number of connected controllers that aren't assigned to a
+    /// player.
+    pub fn unassigned(&self) -> usize {
+        self.unassigned
+    }
+
+    /// Returns whether the map has changed since the last call, and should be
+    /// saved.
+    #[cfg_attr(not(feature = "gilrs"), allow(dead_code))] // only claimed using gilrs
+    pub fn take_unsaved(&mut self) -> bool {
+        std::mem::take(&mut self.unsaved)
+    }
+
+    /// Combines the state of each connected controller into the state of
+    /// each player.
+    ///
+    /// `pads` has the raw state of each controller. Buttons derived from the
+    /// raw axes (see [`Axes::to_buttons()`]) are added before remapping, and
+    /// deadzones are applied to the axes.
+    ///
+    /// Pressing A on an unassigned controller assigns it to the first player
+    /// without a connected controller.
+    ///
+    /// [`Axes::to_buttons()`]: crate::Axes::to_buttons
+    #[cfg_attr(not(feature = "gilrs"), allow(dead_code))] // only used with gilrs
+    pub fn update(&mut self, pads: &[(Uuid, ControllerState)]) -> FrameInputs {
+        let mut inputs: FrameInputs = [None; MAX_PLAYERS];
+        let mut unassigned = vec![];
+
+        let states: Vec<(Uuid, Option<&ControllerConfig>, ControllerState)> = pads
+            .iter()
+            .map(|&(uuid, raw)| {
+                let config = self.map.get(uuid);
+                let deadzones = config
+                    .and_then(|c| c.deadzones)
+                    .unwrap_or(self.map.deadzones);
+                let axes = raw.axes.with_deadzones(deadzones);
+                let mut buttons = raw.buttons | raw.axes.to_buttons();
+                if let Some(config) = config {
+                    buttons = config.remap.apply(buttons);
+                }
+                (uuid, config, ControllerState { buttons, axes })
+            })
+            .collect();
+
+        self.held_since_claim.retain_mut(|(uuid, held)| {
+            let current = states.iter().find(|(u, _, _)| u == uuid);
+            *held = *held & current.map(|(_, _, s)| s.buttons).unwrap_or_default();
+            *held != Buttons::default()
+        });
+
+        for (uuid, config, mut state) in states {
+            let Some(config) = config else {
+                unassigned.push((uuid, state.buttons));
+                continue;
+            };
+            if let Some((_, held)) = self.held_since_claim.iter().find(|(u, _)| *u == uuid) {
+                state.buttons = state.buttons & !*held;
+            }
+            let input = &mut inputs[config.player as usize];
+            *input = Some(match *input {
+                Some(other) => other.merge(state),
+                None => state,
+            });
+        }
+
+        self.unassigned = 0;
+        for (uuid, buttons) in unassigned {
+            let free_player = Player::ALL
+                .into_iter()
+                .find(|&p| inputs[p as usize].is_none());
+            match free_player {
+                Some(player) if buttons.a => {
+                    println!("Assigned controller with UUID {uuid} to {player} player");
+                    self.map.controllers.push(ControllerConfig {
+                        uuid,
+                        player,
+                        remap: Remap::default(),
+                        deadzones: None,
+                    });
+                    self.unsaved = true;
+                    self.held_since_claim.push((uuid, buttons));
+                    inputs[player as usize] = Some(ControllerState::default());
+                }
+                _ => self.unassigned += 1,
+            }
+        }
+
+        inputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Axes;
+    use crate::input::AXIS_BUTTON_THRESHOLD;
+
+    const UNKNOWN_UUID: Uuid = Uuid([7; 16]);
+
+    fn buttons(s: &str) -> Buttons {
+        s.parse().unwrap()
+    }
+
+    fn pad(s: &str) -> ControllerState {
+        buttons(s).into()
+    }
+
+    fn held(inputs: FrameInputs) -> [Option<Buttons>; MAX_PLAYERS] {
+        inputs.map(|input| Some(input?.buttons))
+    }
+
+    #[test]
+    fn parse_map() {
+        let map: ControllerMap = toml::from_str(
+            r##"
+            colors = { green = "#00ff00" }
+
+            [[controller]]
+            uuid = "03000000-c82d-0000-2090-000000010000"
+            player = "blue"
+            remap = { a = "b", B = "a", x = "star" }
+            deadzones = { stick = 0.25 }
+            "##,
+        )
+        .unwrap();
+        assert_eq!(map.colors.blue, HexColor(DEFAULT_BLUE_COLOR));
+        assert_eq!(map.colors.green, HexColor(Rgb([0, 255, 0])));
+        let config = map.get(DEFAULT_GREEN_UUID).unwrap();
+        assert_eq!(config.player, Player::Blue);
+        assert_eq!(config.remap.apply(buttons("a+up")), buttons("b+up"));
+        assert_eq!(config.remap.apply(buttons("a+b")), buttons("a+b"));
+        assert_eq!(config.remap.apply(buttons("x+star")), buttons("star"));
+        assert_eq!(map.deadzones, Deadzones::default());
+        let deadzones = config.deadzones.unwrap();
+        assert_eq!(deadzones.stick, 0.25);
+        assert_eq!(deadzones.trigger, Deadzones::default().trigger);
+
+        let saved = toml::to_string(&map).unwrap();
+        assert_eq!(toml::from_str::<ControllerMap>(&saved).unwrap(), map);
+
+        for invalid in [
+            r#"colors = { blue = "blue" }"#,
+            r#"controller = [{ uuid = "1234", player = "blue" }]"#,
+            r#"controller = [{ uuid = "03000000c82d00002090000000010000", player = "purple" }]"#,
+            r#"controller = [{ uuid = "03000000c82d00002090000000010000", player = "blue", remap = { a = "z" } }]"#,
+            r#"controller = [{ uuid = "03000000c82d00002090000000010000", player = "blue", remap = { a = "b", A = "x" } }]"#,
+        ] {
+            assert!(
+                toml::from_str::<ControllerMap>(invalid).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn claim() {
+        let mut controllers = Controllers::new(ControllerMap::default());
+
+        // Unknown controllers don't control anything.
+        let inputs =
+            controllers.update(&[(DEFAULT_GREEN_UUID, pad("up")), (UNKNOWN_UUID, pad("b"))]);
+        assert_eq!(held(inputs), [None, Some(buttons("up")), None, None]);
+        assert_eq!(controllers.unassigned(), 1);
+        assert!(!controllers.take_unsaved());
+
+        // Pressing A claims the first free player, and A is ignored until it
+        // is released.
+        let inputs =
+            controllers.update(&[(DEFAULT_GREEN_UUID, pad("up")), (UNKNOWN_UUID, pad("a+b"))]);
+        assert_eq!(
+            held(inputs),
+            [Some(Buttons::default()), Some(buttons("up")), None, None],
+        );
+        assert_eq!(controllers.unassigned(), 0);
+        assert!(controllers.take_unsaved());
+        assert_eq!(
+            controllers.map.get(UNKNOWN_UUID).unwrap().player,
+            Player::Blue
+        );
+
+        let inputs = controllers.update(&[(UNKNOWN_UUID, pad("a+b+x"))]);
+        assert_eq!(held(inputs), [Some(buttons("x")), None, None, None]);
+        controllers.update(&[(UNKNOWN_UUID, pad("."))]);
+        let inputs = controllers.update(&[(UNKNOWN_UUID, pad("a"))]);
+        assert_eq!(held(inputs), [Some(buttons("a")), None, None, None]);
+
+        // Controllers assigned to the same player are combined, and later
+        // controllers claim the remaining players in order until there are
+        // none left.
+        let pads = [
+            (DEFAULT_BLUE_UUID, pad("b")),
+            (UNKNOWN_UUID, pad("a")),
+            (DEFAULT_GREEN_UUID, pad(".")),
+            (Uuid([8; 16]), pad("a")),
+            (Uuid([9; 16]), pad("a")),
+            (Uuid([10; 16]), pad("a")),
+        ];
+        let inputs = controllers.update(&pads);
+        let connected = Some(Buttons::default());
+        assert_eq!(
+            held(inputs),
+            [Some(buttons("a+b")), connected, connected, connected],
+        );
+        assert_eq!(controllers.unassigned(), 1);
+        assert_eq!(
+            controllers.map.get(Uuid([8; 16])).unwrap().player,
+            Player::Red
+        );
+        assert_eq!(
+            controllers.map.get(Uuid([9; 16])).unwrap().player,
+            Player::Yellow
+        );
+        assert!(controllers.map.get(Uuid([10; 16])).is_none());
+    }
+
+    #[test]
+    fn analog() {
+        let mut map = ControllerMap::default();
+        map.controllers[1].deadzones = Some(Deadzones {
+            stick: 0.5,
+            trigger: 0.5,
+        });
+        map.controllers[1].remap =
+            Remap::try_from(BTreeMap::from([("up".to_string(), "x".to_string())])).unwrap();
+        let mut controllers = Controllers::new(map);
+
+        let axes = Axes {
+            left_stick: [0.0, 0.8],
+            right_stick: [0.05, 0.0],
+            lt: 0.7,
+            rt: 0.02,
+        };
+        let state = ControllerState {
+            buttons: buttons("a"),
+            axes,
+        };
+        let [blue, green, ..] =
+            controllers.update(&[(DEFAULT_BLUE_UUID, state), (DEFAULT_GREEN_UUID, state)]);
+
+        // Default deadzones
+        let blue = blue.unwrap();
+        assert_eq!(blue.buttons, buttons("a+up+lt"));
+        assert!((blue.axes.left_stick[1] - (0.8 - 0.1) / 0.9).abs() < 1e-6);
+        assert_eq!(blue.axes.right_stick, [0.0, 0.0]);
+        assert_eq!(blue.axes.rt, 0.0);
+
+        // Larger deadzones, which don't move the point where derived buttons
+        // trigger, with derived buttons remapped
+        let green = green.unwrap();
+        assert_eq!(green.buttons, buttons("a+x+lt"));
+        assert!((green.axes.left_stick[1] - 0.6).abs() < 1e-6);
+        assert!((green.axes.lt - 0.4).abs() < 1e-6);
+
+        // Derived buttons trigger at the threshold of the raw axes.
+        let state = ControllerState {
+            buttons: Buttons::default(),
+            axes: Axes {
+                left_stick: [AXIS_BUTTON_THRESHOLD + 0.01, 0.0],
+                ..Axes::default()
+            },
+        };
+        let [blue, ..] = controllers.update(&[(DEFAULT_BLUE_UUID, state)]);
+        assert_eq!(blue.unwrap().buttons, buttons("right"));
+    }
+}
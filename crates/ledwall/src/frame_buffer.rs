@@ -1,8 +1,77 @@
 use std::ops::{Index, IndexMut};
 
-use crate::{HEIGHT, Rgb, WIDTH};
+use serde::{Deserialize, Serialize};
 
-pub type FrameBuffer = [[Rgb; WIDTH]; HEIGHT];
+use crate::{BLACK, Rgb};
+
+/// Pixels shown on the panel, whose size depends on how many panels make up
+/// the wall. Indexing returns a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameBuffer {
+    width: usize,
+    /// Pixels in row-major order.
+    pixels: Vec<Rgb>,
+}
+
+impl FrameBuffer {
+    /// Constructs a black frame buffer of `[width, height]` pixels.
+    pub fn new(size: [usize; 2]) -> Self {
+        Self::filled(size, BLACK)
+    }
+    pub fn filled([width, height]: [usize; 2], color: Rgb) -> Self {
+        assert!(width > 0, "frame buffer must not be empty");
+        Self {
+            width,
+            pixels: vec![color; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.pixels.len() / self.width
+    }
+    pub fn size(&self) -> [usize; 2] {
+        [self.width(), self.height()]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Rgb]> {
+        self.pixels.chunks_exact(self.width)
+    }
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Rgb]> {
+        self.pixels.chunks_exact_mut(self.width)
+    }
+
+    /// Returns row `y`, or `None` if it is out of bounds.
+    pub fn get(&self, y: usize) -> Option<&[Rgb]> {
+        (y < self.height()).then(|| &self[y])
+    }
+    pub fn get_mut(&mut self, y: usize) -> Option<&mut [Rgb]> {
+        (y < self.height()).then(|| &mut self[y])
+    }
+
+    pub fn as_flattened(&self) -> &[Rgb] {
+        &self.pixels
+    }
+    pub fn as_flattened_mut(&mut self) -> &mut [Rgb] {
+        &mut self.pixels
+    }
+}
+
+impl Index<usize> for FrameBuffer {
+    type Output = [Rgb];
+
+    fn index(&self, y: usize) -> &Self::Output {
+        &self.pixels[y * self.width..][..self.width]
+    }
+}
+
+impl IndexMut<usize> for FrameBuffer {
+    fn index_mut(&mut self, y: usize) -> &mut Self::Output {
+        &mut self.pixels[y * self.width..][..self.width]
+    }
+}
 
 /// Rectangular region within the frame buffer.
 pub struct FrameBufferRect<'a> {
@@ -13,7 +82,7 @@ pub struct FrameBufferRect<'a> {
 
 impl<'a> FrameBufferRect<'a> {
     pub fn new(frame_buffer: &'a mut FrameBuffer) -> FrameBufferRect<'a> {
-        let size = [frame_buffer[0].len(), frame_buffer.len()];
+        let size = frame_buffer.size();
         FrameBufferRect {
             frame_buffer,
             offset: [0; 2],
@@ -43,6 +112,17 @@ impl<'a> FrameBufferRect<'a> {
         }
     }
 
+    /// Returns a region of at most `size` in the middle of this one, for
+    /// layouts that are designed for a single panel.
+    pub fn centered<'b>(&'b mut self, size: [usize; 2]) -> FrameBufferRect<'b> {
+        let [dx, dy] = [0, 1].map(|i| (self.size[i].saturating_sub(size[i]) / 2) as isize);
+        FrameBufferRect {
+            frame_buffer: self.frame_buffer,
+            offset: [self.offset[0] + dx, self.offset[1] + dy],
+            size: [0, 1].map(|i| size[i].min(self.size[i])),
+        }
+    }
+
     pub fn width(&self) -> usize {
         self.size[0]
     }
@@ -66,13 +146,14 @@ impl<'a> FrameBufferRect<'a> {
         }
     }
     pub fn rows(&mut self) -> impl Iterator<Item = &mut [Rgb]> {
-        let max_w = self.frame_buffer[0].len() as isize;
-        let max_h = self.frame_buffer.len() as isize;
+        let max_w = self.frame_buffer.width() as isize;
+        let max_h = self.frame_buffer.height() as isize;
         let [bx, by] = self.offset;
         let [w, h] = self.size;
 
-        self.frame_buffer[by.clamp(0, max_h) as usize..][..h]
-            .iter_mut()
+        (self.frame_buffer.rows_mut())
+            .skip(by.clamp(0, max_h) as usize)
+            .take(h)
             .map(move |row| &mut row[bx.clamp(0, max_w) as usize..][..w])
     }
     pub fn pixels(&mut self) -> impl Iterator<Item = &mut Rgb> {
@@ -85,9 +166,9 @@ impl<'a> FrameBufferRect<'a> {
             return None;
         }
         let [bx, by] = self.offset;
-        self.frame_buffer
-            .get_mut(usize::try_from(by + y as isize).ok()?)?
-            .get_mut(usize::try_from(bx + x as isize).ok()?)
+        let y = usize::try_from(by + y as isize).ok()?;
+        let x = usize::try_from(bx + x as isize).ok()?;
+        self.frame_buffer.get_mut(y)?.get_mut(x)
     }
 
     pub fn set(&mut self, x: usize, y: usize, color: Rgb) {
@@ -116,3 +197,62 @@ impl IndexMut<[usize; 2]> for FrameBufferRect<'_> {
         &mut self.frame_buffer[(by + y as isize) as usize][(bx + x as isize) as usize]
     }
 }
+
+/// Clockwise rotation, in multiples of 90 degrees.
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "u16", into = "u16")]
+pub enum Rotation {
+    #[default]
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+impl Rotation {
+    /// Returns the size of a frame of `size` after the rotation.
+    pub fn rotate_size(self, [width, height]: [usize; 2]) -> [usize; 2] {
+        match self {
+            Self::R0 | Self::R180 => [width, height],
+            Self::R90 | Self::R270 => [height, width],
+        }
+    }
+
+    /// Returns where the pixel at `[x, y]` in a frame of `size` ends up after
+    /// the rotation.
+    pub fn rotate(self, [width, height]: [usize; 2], [x, y]: [usize; 2]) -> [usize; 2] {
+        match self {
+            Self::R0 => [x, y],
+            Self::R90 => [height - 1 - y, x],
+            Self::R180 => [width - 1 - x, height - 1 - y],
+            Self::R270 => [y, width - 1 - x],
+        }
+    }
+}
+
+impl TryFrom<u16> for Rotation {
+    type Error = String;
+
+    fn try_from(degrees: u16) -> Result<Self, Self::Error> {
+        match degrees {
+            0 => Ok(Self::R0),
+            90 => Ok(Self::R90),
+            180 => Ok(Self::R180),
+            270 => Ok(Self::R270),
+            _ => Err(format!(
+                "invalid rotation {degrees}; expected 0, 90, 180, or 270"
+            )),
+        }
+    }
+}
+
+impl From<Rotation> for u16 {
+    fn from(rotation: Rotation) -> Self {
+        match rotation {
+            Rotation::R0 => 0,
+            Rotation::R90 => 90,
+            Rotation::R180 => 180,
+            Rotation::R270 => 270,
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Frontend that runs the shell without a window or LED panel, using scripted
+//! input and writing frames to files.
+
+use std::path::{Path, PathBuf};
+
+use crate::input_script::InputScript;
+use crate::recording::Recording;
+use crate::{DEFAULT_SIZE, FrameBuffer, Shell, sound, stream};
+
+const USAGE: &str = "\
+usage: `ledwall [options]`
+
+options:
+    --frames <n>       number of frames to simulate (default: length of input script)
+    --input <file>     input script (see `input_script.rs` for the format)
+    --out <dir>        directory to write frames to (default: no output)
+    --format <fmt>     `png` or `rgb` (default: `png`)
+    --every <n>        only write every `n`th frame (default: 1)
+    --seed <n>         seed for all randomness (default: random)
+    --size <w>x<h>     size of the frame in pixels (default: 32x64, or the recording's size)
+    --dir <dir>        load and save settings and state in this directory (default: none)
+    --record <file>    record input to a file
+    --audio <dest>     write audio to a `.wav` file or pipe it to a command (default: none)
+    --replay <file>    replay input from a recording instead of an input script";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum OutputFormat {
+    Png,
+    Rgb,
+}
+
+struct Args {
+    frames: Option<usize>,
+    input: Option<PathBuf>,
+    out: Option<PathBuf>,
+    format: OutputFormat,
+    every: usize,
+    seed: Option<u64>,
+    size: Option<[usize; 2]>,
+    dir: Option<PathBuf>,
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    audio: Option<String>,
+}
+
+pub fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{e}\n\n{USAGE}");
+        std::process::exit(1);
+    });
+
+    let script = match &args.input {
+        Some(path) => {
+            let s = std::fs::read_to_string(path).expect("error reading input script");
+            InputScript::parse(&s).unwrap_or_else(|e| {
+                eprintln!("error parsing input script: {e}");
+                std::process::exit(1);
+            })
+        }
+        None => InputScript::default(),
+    };
+
+    let mut shell;
+    let frame_count;
+    if let Some(path) = &args.replay {
+        let recording = Recording::load(path).expect("error loading recording");
+        frame_count = args.frames.unwrap_or(recording.frames.len());
+        if let Some([width, height]) = args.size
+            && args.size != Some(recording.size)
+        {
+            let [recorded_width, recorded_height] = recording.size;
+            eprintln!(
+                "--size {width}x{height} doesn't match the recording, which is \
+                    {recorded_width}x{recorded_height}"
+            );
+            std::process::exit(1);
+        }
+        shell = Shell::from_recording(recording);
+    } else {
+        frame_count = args.frames.unwrap_or(script.len());
+        shell = match args.seed {
+            Some(seed) => Shell::with_seed(seed),
+            None => Shell::with_random_seed(),
+        }
+        .with_size(args.size.unwrap_or(DEFAULT_SIZE));
+        if let Some(dir) = &args.dir {
+            shell = shell.with_storage_dir(dir.clone());
+        }
+    }
+    if args.record.is_some() {
+        shell.start_recording().expect("error starting recording");
+    }
+
+    if let Some(out) = &args.out {
+        std::fs::create_dir_all(out).expect("error creating output directory");
+    }
+    let mut stream_server = stream::start_from_env();
+    let mut audio = match &args.audio {
+        Some(dest) => sound::output::open(dest).expect("error opening audio output"),
+        None => None,
+    };
+
+    for i in 0..frame_count {
+        // Update state
+        shell.update(script.get(i));
+        if let Some(stream_server) = &mut stream_server {
+            stream_server.publish(shell.frame_buffer());
+        }
+        if let Some(audio) = &mut audio {
+            audio
+                .write(shell.audio_samples())
+                .expect("error writing audio");
+        }
+
+        // Write frame
+        if let Some(out) = &args.out
+            && i % args.every == 0
+        {
+            write_frame(out, i, args.format, shell.frame_buffer()).expect("error writing frame");
+        }
+    }
+
+    shell.save_now();
+    drop(audio); // finishes writing
+
+    println!("Simulated {frame_count} frames with seed {}", shell.seed());
+
+    if let Some(path) = &args.record
+        && let Some(recording) = shell.stop_recording()
+    {
+        recording.save(path).expect("error saving recording");
+    }
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut ret = Args {
+        frames: None,
+        input: None,
+        out: None,
+        format: OutputFormat::Png,
+        every: 1,
+        seed: None,
+        size: None,
+        dir: None,
+        record: None,
+        replay: None,
+        audio: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--frames" => ret.frames = Some(parse_number(&value()?)?),
+            "--input" => ret.input = Some(value()?.into()),
+            "--out" => ret.out = Some(value()?.into()),
+            "--format" => {
+                ret.format = match value()?.as_str() {
+                    "png" => OutputFormat::Png,
+                    "rgb" => OutputFormat::Rgb,
+                    other => return Err(format!("unknown output format {other:?}")),
+                }
+            }
+            "--every" => ret.every = parse_number(&value()?)?.max(1),
+            "--seed" => ret.seed = Some(parse_number(&value()?)? as u64),
+            "--size" => ret.size = Some(parse_size(&value()?)?),
+            "--dir" => ret.dir = Some(value()?.into()),
+            "--record" => ret.record = Some(value()?.into()),
+            "--replay" => ret.replay = Some(value()?.into()),
+            "--audio" => ret.audio = Some(value()?),
+            "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            _ => return Err(format!("unknown argument {arg:?}")),
+        }
+    }
+
+    if ret.dir.is_some() && (ret.record.is_some() || ret.replay.is_some()) {
+        return Err(
+            "recordings start without storage, so --dir can't be combined with \
+                    --record or --replay"
+                .to_string(),
+        );
+    }
+
+    Ok(ret)
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("invalid number {s:?}"))
+}
+
+fn parse_size(s: &str) -> Result<[usize; 2], String> {
+    let (width, height) = (s.split_once('x')).ok_or_else(|| format!("invalid size {s:?}"))?;
+    let size = [parse_number(width)?, parse_number(height)?];
+    match size.contains(&0) {
+        true => Err(format!("invalid size {s:?}")),
+        false => Ok(size),
+    }
+}
+
+fn write_frame(
+    dir: &Path,
+    frame: usize,
+    format: OutputFormat,
+    frame_buffer: &FrameBuffer,
+) -> std::io::Result<()> {
+    let rgb_bytes: &[u8] = bytemuck::cast_slice(frame_buffer.as_flattened());
+    match format {
+        OutputFormat::Png => image::save_buffer(
+            dir.join(format!("frame_{frame:06}.png")),
+            rgb_bytes,
+            frame_buffer.width() as u32,
+            frame_buffer.height() as u32,
+            image::ExtendedColorType::Rgb8,
+        )
+        .map_err(std::io::Error::other),
+        OutputFormat::Rgb => std::fs::write(dir.join(format!("frame_{frame:06}.rgb")), rgb_bytes),
+    }
+}
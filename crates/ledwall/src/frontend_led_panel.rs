@@ -1,40 +1,97 @@
+use std::time::Instant;
+
 use rpi_led_panel::*;
 
-use crate::{FPS, HEIGHT, Rgb, Shell, WIDTH};
+use crate::panel::PanelConfig;
+use crate::panel::brightness::BrightnessFade;
+use crate::panel::color::ColorPipeline;
+use crate::panel::geometry::GeometryConfig;
+use crate::panel::power::PowerLimiter;
+use crate::timestep::Timestep;
+use crate::{FPS, FrameBuffer, Rgb, Shell, remote, sound, storage, stream};
 
 pub fn main() {
-    let (mut matrix, mut canvas) = init_matrix(crate::DEFAULT_BRIGHTNESS);
+    let panel_config = PanelConfig::load(&storage::dir()).unwrap_or_else(|e| {
+        eprintln!("Error loading panel configuration: {e}");
+        PanelConfig::default()
+    });
+    let pixel_map = panel_config.pixel_map(&storage::dir());
+    let size = pixel_map.size();
+
+    let mut shell = Shell::new(size);
+    if let Some(addr) = remote::addr_from_env()
+        && let Err(e) = shell.start_remote_server(&addr)
+    {
+        eprintln!("Error starting remote control server on {addr}: {e}");
+    }
+    let mut stream_server = stream::start_from_env();
+    let mut audio = sound::output::output_from_env();
 
-    let mut shell = Shell::default();
+    let mut color_pipeline = ColorPipeline::new(&panel_config.color, size);
+    let mut corrected = FrameBuffer::new(size);
+    let mut canvas_pixels = FrameBuffer::new(pixel_map.canvas_size());
+    let mut brightness = BrightnessFade::new(shell.brightness());
+    let mut power_limiter = PowerLimiter::new(&panel_config.power);
+
+    let (mut matrix, mut canvas) = init_matrix(&panel_config.geometry);
+    let mut timestep = Timestep::new(Instant::now());
 
     loop {
         // Take input
-        let (blue, green) = shell.read_gilrs_input();
+        let inputs = shell.read_gilrs_input();
 
-        // Update state
-        let output = shell.update(blue, green);
-        if let Some(new_brightness) = output.new_brightness {
-            (matrix, canvas) = init_matrix(new_brightness);
+        // Update state as many times as are due
+        let steps = timestep.steps_due(Instant::now());
+        for _ in 0..steps {
+            let output = shell.update(inputs);
+            if let Some(new_brightness) = output.new_brightness {
+                brightness.set(new_brightness);
+            }
+            brightness.step();
+            if let Some(output) = &mut audio
+                && let Err(e) = output.write(shell.audio_samples())
+            {
+                eprintln!("Error playing audio: {e}");
+                audio = None;
+            }
+        }
+
+        if steps > 0
+            && let Some(stream_server) = &mut stream_server
+        {
+            stream_server.publish(shell.frame_buffer());
         }
 
         // Update canvas
-        for (y, row) in shell.frame_buffer().iter().enumerate() {
+        let limited_brightness = power_limiter.limit(shell.frame_buffer(), brightness.scale());
+        if panel_config.power.overlay {
+            shell.set_power_draw(power_limiter.draw());
+        }
+        color_pipeline.process(shell.frame_buffer(), limited_brightness, &mut corrected);
+        pixel_map.map(&corrected, &mut canvas_pixels);
+        for (y, row) in canvas_pixels.rows().enumerate() {
             for (x, &Rgb([r, g, b])) in row.iter().enumerate() {
-                canvas.set_pixel(HEIGHT - 1 - y, x, r, g, b);
+                canvas.set_pixel(x, y, r, g, b);
             }
         }
 
-        // Update display and wait for next frame
+        // Update display and wait for the panel to refresh
         canvas = matrix.update_on_vsync(canvas);
     }
 }
 
-fn init_matrix(brightness: u8) -> (RGBMatrix, Box<Canvas>) {
+fn init_matrix(geometry: &GeometryConfig) -> (RGBMatrix, Box<Canvas>) {
+    let hardware_mapping = geometry.hardware_mapping.parse().unwrap_or_else(|e| {
+        eprintln!("Error in panel configuration: {e}");
+        HardwareMapping::adafruit_hat_pwm()
+    });
     let config = RGBMatrixConfig {
-        led_brightness: brightness * 5, // 0..=100
-        hardware_mapping: HardwareMapping::adafruit_hat_pwm(),
-        cols: HEIGHT,
-        rows: WIDTH,
+        led_brightness: 100, // scaled in software instead
+        hardware_mapping,
+        rows: geometry.rows,
+        cols: geometry.cols,
+        chain_length: geometry.chain_length,
+        parallel: geometry.parallel,
         refresh_rate: FPS,
         ..Default::default()
     };
@@ -1,8 +1,14 @@
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
 use macroquad::prelude::*;
 
-use crate::{Buttons, FPS, HEIGHT, Rgb, Shell, WIDTH};
+use crate::panel::PanelConfig;
+use crate::panel::brightness::BrightnessFade;
+use crate::panel::color::{ColorConfig, ColorPipeline};
+use crate::panel::power::PowerLimiter;
+use crate::recording::Recording;
+use crate::timestep::Timestep;
+use crate::{Buttons, FrameBuffer, Player, Shell, remote, sound, storage, stream};
 
 const SCALE_FACTOR: f32 = 10.0;
 const PADDING: f32 = 25.0;
@@ -13,42 +19,107 @@ const BOTTOM_PADDING: f32 = 30.0 + PADDING;
 
 #[macroquad::main("ledwall")]
 pub async fn main() {
+    // Match the size and mapping of the wall, and scale the brightness and
+    // limit the power the same way as the panel, without its color correction
+    let panel_config = PanelConfig::load(&storage::dir()).unwrap_or_else(|e| {
+        eprintln!("Error loading panel configuration: {e}");
+        PanelConfig::default()
+    });
+    let pixel_map = panel_config.pixel_map(&storage::dir());
+    let size = pixel_map.size();
+    let [width, height] = size.map(|n| n as f32 * SCALE_FACTOR);
     request_new_screen_size(
-        32.0 * SCALE_FACTOR + X_PADDING * 2.0,
-        64.0 * SCALE_FACTOR + TOP_PADDING + BOTTOM_PADDING,
+        width + X_PADDING * 2.0,
+        height + TOP_PADDING + BOTTOM_PADDING,
     );
 
-    let mut next_frame_time = Instant::now();
-    let mut shell = Shell::default();
+    // Record from a fresh shell with `--record`, or replay a recording if one
+    // is given on the command line
+    let mut shell = match std::env::args().nth(1).as_deref() {
+        Some("--record") => {
+            let mut shell = Shell::with_random_seed().with_size(size);
+            shell.start_recording().expect("error starting recording");
+            println!("Started recording");
+            shell
+        }
+        Some(path) => {
+            let recording = Recording::load(path.as_ref()).expect("error loading recording");
+            if recording.size != size {
+                let [width, height] = recording.size;
+                eprintln!(
+                    "The recording is {width}x{height}, which doesn't match the panel \
+                        configuration"
+                );
+                std::process::exit(1);
+            }
+            Shell::from_recording(recording)
+        }
+        None => Shell::new(size),
+    };
+    if let Some(addr) = remote::addr_from_env()
+        && let Err(e) = shell.start_remote_server(&addr)
+    {
+        eprintln!("Error starting remote control server on {addr}: {e}");
+    }
+    let mut stream_server = stream::start_from_env();
+    let mut audio = sound::output::output_from_env();
+
+    let mut color_pipeline = ColorPipeline::new(&ColorConfig::default(), size);
+    let mut corrected = FrameBuffer::new(size);
+    let mut canvas_pixels = FrameBuffer::new(pixel_map.canvas_size());
+    let mut brightness = BrightnessFade::new(shell.brightness());
+    let mut power_limiter = PowerLimiter::new(&panel_config.power);
 
     let mut rgba_buffer = vec![];
 
     rgb_to_rgba(&mut rgba_buffer, shell.frame_buffer());
-    let texture = Texture2D::from_rgba8(WIDTH as u16, HEIGHT as u16, &rgba_buffer);
+    let texture = Texture2D::from_rgba8(size[0] as u16, size[1] as u16, &rgba_buffer);
     texture.set_filter(FilterMode::Nearest);
 
     let mut show_fps = false;
-    let frame_duration: Duration = Duration::from_secs_f64(1.0 / FPS as f64);
+    let mut timestep = Timestep::new(Instant::now());
 
     loop {
-        next_frame_time += frame_duration;
-        if next_frame_time + frame_duration < Instant::now() {
-            next_frame_time = Instant::now();
-        }
-
         // Toggle FPS counter
         if is_key_pressed(KeyCode::F) {
             show_fps ^= true;
         }
 
+        // Save recording
+        if is_key_pressed(KeyCode::R) {
+            match shell.stop_recording() {
+                Some(recording) => {
+                    let path = recording_path();
+                    match recording.save(&path) {
+                        Ok(()) => {
+                            println!("Saved recording to {}", path.display());
+                            shell.notify("recording saved");
+                        }
+                        Err(e) => eprintln!("Error saving recording: {e}"),
+                    }
+                }
+                None => {
+                    eprintln!("Not recording; pass --record to record from the start");
+                    shell.notify("not recording");
+                }
+            }
+        }
+
+        // Annotate recording
+        if is_key_pressed(KeyCode::M) {
+            shell.add_recording_marker("marker");
+        }
+
         // Take gamepad input
         #[cfg(feature = "gilrs")]
-        let (mut blue, green) = shell.read_gilrs_input();
+        let mut inputs = shell.read_gilrs_input();
         #[cfg(not(feature = "gilrs"))]
-        let (mut blue, green) = (None, None);
+        let mut inputs: crate::FrameInputs = [None; crate::MAX_PLAYERS];
 
-        // Take keyboard input
-        let pressed: &mut Buttons = blue.get_or_insert_default();
+        // Take keyboard input as the blue controller
+        let pressed: &mut Buttons = &mut inputs[Player::Blue as usize]
+            .get_or_insert_default()
+            .buttons;
         {
             // D pad
             pressed.up |= is_key_down(KeyCode::Up) || is_key_down(KeyCode::W);
@@ -76,40 +147,70 @@ pub async fn main() {
             pressed.heart |= is_key_down(KeyCode::Escape);
         }
 
-        // Update state
-        shell.update(blue, green);
-
-        // Wait for next frame
-        while Instant::now() < next_frame_time {
-            // Update display
-            rgb_to_rgba(&mut rgba_buffer, shell.frame_buffer());
-            texture.update_from_bytes(WIDTH as u32, HEIGHT as u32, &rgba_buffer);
-            draw_texture_ex(
-                &texture,
-                X_PADDING,
-                TOP_PADDING,
-                WHITE,
-                DrawTextureParams {
-                    dest_size: Some(vec2(
-                        WIDTH as f32 * SCALE_FACTOR,
-                        HEIGHT as f32 * SCALE_FACTOR,
-                    )),
-                    ..Default::default()
-                },
-            );
-
-            if show_fps {
-                draw_fps();
+        // Update state as many times as are due
+        let steps = timestep.steps_due(Instant::now());
+        for _ in 0..steps {
+            let output = shell.update(inputs);
+            if let Some(new_brightness) = output.new_brightness {
+                brightness.set(new_brightness);
+            }
+            brightness.step();
+            if let Some(output) = &mut audio
+                && let Err(e) = output.write(shell.audio_samples())
+            {
+                eprintln!("Error playing audio: {e}");
+                audio = None;
             }
+        }
+        if steps > 0
+            && let Some(stream_server) = &mut stream_server
+        {
+            stream_server.publish(shell.frame_buffer());
+        }
 
-            next_frame().await;
+        // Update display
+        let limited_brightness = power_limiter.limit(shell.frame_buffer(), brightness.scale());
+        if panel_config.power.overlay {
+            shell.set_power_draw(power_limiter.draw());
+        }
+        color_pipeline.process(shell.frame_buffer(), limited_brightness, &mut corrected);
+        // Show what the panel would, so that mistakes in a remap table are
+        // visible in the simulator
+        pixel_map.map(&corrected, &mut canvas_pixels);
+        pixel_map.show_mounted(&canvas_pixels, &mut corrected);
+        rgb_to_rgba(&mut rgba_buffer, &corrected);
+        texture.update_from_bytes(size[0] as u32, size[1] as u32, &rgba_buffer);
+        draw_texture_ex(
+            &texture,
+            X_PADDING,
+            TOP_PADDING,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(width, height)),
+                ..Default::default()
+            },
+        );
+
+        if show_fps {
+            draw_fps();
         }
+
+        // Wait for the display to refresh
+        next_frame().await;
     }
 }
 
-fn rgb_to_rgba(rgba_buffer: &mut Vec<u8>, rgb: &[[Rgb; WIDTH]; HEIGHT]) {
-    rgba_buffer.resize(WIDTH * HEIGHT * 4, 255);
+fn rgb_to_rgba(rgba_buffer: &mut Vec<u8>, rgb: &FrameBuffer) {
+    rgba_buffer.resize(rgb.width() * rgb.height() * 4, 255);
     for (i, rgb) in rgb.as_flattened().iter().enumerate() {
         rgba_buffer[i * 4..][..3].copy_from_slice(bytemuck::bytes_of(rgb));
     }
 }
+
+fn recording_path() -> std::path::PathBuf {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    format!("recordings/{timestamp}.lwrec").into()
+}
@@ -1,37 +1,104 @@
 use std::{
+    fmt,
     ops::{BitAnd, BitOr, BitXor, Not},
-    time::{Duration, Instant},
+    str::FromStr,
 };
 
+use serde::{Deserialize, Serialize};
+
+use crate::FPS;
+
 const DELAY: f32 = 1.0 / 3.0; // number of seconds
 const RATE: f32 = 1.0 / 16.0; // number of seconds
+// Key repeat counts frames rather than wall time so that replays are exact.
+const DELAY_FRAMES: u32 = (DELAY * FPS as f32) as u32;
+const RATE_FRAMES: u32 = (RATE * FPS as f32) as u32;
 
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+/// Maximum number of players, each with their own controller.
+pub const MAX_PLAYERS: usize = 4;
+
+/// Minimum deflection of an analog stick or trigger, before applying the
+/// deadzone, for the corresponding button to count as held.
+pub const AXIS_BUTTON_THRESHOLD: f32 = 0.5;
+
+/// State of each player's controller for a single frame, or `None` for a
+/// disconnected controller.
+pub type FrameInputs = [Option<ControllerState>; MAX_PLAYERS];
+
+/// Player slot, which determines the player's color.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Player {
+    Blue = 0,
+    Green = 1,
+    Red = 2,
+    Yellow = 3,
+}
+
+impl Player {
+    /// Every player, in order of their index.
+    pub const ALL: [Player; MAX_PLAYERS] =
+        [Player::Blue, Player::Green, Player::Red, Player::Yellow];
+}
+
+impl fmt::Display for Player {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Player::Blue => write!(f, "blue"),
+            Player::Green => write!(f, "green"),
+            Player::Red => write!(f, "red"),
+            Player::Yellow => write!(f, "yellow"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct FullInput {
-    /// Blue controller input state.
-    pub blue: Option<ControllerInput>,
-    /// Green controller input state.
-    pub green: Option<ControllerInput>,
+    /// Input state of each player's controller, indexed by [`Player`].
+    pub players: [Option<ControllerInput>; MAX_PLAYERS],
 }
 
 impl FullInput {
-    /// Returns inputs on either controller.
+    /// Constructs the input for a frame from the controller state on the
+    /// current frame and on the previous frame.
+    pub fn new(current: FrameInputs, previous: FrameInputs) -> Self {
+        Self {
+            players: std::array::from_fn(|i| {
+                current[i].map(|current| ControllerInput {
+                    current: current.buttons,
+                    previous: previous[i].unwrap_or_default().buttons,
+                    axes: current.axes,
+                })
+            }),
+        }
+    }
+
+    /// Returns the input state of a player's controller, or `None` if it is
+    /// disconnected.
+    pub fn player(self, player: Player) -> Option<ControllerInput> {
+        self.players[player as usize]
+    }
+
+    /// Returns inputs on any controller.
     pub fn any(self) -> ControllerInput {
-        let blue = self.blue.unwrap_or_default();
-        let green = self.green.unwrap_or_default();
-        ControllerInput {
-            current: blue.current | green.current,
-            previous: blue.previous | green.previous,
+        let mut ret = ControllerInput::default();
+        for input in self.players.into_iter().flatten() {
+            ret.current = ret.current | input.current;
+            ret.previous = ret.previous | input.previous;
+            ret.axes = ret.axes.merge(input.axes);
         }
+        ret
     }
 }
 
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct ControllerInput {
     /// Buttons currently held down.
     pub current: Buttons,
     /// Buttons held down last frame.
     pub previous: Buttons,
+    /// Current position of the analog sticks and triggers.
+    pub axes: Axes,
 }
 
 impl ControllerInput {
@@ -45,6 +112,140 @@ impl ControllerInput {
     }
 }
 
+/// State of a controller for a single frame.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct ControllerState {
+    /// Buttons held, including buttons derived from the analog sticks and
+    /// triggers (see [`Axes::to_buttons()`]).
+    pub buttons: Buttons,
+    /// Position of the analog sticks and triggers.
+    pub axes: Axes,
+}
+
+impl From<Buttons> for ControllerState {
+    fn from(buttons: Buttons) -> Self {
+        Self {
+            buttons,
+            axes: Axes::default(),
+        }
+    }
+}
+
+impl ControllerState {
+    /// Combines the state of two controllers acting as the same player.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            buttons: self.buttons | other.buttons,
+            axes: self.axes.merge(other.axes),
+        }
+    }
+}
+
+/// Position of the analog sticks and triggers on a controller.
+///
+/// Sticks are `[x, y]` from `-1.0` to `1.0`, with positive `y` pointing up.
+/// Triggers are from `0.0` (released) to `1.0` (fully pressed). Controllers
+/// without analog input leave everything at zero.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Axes {
+    pub left_stick: [f32; 2],
+    pub right_stick: [f32; 2],
+    pub lt: f32,
+    pub rt: f32,
+}
+
+impl Axes {
+    /// Returns the axes in the order `[left_stick, right_stick, lt, rt]`.
+    pub fn to_array(self) -> [f32; 6] {
+        let [lx, ly] = self.left_stick;
+        let [rx, ry] = self.right_stick;
+        [lx, ly, rx, ry, self.lt, self.rt]
+    }
+
+    /// Constructs axes from an array in the same order as
+    /// [`Axes::to_array()`].
+    pub fn from_array([lx, ly, rx, ry, lt, rt]: [f32; 6]) -> Self {
+        Self {
+            left_stick: [lx, ly],
+            right_stick: [rx, ry],
+            lt,
+            rt,
+        }
+    }
+
+    /// Returns the axes with a deadzone applied to each stick and trigger.
+    ///
+    /// Positions within the deadzone become zero, and positions outside it
+    /// are rescaled so that the edge of the deadzone is zero and the maximum
+    /// is still `1.0`. Sticks use a circular deadzone, so that diagonals
+    /// aren't snapped to the nearest axis.
+    pub fn with_deadzones(self, deadzones: Deadzones) -> Self {
+        let rescale = |magnitude: f32, deadzone: f32| {
+            let deadzone = deadzone.clamp(0.0, 0.99);
+            ((magnitude - deadzone) / (1.0 - deadzone)).clamp(0.0, 1.0)
+        };
+        let stick = |[x, y]: [f32; 2]| {
+            let magnitude = x.hypot(y);
+            if magnitude <= deadzones.stick {
+                return [0.0, 0.0];
+            }
+            let scale = rescale(magnitude, deadzones.stick) / magnitude;
+            [x * scale, y * scale]
+        };
+        Self {
+            left_stick: stick(self.left_stick),
+            right_stick: stick(self.right_stick),
+            lt: rescale(self.lt, deadzones.trigger),
+            rt: rescale(self.rt, deadzones.trigger),
+        }
+    }
+
+    /// Returns the buttons that the axes count as: the D pad for the left
+    /// stick and `lt`/`rt` for the triggers, each held once the axis passes
+    /// [`AXIS_BUTTON_THRESHOLD`]. This is meant for raw axes, so that the
+    /// point where the buttons trigger doesn't move with the deadzone.
+    pub fn to_buttons(self) -> Buttons {
+        let [x, y] = self.left_stick;
+        Buttons {
+            up: y > AXIS_BUTTON_THRESHOLD,
+            down: y < -AXIS_BUTTON_THRESHOLD,
+            left: x < -AXIS_BUTTON_THRESHOLD,
+            right: x > AXIS_BUTTON_THRESHOLD,
+            lt: self.lt > AXIS_BUTTON_THRESHOLD,
+            rt: self.rt > AXIS_BUTTON_THRESHOLD,
+            ..Buttons::default()
+        }
+    }
+
+    /// Combines two sets of axes by taking whichever value is farthest from
+    /// zero for each axis.
+    pub fn merge(self, other: Self) -> Self {
+        let a = self.to_array();
+        let b = other.to_array();
+        Self::from_array(std::array::from_fn(|i| {
+            if b[i].abs() > a[i].abs() { b[i] } else { a[i] }
+        }))
+    }
+}
+
+/// Size of the deadzone around the resting position of analog sticks and
+/// triggers, as a fraction of their full range.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Deadzones {
+    pub stick: f32,
+    pub trigger: f32,
+}
+
+impl Default for Deadzones {
+    fn default() -> Self {
+        Self {
+            stick: 0.1,
+            trigger: 0.05,
+        }
+    }
+}
+
 /// Boolean for each controller button.
 ///
 /// Depending on context, this may represent buttons currently held, buttons
@@ -76,6 +277,113 @@ pub struct Buttons {
     pub heart: bool,
 }
 
+impl Buttons {
+    /// Name of each button, in the same order as [`Buttons::to_array()`].
+    pub const NAMES: [&str; 16] = [
+        "up", "down", "left", "right", "a", "b", "x", "y", "l", "r", "lt", "rt", "plus", "minus",
+        "star", "heart",
+    ];
+
+    /// Returns the state of each button, in the same order as
+    /// [`Buttons::NAMES`].
+    pub fn to_array(self) -> [bool; 16] {
+        [
+            self.up, self.down, self.left, self.right, self.a, self.b, self.x, self.y, self.l,
+            self.r, self.lt, self.rt, self.plus, self.minus, self.star, self.heart,
+        ]
+    }
+
+    /// Constructs a button state from an array in the same order as
+    /// [`Buttons::NAMES`].
+    pub fn from_array(array: [bool; 16]) -> Self {
+        let [
+            up,
+            down,
+            left,
+            right,
+            a,
+            b,
+            x,
+            y,
+            l,
+            r,
+            lt,
+            rt,
+            plus,
+            minus,
+            star,
+            heart,
+        ] = array;
+        Self {
+            up,
+            down,
+            left,
+            right,
+            a,
+            b,
+            x,
+            y,
+            l,
+            r,
+            lt,
+            rt,
+            plus,
+            minus,
+            star,
+            heart,
+        }
+    }
+
+    /// Returns the buttons as a bitmask, with bits in the same order as
+    /// [`Buttons::NAMES`] starting from the least significant bit.
+    pub fn to_bits(self) -> u16 {
+        (self.to_array().iter().rev()).fold(0, |bits, &is_set| (bits << 1) | is_set as u16)
+    }
+
+    /// Constructs a button state from a bitmask in the same format as
+    /// [`Buttons::to_bits()`].
+    pub fn from_bits(bits: u16) -> Self {
+        Self::from_array(std::array::from_fn(|i| bits & (1 << i) != 0))
+    }
+}
+
+/// Formats buttons as a list of names separated by `+`, or `.` if no buttons
+/// are set.
+impl fmt::Display for Buttons {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = std::iter::zip(Self::NAMES, self.to_array())
+            .filter(|&(_name, is_set)| is_set)
+            .map(|(name, _is_set)| name);
+        match names.next() {
+            Some(first) => write!(f, "{first}")?,
+            None => return write!(f, "."),
+        }
+        for name in names {
+            write!(f, "+{name}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses buttons in the same format as [`Buttons`]'s `Display` impl.
+impl FromStr for Buttons {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut array = [false; 16];
+        if s != "." {
+            for name in s.split('+') {
+                let i = Self::NAMES
+                    .iter()
+                    .position(|&n| n.eq_ignore_ascii_case(name.trim()))
+                    .ok_or_else(|| format!("unknown button {name:?}"))?;
+                array[i] = true;
+            }
+        }
+        Ok(Self::from_array(array))
+    }
+}
+
 // tt muncher! because I'm feeling quirky
 macro_rules! construct_fieldwise {
     (@[$field:ident] [$($done:tt)*] []) => {
@@ -133,12 +441,13 @@ impl Not for Buttons {
     }
 }
 
+/// Repeats a held key, for a call to [`KeyRepeat::update()`] every frame.
 #[derive(Debug, Default, Clone)]
 pub enum KeyRepeat {
     #[default]
     Released,
-    Pressed(Instant),
-    Repeating(Instant),
+    /// Number of frames until the key repeats.
+    Held(u32),
 }
 
 impl KeyRepeat {
@@ -147,16 +456,16 @@ impl KeyRepeat {
     }
 
     pub fn update(&mut self, is_down: bool) -> bool {
-        let now = Instant::now();
         match is_down {
             true => match self {
                 Self::Released => {
-                    *self = Self::Pressed(now + Duration::from_secs_f32(DELAY));
+                    *self = Self::Held(DELAY_FRAMES);
                     true
                 }
-                Self::Pressed(time_to_repeat) | Self::Repeating(time_to_repeat) => {
-                    if now >= *time_to_repeat {
-                        *self = Self::Repeating(*time_to_repeat + Duration::from_secs_f32(RATE));
+                Self::Held(frames_left) => {
+                    *frames_left = frames_left.saturating_sub(1);
+                    if *frames_left == 0 {
+                        *frames_left = RATE_FRAMES;
                         true
                     } else {
                         false
@@ -170,3 +479,21 @@ impl KeyRepeat {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_repeat() {
+        let mut key = KeyRepeat::new();
+        let presses: Vec<usize> = (0..DELAY_FRAMES as usize + RATE_FRAMES as usize * 2 + 1)
+            .filter(|_| key.update(true))
+            .collect();
+        let delay = DELAY_FRAMES as usize;
+        let rate = RATE_FRAMES as usize;
+        assert_eq!(presses, [0, delay, delay + rate, delay + rate * 2]);
+        assert!(!key.update(false));
+        assert!(key.update(true));
+    }
+}
@@ -0,0 +1,81 @@
+//! Plain-text format for scripted controller input.
+//!
+//! Each line has the form `<frames> <blue> [<green> [<red> [<yellow>]]]`,
+//! which holds the given controller state for `<frames>` frames. A controller
+//! state is either `-` (disconnected), `.` (connected with no buttons held),
+//! or a list of button names separated by `+`, such as `a+up`. Omitted
+//! controllers are disconnected. Analog sticks and triggers are always
+//! centered. Text after `#` is ignored.
+//!
+//! ```text
+//! # open the menu and move to the next activity
+//! 10 .
+//! 1  heart
+//! 10 .
+//! 1  right
+//! 1  a
+//! ```
+
+use crate::{Buttons, ControllerState, FrameInputs, MAX_PLAYERS};
+
+/// Sequence of per-frame controller input.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InputScript {
+    frames: Vec<FrameInputs>,
+}
+
+impl InputScript {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut frames = vec![];
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parse_line = || -> Result<(usize, FrameInputs), String> {
+                let mut words = line.split_whitespace();
+                let count = words.next().unwrap_or_default();
+                let count = count
+                    .parse()
+                    .map_err(|_| format!("invalid frame count {count:?}"))?;
+                let mut inputs = [None; MAX_PLAYERS];
+                for input in &mut inputs {
+                    *input = parse_controller(words.next().unwrap_or("-"))?;
+                }
+                if let Some(extra) = words.next() {
+                    return Err(format!("unexpected {extra:?}"));
+                }
+                Ok((count, inputs))
+            };
+            let (count, inputs) = parse_line().map_err(|e| format!("line {}: {e}", i + 1))?;
+            frames.extend(std::iter::repeat_n(inputs, count));
+        }
+        Ok(Self { frames })
+    }
+
+    /// Returns the number of frames in the script.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns the input for frame `i`, holding the last frame of the script
+    /// forever. If the script is empty, the blue controller is connected with
+    /// no buttons held.
+    pub fn get(&self, i: usize) -> FrameInputs {
+        match self.frames.get(i).or(self.frames.last()) {
+            Some(&inputs) => inputs,
+            None => {
+                let mut inputs = [None; MAX_PLAYERS];
+                inputs[0] = Some(ControllerState::default());
+                inputs
+            }
+        }
+    }
+}
+
+fn parse_controller(s: &str) -> Result<Option<ControllerState>, String> {
+    match s {
+        "-" => Ok(None),
+        _ => s.parse::<Buttons>().map(|buttons| Some(buttons.into())),
+    }
+}
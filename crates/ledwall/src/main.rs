@@ -5,11 +5,31 @@ mod animation;
 
 mod activities;
 mod array_vec;
+mod attract;
 mod color;
+mod controllers;
 mod frame_buffer;
 mod input;
+#[cfg(any(feature = "headless", test))]
+mod input_script;
+#[cfg(any(not(feature = "headless"), test))]
+mod panel;
+mod recording;
+mod remote;
+mod rng;
+mod schedule;
+mod settings;
 mod shell;
+#[cfg(test)]
+mod snapshot_tests;
+mod sound;
+mod state;
+mod storage;
+mod stream;
 mod text;
+#[cfg(any(not(feature = "headless"), test))]
+mod timestep;
+mod toast;
 mod traits;
 mod widgets;
 
@@ -18,19 +38,25 @@ use std::ops::Range;
 pub use animation::{Animation, AnimationFrame, draw_opt_animation, step_opt_animation};
 pub use array_vec::ArrayVec;
 pub use color::*;
-pub use frame_buffer::{FrameBuffer, FrameBufferRect};
+pub use frame_buffer::{FrameBuffer, FrameBufferRect, Rotation};
 pub use image::StaticImage;
-pub use input::{Buttons, ControllerInput, FullInput, KeyRepeat};
+pub use input::{
+    Axes, Buttons, ControllerInput, ControllerState, Deadzones, FrameInputs, FullInput, KeyRepeat,
+    MAX_PLAYERS, Player,
+};
 pub use shell::Shell;
 pub use traits::{Activity, Widget};
 
 #[cfg_attr(feature = "rpi-led-panel", path = "frontend_led_panel.rs")]
 #[cfg_attr(feature = "macroquad", path = "frontend_macroquad.rs")]
+#[cfg_attr(feature = "headless", path = "frontend_headless.rs")]
 mod frontend;
 
 pub const FPS: usize = 120;
-pub const WIDTH: usize = 32;
-pub const HEIGHT: usize = 64;
+/// Size of the frame buffer on a single panel, which is also the size that
+/// fixed layouts are designed for. Walls of several panels are larger (see
+/// [`FrameBuffer::size()`]).
+pub const DEFAULT_SIZE: [usize; 2] = [32, 64];
 
 pub const DEFAULT_VOLUME: u8 = 10; // 0..=20
 pub const DEFAULT_BRIGHTNESS: u8 = 12; // 0..=20
@@ -56,10 +82,6 @@ pub fn map_range(parameter: f32, in_range: Range<f32>, out_range: Range<f32>) ->
     }
 }
 
-pub fn xy_is_in_frame([x, y]: [usize; 2]) -> bool {
-    (0..crate::WIDTH).contains(&x) && (0..crate::HEIGHT).contains(&y)
-}
-
 /// Returns (sin(pi * t))^2, which varies smoothly from 0 to 1 and back as
 /// `t` varies from 0 to 1.
 pub fn smooth_sine(t: f32) -> f32 {
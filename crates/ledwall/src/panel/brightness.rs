@@ -0,0 +1,89 @@
+//! Brightness applied to frames in software, so that changing it fades
+//! smoothly instead of reinitializing the panel.
+//!
+//! The panel's hardware brightness scales the lightness of each channel
+//! before its luminance correction, so scaling each channel's level has the
+//! same perceptual effect. Dithering (see [`super::color`]) keeps the
+//! fractional levels that this produces at low brightness.
+
+use crate::FPS;
+
+/// Brightness at which frames are shown unscaled.
+const MAX_BRIGHTNESS: u8 = 20;
+/// Number of seconds to fade to a new brightness.
+const FADE_DURATION: f32 = 0.25;
+
+/// Scale of each channel's level, which fades toward the brightness chosen in
+/// the shell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrightnessFade {
+    scale: f32,
+    target: f32,
+    /// Amount that the scale changes per step until it reaches the target.
+    rate: f32,
+}
+
+impl BrightnessFade {
+    /// Constructs a fade that starts at `brightness`, on a scale from 0 to 20.
+    pub fn new(brightness: u8) -> Self {
+        let scale = brightness_scale(brightness);
+        Self {
+            scale,
+            target: scale,
+            rate: 0.0,
+        }
+    }
+
+    /// Starts fading to `brightness`, on a scale from 0 to 20.
+    pub fn set(&mut self, brightness: u8) {
+        self.target = brightness_scale(brightness);
+        self.rate = (self.target - self.scale).abs() / (FADE_DURATION * FPS as f32);
+    }
+
+    /// Advances the fade by one step.
+    pub fn step(&mut self) {
+        let difference = self.target - self.scale;
+        self.scale = match difference.abs() <= self.rate {
+            true => self.target,
+            false => self.scale + self.rate.copysign(difference),
+        };
+    }
+
+    /// Returns the current scale, from 0 to 1.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+}
+
+fn brightness_scale(brightness: u8) -> f32 {
+    brightness.min(MAX_BRIGHTNESS) as f32 / MAX_BRIGHTNESS as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fade() {
+        let mut fade = BrightnessFade::new(20);
+        assert_eq!(fade.scale(), 1.0);
+
+        fade.set(10);
+        let frames = (FADE_DURATION * FPS as f32) as usize;
+        for _ in 0..frames / 2 {
+            fade.step();
+        }
+        assert!((fade.scale() - 0.75).abs() < 0.01, "{}", fade.scale());
+        for _ in 0..frames {
+            fade.step();
+        }
+        assert_eq!(fade.scale(), 0.5);
+
+        // Changing course partway fades from where it was.
+        fade.set(0);
+        fade.step();
+        fade.set(20);
+        fade.step();
+        assert!((fade.scale() - 0.5).abs() < 0.02, "{}", fade.scale());
+    }
+}
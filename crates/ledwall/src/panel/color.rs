@@ -0,0 +1,196 @@
+//! Color correction for the LED panel.
+//!
+//! The panel applies its own luminance correction to each 8-bit channel, so
+//! the shell's sRGB colors are passed through unchanged by default. Gamma and
+//! white balance adjust each channel to make the panel match the simulator.
+//! Brightness is applied here too, in software, so that it fades smoothly in
+//! both the simulator and on the panel. Temporal dithering, which is on by
+//! default, keeps the fractional levels that all of these produce by
+//! alternating between neighboring levels from frame to frame, which matters
+//! most at low brightness.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{FrameBuffer, Rgb};
+
+/// Ordered dithering matrix, which offsets the dithering of neighboring
+/// pixels so that they don't flicker in unison.
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Color correction settings, in the `[color]` table of the panel
+/// configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorConfig {
+    /// Exponent applied to the red, green, and blue channels. Values above 1
+    /// darken midtones.
+    pub gamma: [f32; 3],
+    /// Scale of the red, green, and blue channels, from 0 to 1.
+    pub white_balance: [f32; 3],
+    /// Whether to dither fractional levels over time. Defaults to `true`.
+    pub dither: bool,
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        Self {
+            gamma: [1.0; 3],
+            white_balance: [1.0; 3],
+            dither: true,
+        }
+    }
+}
+
+/// Converts frames from the shell into the levels sent to the panel.
+pub struct ColorPipeline {
+    /// Output level of each input level for each channel, in 256ths of a
+    /// level.
+    lut: [[u16; 256]; 3],
+    /// Fraction of a level accumulated by each channel of each pixel, in
+    /// 256ths of a level and in the same order as
+    /// [`FrameBuffer::as_flattened()`], or `None` if dithering is disabled.
+    dither: Option<Vec<[u8; 3]>>,
+}
+
+impl ColorPipeline {
+    /// Constructs a pipeline for frames of `size`.
+    pub fn new(config: &ColorConfig, [width, height]: [usize; 2]) -> Self {
+        let lut = std::array::from_fn(|channel| {
+            let gamma = config.gamma[channel].max(0.0);
+            let gain = config.white_balance[channel].clamp(0.0, 1.0);
+            std::array::from_fn(|level| {
+                let out = (level as f32 / 255.0).powf(gamma) * gain * 255.0;
+                (out * 256.0).round() as u16
+            })
+        });
+        let dither = config.dither.then(|| {
+            (0..width * height)
+                .map(|i| {
+                    let offset = BAYER_4X4[i / width % 4][i % width % 4] * 16;
+                    // Offset each channel too, so that grays stay gray.
+                    [offset, offset.wrapping_add(85), offset.wrapping_add(170)]
+                })
+                .collect()
+        });
+        Self { lut, dither }
+    }
+
+    /// Writes the corrected levels of `fb`, scaled by `brightness` from 0 to 1
+    /// (see [`super::brightness`]), to `out`. With dithering, levels below 1
+    /// at low brightness still show, and this should be called exactly once
+    /// per frame shown. `fb` and `out` should have the size that the pipeline
+    /// was constructed with.
+    pub fn process(&mut self, fb: &FrameBuffer, brightness: f32, out: &mut FrameBuffer) {
+        let brightness = (brightness.clamp(0.0, 1.0) * 256.0) as u32;
+        let pixels = std::iter::zip(fb.as_flattened(), out.as_flattened_mut());
+        for (i, (&Rgb(color), out_pixel)) in pixels.enumerate() {
+            out_pixel.0 = std::array::from_fn(|c| {
+                let level = (self.lut[c][color[c] as usize] as u32 * brightness) >> 8;
+                let [whole, fraction] = [(level >> 8) as u8, level as u8];
+                match &mut self.dither {
+                    Some(dither) => {
+                        let (sum, carry) = dither[i][c].overflowing_add(fraction);
+                        dither[i][c] = sum;
+                        whole + carry as u8
+                    }
+                    None => whole + (fraction >= 128) as u8,
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BLACK, DEFAULT_SIZE};
+
+    fn process_frames(
+        pipeline: &mut ColorPipeline,
+        fb: &FrameBuffer,
+        brightness: f32,
+        n: usize,
+    ) -> Vec<FrameBuffer> {
+        (0..n)
+            .map(|_| {
+                let mut out = FrameBuffer::new(fb.size());
+                pipeline.process(fb, brightness, &mut out);
+                out
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identity() {
+        let mut pipeline = ColorPipeline::new(&ColorConfig::default(), DEFAULT_SIZE);
+        let mut fb = FrameBuffer::new(DEFAULT_SIZE);
+        for (y, row) in fb.rows_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = Rgb([x as u8 * 8, y as u8 * 4, 77]);
+            }
+        }
+        assert_eq!(process_frames(&mut pipeline, &fb, 1.0, 1), [fb]);
+    }
+
+    #[test]
+    fn gamma_and_white_balance() {
+        let config = ColorConfig {
+            gamma: [2.0, 1.0, 1.0],
+            white_balance: [1.0, 0.5, 2.0],
+            dither: false,
+        };
+        let mut pipeline = ColorPipeline::new(&config, DEFAULT_SIZE);
+        let fb = FrameBuffer::filled(DEFAULT_SIZE, Rgb([128, 100, 200]));
+        let out = process_frames(&mut pipeline, &fb, 1.0, 1).remove(0);
+        assert_eq!(out[0][0], Rgb([64, 50, 200]));
+        assert!(out.as_flattened().iter().all(|&c| c == out[0][0]));
+    }
+
+    #[test]
+    fn dither() {
+        let config = ColorConfig {
+            white_balance: [0.5; 3],
+            dither: true,
+            ..ColorConfig::default()
+        };
+        let mut pipeline = ColorPipeline::new(&config, DEFAULT_SIZE);
+        // Level 3 becomes 1.5, which alternates between 1 and 2.
+        let fb = FrameBuffer::filled(DEFAULT_SIZE, Rgb([3, 3, 3]));
+        let frames = process_frames(&mut pipeline, &fb, 1.0, 4);
+        for y in 0..fb.height() {
+            for x in 0..fb.width() {
+                for c in 0..3 {
+                    let levels: Vec<u8> = frames.iter().map(|f| f[y][x].0[c]).collect();
+                    assert!(levels.iter().all(|&l| l == 1 || l == 2), "{levels:?}");
+                    assert_eq!(levels.iter().sum::<u8>(), 6, "{levels:?}");
+                }
+            }
+        }
+        // Neighboring pixels are out of phase.
+        assert_ne!(frames[0][0][0], frames[0][0][1]);
+    }
+
+    #[test]
+    fn brightness() {
+        let config = ColorConfig {
+            dither: false,
+            ..ColorConfig::default()
+        };
+        let mut pipeline = ColorPipeline::new(&config, DEFAULT_SIZE);
+        let fb = FrameBuffer::filled(DEFAULT_SIZE, Rgb([200, 101, 0]));
+        let out = process_frames(&mut pipeline, &fb, 0.5, 1).remove(0);
+        assert_eq!(out[0][0], Rgb([100, 51, 0]));
+
+        let out = process_frames(&mut pipeline, &fb, 0.0, 1).remove(0);
+        assert!(out.as_flattened().iter().all(|&c| c == BLACK));
+
+        // Without dithering, levels below half a level vanish, but the
+        // default pipeline dithers them.
+        let dim = FrameBuffer::filled(DEFAULT_SIZE, Rgb([10, 10, 10]));
+        let out = process_frames(&mut pipeline, &dim, 0.05, 16);
+        assert!(out.iter().all(|f| f[0][0] == BLACK));
+        let mut pipeline = ColorPipeline::new(&ColorConfig::default(), DEFAULT_SIZE);
+        let out = process_frames(&mut pipeline, &dim, 0.05, 16);
+        assert!(out.iter().any(|f| f[0][0] != BLACK));
+    }
+}
@@ -0,0 +1,62 @@
+//! Arrangement of the physical panels that make up the wall.
+//!
+//! The panels form a single canvas as the driver sees it, with panels in a
+//! chain side by side and parallel chains stacked. How the canvas is mounted
+//! on the wall is up to [`super::mapping`].
+
+use serde::{Deserialize, Serialize};
+
+/// Geometry settings, in the `[geometry]` table of the panel configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeometryConfig {
+    /// Number of rows of each panel.
+    pub rows: usize,
+    /// Number of columns of each panel.
+    pub cols: usize,
+    /// Number of panels daisy-chained on each output.
+    pub chain_length: usize,
+    /// Number of chains driven in parallel.
+    pub parallel: usize,
+    /// Wiring between the Raspberry Pi and the panels, as named by
+    /// `rpi-led-panel`, such as `"AdafruitHatPwm"` or `"Regular"`.
+    pub hardware_mapping: String,
+}
+
+impl Default for GeometryConfig {
+    fn default() -> Self {
+        Self {
+            rows: 32,
+            cols: 64,
+            chain_length: 1,
+            parallel: 1,
+            hardware_mapping: "AdafruitHatPwm".to_string(),
+        }
+    }
+}
+
+impl GeometryConfig {
+    /// Returns the width and height of the canvas in pixels.
+    pub fn canvas_size(&self) -> [usize; 2] {
+        [
+            (self.cols * self.chain_length).max(1),
+            (self.rows * self.parallel).max(1),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canvas_size() {
+        assert_eq!(GeometryConfig::default().canvas_size(), [64, 32]);
+        let config = GeometryConfig {
+            chain_length: 2,
+            parallel: 3,
+            ..GeometryConfig::default()
+        };
+        assert_eq!(config.canvas_size(), [128, 96]);
+    }
+}
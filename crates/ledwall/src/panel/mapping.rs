@@ -0,0 +1,234 @@
+//! Mapping from pixels of the wall to pixels of the canvas (see
+//! [`super::geometry`]), which depends on how the panels are mounted and
+//! wired.
+//!
+//! The wall is mirrored and rotated onto the canvas, and then passed through
+//! an optional remap table for panels whose wiring doesn't follow the
+//! driver's pixel order. The table is a text file in the settings directory
+//! with one `x,y` line for each pixel of the rotated canvas in row-major
+//! order, giving the position on the canvas that the pixel is wired to. Blank
+//! lines and lines starting with `#` are ignored.
+
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::invalid_data;
+use crate::{BLACK, FrameBuffer, Rotation};
+
+/// Mapping settings, in the `[mapping]` table of the panel configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MappingConfig {
+    /// Clockwise rotation of the wall onto the canvas in degrees (0, 90, 180,
+    /// or 270). The default of 90 suits a panel mounted on its side.
+    pub rotation: Rotation,
+    /// Whether to mirror the wall horizontally before rotating it.
+    pub mirror: bool,
+    /// File name of the remap table within the settings directory, if any.
+    pub remap: Option<String>,
+}
+
+impl Default for MappingConfig {
+    fn default() -> Self {
+        Self {
+            rotation: Rotation::R90,
+            mirror: false,
+            remap: None,
+        }
+    }
+}
+
+impl MappingConfig {
+    /// Returns the size of the wall mounted on a canvas of `canvas_size`.
+    pub fn wall_size(&self, canvas_size: [usize; 2]) -> [usize; 2] {
+        self.rotation.rotate_size(canvas_size)
+    }
+}
+
+/// Lookup table from pixels of the wall to pixels of the canvas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PixelMap {
+    size: [usize; 2],
+    canvas_size: [usize; 2],
+    /// Index in the canvas of each pixel of the wall, in the same order as
+    /// [`FrameBuffer::as_flattened()`].
+    targets: Vec<usize>,
+}
+
+impl PixelMap {
+    /// Constructs the map for a canvas of `canvas_size`, ignoring the remap
+    /// table.
+    pub fn new(config: &MappingConfig, canvas_size: [usize; 2]) -> Self {
+        Self::with_remap(config, canvas_size, None)
+    }
+
+    /// Constructs the map for a canvas of `canvas_size`, loading the remap
+    /// table from `dir` if there is one.
+    pub fn load(config: &MappingConfig, canvas_size: [usize; 2], dir: &Path) -> io::Result<Self> {
+        let remap = match &config.remap {
+            Some(file_name) => {
+                let path = dir.join(file_name);
+                let text = std::fs::read_to_string(&path)?;
+                let remap = parse_remap(&text, canvas_size)
+                    .map_err(|e| invalid_data(format!("{}: {e}", path.display())))?;
+                Some(remap)
+            }
+            None => None,
+        };
+        Ok(Self::with_remap(config, canvas_size, remap.as_deref()))
+    }
+
+    fn with_remap(
+        config: &MappingConfig,
+        canvas_size: [usize; 2],
+        remap: Option<&[usize]>,
+    ) -> Self {
+        let size = config.wall_size(canvas_size);
+        let [width, height] = size;
+        let targets = (0..width * height)
+            .map(|i| {
+                let [mut x, y] = [i % width, i / width];
+                if config.mirror {
+                    x = width - 1 - x;
+                }
+                let [x, y] = config.rotation.rotate(size, [x, y]);
+                let index = y * canvas_size[0] + x;
+                remap.map_or(index, |remap| remap[index])
+            })
+            .collect();
+        Self {
+            size,
+            canvas_size,
+            targets,
+        }
+    }
+
+    /// Returns the size of the wall.
+    pub fn size(&self) -> [usize; 2] {
+        self.size
+    }
+
+    /// Returns the size of the canvas.
+    pub fn canvas_size(&self) -> [usize; 2] {
+        self.canvas_size
+    }
+
+    /// Writes `fb`, which covers the wall, to `canvas`. Pixels of the canvas
+    /// that no pixel of the wall is mapped to are black.
+    pub fn map(&self, fb: &FrameBuffer, canvas: &mut FrameBuffer) {
+        let canvas = canvas.as_flattened_mut();
+        canvas.fill(BLACK);
+        for (&color, &target) in std::iter::zip(fb.as_flattened(), &self.targets) {
+            canvas[target] = color;
+        }
+    }
+
+    /// Writes what each pixel of the wall shows when `canvas` is sent to the
+    /// panels to `fb`, which undoes [`Self::map()`] unless the remap table maps
+    /// several pixels to the same place.
+    #[cfg_attr(feature = "rpi-led-panel", allow(dead_code))] // only shown in the simulator
+    pub fn show_mounted(&self, canvas: &FrameBuffer, fb: &mut FrameBuffer) {
+        let canvas = canvas.as_flattened();
+        for (color, &target) in std::iter::zip(fb.as_flattened_mut(), &self.targets) {
+            *color = canvas[target];
+        }
+    }
+}
+
+/// Parses a remap table for a canvas of `size` into the index on the canvas
+/// of each of its pixels.
+fn parse_remap(text: &str, [width, height]: [usize; 2]) -> Result<Vec<usize>, String> {
+    let remap = (text.lines().enumerate())
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim().starts_with('#'))
+        .map(|(i, line)| {
+            let position = line.split_once(',').and_then(|(x, y)| {
+                let [x, y]: [usize; 2] = [x.trim().parse().ok()?, y.trim().parse().ok()?];
+                (x < width && y < height).then_some(y * width + x)
+            });
+            position.ok_or_else(|| format!("line {}: invalid position {line:?}", i + 1))
+        })
+        .collect::<Result<Vec<usize>, String>>()?;
+    match remap.len() == width * height {
+        true => Ok(remap),
+        false => Err(format!(
+            "expected {} positions but found {}",
+            width * height,
+            remap.len()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DEFAULT_SIZE, Rgb};
+
+    /// Returns a frame of `size` where each pixel has a unique color.
+    fn numbered(size: [usize; 2]) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(size);
+        for (i, pixel) in fb.as_flattened_mut().iter_mut().enumerate() {
+            *pixel = Rgb([i as u8, (i >> 8) as u8, 0]);
+        }
+        fb
+    }
+
+    #[test]
+    fn default() {
+        let map = PixelMap::new(&MappingConfig::default(), [64, 32]);
+        assert_eq!(map.size(), DEFAULT_SIZE);
+
+        // The wall's top left is at the canvas's top right.
+        let fb = numbered(map.size());
+        let mut canvas = FrameBuffer::new(map.canvas_size());
+        map.map(&fb, &mut canvas);
+        assert_eq!(canvas[0][63], fb[0][0]);
+        assert_eq!(canvas[31][63], fb[0][31]);
+        assert_eq!(canvas[0][0], fb[63][0]);
+
+        let mut mounted = FrameBuffer::new(map.size());
+        map.show_mounted(&canvas, &mut mounted);
+        assert_eq!(mounted, fb);
+    }
+
+    #[test]
+    fn mirror() {
+        let config = MappingConfig {
+            rotation: Rotation::R0,
+            mirror: true,
+            remap: None,
+        };
+        let map = PixelMap::new(&config, [4, 2]);
+        let fb = numbered(map.size());
+        let mut canvas = FrameBuffer::new(map.canvas_size());
+        map.map(&fb, &mut canvas);
+        assert_eq!(canvas[0], [fb[0][3], fb[0][2], fb[0][1], fb[0][0]]);
+        assert_eq!(canvas[1][0], fb[1][3]);
+    }
+
+    #[test]
+    fn remap() {
+        let config = MappingConfig {
+            rotation: Rotation::R0,
+            ..MappingConfig::default()
+        };
+        // The second row is wired in the opposite direction, with two pixels
+        // wired to the same place by mistake.
+        let remap = parse_remap("# serpentine\n0,0\n1,0\n2,0\n\n2,1\n1,1\n1,1\n", [3, 2]).unwrap();
+        let map = PixelMap::with_remap(&config, [3, 2], Some(&remap));
+        let fb = numbered(map.size());
+        let mut canvas = FrameBuffer::new(map.canvas_size());
+        map.map(&fb, &mut canvas);
+        assert_eq!(canvas[0], fb[0]);
+        assert_eq!(canvas[1], [BLACK, fb[1][2], fb[1][0]]);
+
+        let mut mounted = FrameBuffer::new(map.size());
+        map.show_mounted(&canvas, &mut mounted);
+        assert_eq!(mounted[1], [fb[1][0], fb[1][2], fb[1][2]]);
+
+        assert!(parse_remap("0,0\n", [3, 2]).is_err());
+        assert!(parse_remap("0,0\n1,0\n2,0\n0,1\n1,1\n3,1\n", [3, 2]).is_err());
+        assert!(parse_remap("0 0\n", [1, 1]).is_err());
+    }
+}
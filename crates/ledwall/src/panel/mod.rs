@@ -0,0 +1,103 @@
+//! Processing between the shell's frame buffer and the physical LED panel,
+//! configured in `panel.toml` within [`storage::dir()`]:
+//!
+//! ```toml
+//! [geometry]
+//! rows = 32
+//! cols = 64
+//! chain_length = 2
+//! hardware_mapping = "AdafruitHatPwm"
+//!
+//! [mapping]
+//! rotation = 90
+//! mirror = false
+//! remap = "remap.txt"
+//!
+//! [color]
+//! gamma = [1.0, 1.0, 1.0]
+//! white_balance = [1.0, 0.9, 0.8]
+//! dither = true # the default
+//!
+//! [power]
+//! budget = 4000.0
+//! overlay = true
+//! ```
+//!
+//! [`storage::dir()`]: crate::storage::dir
+
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage;
+
+pub mod brightness;
+pub mod color;
+pub mod geometry;
+pub mod mapping;
+pub mod power;
+
+/// File name of the panel configuration within [`storage::dir()`].
+pub const FILE_NAME: &str = "panel.toml";
+
+/// Contents of the panel configuration file.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PanelConfig {
+    pub geometry: geometry::GeometryConfig,
+    pub mapping: mapping::MappingConfig,
+    pub color: color::ColorConfig,
+    pub power: power::PowerConfig,
+}
+
+impl PanelConfig {
+    /// Loads the configuration from `dir`, returning the default configuration
+    /// if the file does not exist.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        storage::load_toml(dir, FILE_NAME)
+    }
+
+    /// Loads the map from the wall to the canvas, falling back to the
+    /// mapping without the remap table if that fails to load.
+    pub fn pixel_map(&self, dir: &Path) -> mapping::PixelMap {
+        let canvas_size = self.geometry.canvas_size();
+        mapping::PixelMap::load(&self.mapping, canvas_size, dir).unwrap_or_else(|e| {
+            eprintln!("Error loading pixel mapping: {e}");
+            mapping::PixelMap::new(&self.mapping, canvas_size)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::TempDir;
+
+    #[test]
+    fn load() {
+        let dir = TempDir::new("panel");
+        let dir = dir.path();
+        assert_eq!(PanelConfig::load(dir).unwrap(), PanelConfig::default());
+
+        let example = "[color]\nwhite_balance = [1.0, 0.9, 0.8]\ndither = false\n";
+        std::fs::write(dir.join(FILE_NAME), example).unwrap();
+        let config = PanelConfig::load(dir).unwrap();
+        assert_eq!(config.color.white_balance, [1.0, 0.9, 0.8]);
+        assert!(!config.color.dither);
+        assert_eq!(config.color.gamma, [1.0; 3]);
+
+        let example =
+            "[geometry]\nrows = 1\ncols = 2\n[mapping]\nrotation = 0\nremap = \"remap.txt\"\n";
+        std::fs::write(dir.join(FILE_NAME), example).unwrap();
+        std::fs::write(dir.join("remap.txt"), "1,0\n0,0\n").unwrap();
+        let config = PanelConfig::load(dir).unwrap();
+        let map = config.pixel_map(dir);
+        assert_ne!(map, mapping::PixelMap::new(&config.mapping, [2, 1]));
+
+        // A broken remap table is ignored.
+        std::fs::write(dir.join("remap.txt"), "1,0\n").unwrap();
+        let map = config.pixel_map(dir);
+        assert_eq!(map, mapping::PixelMap::new(&config.mapping, [2, 1]));
+    }
+}
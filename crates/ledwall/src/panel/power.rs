@@ -0,0 +1,171 @@
+//! Estimate of the current drawn by the panel, which limits the brightness of
+//! frames that would draw more than the power supply provides.
+//!
+//! Each LED is on for a fraction of the time that follows the panel's
+//! luminance correction, so the estimate is the sum of that fraction for each
+//! channel of each pixel, times the current that the channel draws when fully
+//! on. Color correction is ignored, since it usually only lowers the draw.
+
+use serde::{Deserialize, Serialize};
+
+use crate::FrameBuffer;
+use crate::shell::PowerDraw;
+
+/// Number of halvings when searching for the brightness that fits the budget.
+const SEARCH_STEPS: usize = 12;
+
+/// Power settings, in the `[power]` table of the panel configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerConfig {
+    /// Current that the power supply provides, in milliamps.
+    pub budget: f32,
+    /// Current drawn by everything but the LEDs, such as the Raspberry Pi,
+    /// in milliamps.
+    pub idle: f32,
+    /// Current drawn by each red, green, and blue LED when fully on, in
+    /// milliamps averaged over the panel's scan.
+    pub channel: [f32; 3],
+    /// Whether to show the estimated draw in the menu.
+    pub overlay: bool,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            budget: 4000.0,
+            idle: 600.0,
+            channel: [0.7, 0.6, 0.6],
+            overlay: false,
+        }
+    }
+}
+
+/// Limits the brightness of each frame to the power budget.
+pub struct PowerLimiter {
+    config: PowerConfig,
+    /// Number of pixels at each level of each channel in the last frame.
+    histogram: Box<[[u32; 256]; 3]>,
+    draw: PowerDraw,
+}
+
+impl PowerLimiter {
+    pub fn new(config: &PowerConfig) -> Self {
+        Self {
+            config: config.clone(),
+            histogram: Box::new([[0; 256]; 3]),
+            draw: PowerDraw::default(),
+        }
+    }
+
+    /// Returns the brightness from 0 to 1 at which to show `fb`, which is
+    /// `brightness` lowered as little as needed to fit the budget.
+    pub fn limit(&mut self, fb: &FrameBuffer, brightness: f32) -> f32 {
+        *self.histogram = [[0; 256]; 3];
+        for color in fb.as_flattened() {
+            for c in 0..3 {
+                self.histogram[c][color.0[c] as usize] += 1;
+            }
+        }
+
+        let estimate = self.estimate(brightness);
+        let mut limited = brightness;
+        if estimate > self.config.budget {
+            let (mut low, mut high) = (0.0, brightness);
+            for _ in 0..SEARCH_STEPS {
+                let middle = (low + high) / 2.0;
+                match self.estimate(middle) > self.config.budget {
+                    true => high = middle,
+                    false => low = middle,
+                }
+            }
+            limited = low;
+        }
+
+        self.draw = PowerDraw {
+            estimate,
+            limited: self.estimate(limited),
+        };
+        limited
+    }
+
+    /// Returns the draw of the frame passed to the last call to
+    /// [`Self::limit()`].
+    pub fn draw(&self) -> PowerDraw {
+        self.draw
+    }
+
+    /// Returns the current in milliamps drawn by the last frame at
+    /// `brightness`.
+    fn estimate(&self, brightness: f32) -> f32 {
+        let leds: f32 = std::iter::zip(&*self.histogram, self.config.channel)
+            .map(|(counts, channel)| {
+                let on: f32 = (counts.iter().enumerate())
+                    .filter(|&(_, &count)| count > 0)
+                    .map(|(level, &count)| count as f32 * duty(level as f32 * brightness))
+                    .sum();
+                on * channel
+            })
+            .sum();
+        self.config.idle + leds
+    }
+}
+
+/// Returns the fraction of the time that an LED is on at `level` from 0 to
+/// 255, following the panel's CIE 1931 luminance correction.
+fn duty(level: f32) -> f32 {
+    let lightness = level / 255.0 * 100.0;
+    match lightness <= 8.0 {
+        true => lightness / 902.3,
+        false => ((lightness + 16.0) / 116.0).powi(3),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BLACK, DEFAULT_SIZE, FrameBuffer, Rgb, WHITE};
+
+    const CONFIG: PowerConfig = PowerConfig {
+        budget: 2000.0,
+        idle: 500.0,
+        channel: [1.0, 0.5, 0.5],
+        overlay: false,
+    };
+
+    #[test]
+    fn estimate() {
+        let mut limiter = PowerLimiter::new(&CONFIG);
+        let fb = FrameBuffer::filled(DEFAULT_SIZE, BLACK);
+        assert_eq!(limiter.limit(&fb, 1.0), 1.0);
+        assert_eq!(limiter.draw().estimate, 500.0);
+
+        let mut fb = FrameBuffer::filled(DEFAULT_SIZE, BLACK);
+        fb[0][..10].fill(Rgb([255, 255, 0]));
+        assert_eq!(limiter.limit(&fb, 1.0), 1.0);
+        assert_eq!(limiter.draw().estimate, 515.0);
+        assert_eq!(limiter.draw().limited, 515.0);
+
+        // Half the lightness is less than a fifth of the duty.
+        limiter.limit(&fb, 0.5);
+        assert!(limiter.draw().estimate < 503.0, "{:?}", limiter.draw());
+    }
+
+    #[test]
+    fn limit() {
+        let mut limiter = PowerLimiter::new(&CONFIG);
+        let fb = FrameBuffer::filled(DEFAULT_SIZE, WHITE);
+        let brightness = limiter.limit(&fb, 1.0);
+        assert!(brightness < 1.0);
+        let draw = limiter.draw();
+        assert_eq!(
+            draw.estimate,
+            500.0 + (DEFAULT_SIZE[0] * DEFAULT_SIZE[1]) as f32 * 2.0
+        );
+        assert!(draw.limited <= CONFIG.budget, "{draw:?}");
+        assert!(draw.limited > CONFIG.budget * 0.99, "{draw:?}");
+
+        // The limit never raises the brightness.
+        assert_eq!(limiter.limit(&fb, 0.1), 0.1);
+    }
+}
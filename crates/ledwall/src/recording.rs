@@ -0,0 +1,344 @@
+//! Recording and replay of the controller input passed to [`Shell::update()`].
+//!
+//! Combined with the RNG seed and frame size stored in the header, a
+//! recording that starts from a fresh [`Shell`] reproduces the session
+//! exactly.
+//!
+//! # File format
+//!
+//! All integers are little-endian.
+//!
+//! - Header
+//!   - 8 bytes: magic number [`MAGIC`]
+//!   - `u16`: format version ([`VERSION`])
+//!   - `u64`: RNG seed
+//!   - two `u32`s: width and height of the frame
+//! - Any number of records, each starting with a `u8` tag:
+//!   - `0` (frames): `u32` frame count, then the controller state held for
+//!     that many frames. The controller state is a `u8` with bit `i` set if
+//!     the controller for player `i` is connected, then a `u8` with bit `i`
+//!     set if player `i` has analog input, then a `u16` for each of the
+//!     [`MAX_PLAYERS`] players with the buttons held (see
+//!     [`Buttons::to_bits()`]), then six `f32`s for each player with analog
+//!     input (see [`Axes::to_array()`]).
+//!   - `1` (marker): `u16` byte length, then a UTF-8 label. The marker
+//!     annotates the frame after all preceding frame records.
+//!
+//! Recordings of more than [`MAX_FRAMES`] frames in total are rejected when
+//! read.
+//!
+//! [`Shell::update()`]: crate::Shell::update
+//! [`Shell`]: crate::Shell
+//! [`MAX_PLAYERS`]: crate::MAX_PLAYERS
+//! [`Axes::to_array()`]: crate::Axes::to_array
+
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::{Axes, Buttons, ControllerState, FPS, FrameInputs, MAX_PLAYERS};
+
+/// Magic number at the start of every recording file.
+pub const MAGIC: &[u8; 8] = b"LEDWALL\x1E";
+/// Current version of the recording file format.
+pub const VERSION: u16 = 1;
+/// Maximum number of frames in a recording that can be read (four hours),
+/// which keeps a corrupt frame count from using up all memory.
+pub const MAX_FRAMES: usize = 4 * 60 * 60 * FPS;
+
+const TAG_FRAMES: u8 = 0;
+const TAG_MARKER: u8 = 1;
+
+/// Recorded controller input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    /// Seed passed to [`crate::rng::reseed()`] at the start of the recording.
+    pub seed: u64,
+    /// Size of the frames that the shell drew.
+    pub size: [usize; 2],
+    /// Controller input for each frame.
+    pub frames: Vec<FrameInputs>,
+    /// Annotations on individual frames, sorted by frame.
+    pub markers: Vec<Marker>,
+}
+
+/// Annotation on a frame of a recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    /// Index of the frame that the marker annotates.
+    pub frame: usize,
+    /// Text of the annotation.
+    pub label: String,
+}
+
+impl Recording {
+    pub fn new(seed: u64, size: [usize; 2]) -> Self {
+        Self {
+            seed,
+            size,
+            frames: vec![],
+            markers: vec![],
+        }
+    }
+
+    /// Appends a frame to the recording.
+    pub fn push_frame(&mut self, inputs: FrameInputs) {
+        self.frames.push(inputs);
+    }
+
+    /// Annotates the next frame to be recorded.
+    pub fn push_marker(&mut self, label: impl Into<String>) {
+        self.markers.push(Marker {
+            frame: self.frames.len(),
+            label: label.into(),
+        });
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::read_from(io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()
+    }
+
+    pub fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&self.seed.to_le_bytes())?;
+        for n in self.size {
+            let n = u32::try_from(n)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+            w.write_all(&n.to_le_bytes())?;
+        }
+
+        let mut markers = self.markers.iter().peekable();
+        let mut frame = 0;
+        while frame < self.frames.len() || markers.peek().is_some() {
+            while let Some(marker) = markers.next_if(|m| m.frame <= frame) {
+                let label = marker.label.as_bytes();
+                let len = u16::try_from(label.len())
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "label too long"))?;
+                w.write_all(&[TAG_MARKER])?;
+                w.write_all(&len.to_le_bytes())?;
+                w.write_all(label)?;
+            }
+
+            // Run-length encode identical frames, stopping at the next marker.
+            let Some(&inputs) = self.frames.get(frame) else {
+                break;
+            };
+            let run_end = markers.peek().map_or(self.frames.len(), |m| m.frame);
+            let run_len = self.frames[frame..run_end.max(frame + 1)]
+                .iter()
+                .take(u32::MAX as usize)
+                .take_while(|&&other| other == inputs)
+                .count();
+            w.write_all(&[TAG_FRAMES])?;
+            w.write_all(&(run_len as u32).to_le_bytes())?;
+            write_inputs(&mut w, inputs)?;
+            frame += run_len;
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from(mut r: impl Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a recording file"));
+        }
+        let version = u16::from_le_bytes(read_array(&mut r)?);
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported recording version {version}"
+            )));
+        }
+        let seed = u64::from_le_bytes(read_array(&mut r)?);
+        let width = u32::from_le_bytes(read_array(&mut r)?);
+        let height = u32::from_le_bytes(read_array(&mut r)?);
+        let mut ret = Self::new(seed, [width as usize, height as usize]);
+
+        loop {
+            let mut tag = [0];
+            if r.read(&mut tag)? == 0 {
+                break; // end of file
+            }
+            match tag[0] {
+                TAG_FRAMES => {
+                    let count = u32::from_le_bytes(read_array(&mut r)?) as usize;
+                    if ret.frames.len().saturating_add(count) > MAX_FRAMES {
+                        return Err(invalid_data("recording is too long"));
+                    }
+                    let inputs = read_inputs(&mut r)?;
+                    ret.frames.extend(std::iter::repeat_n(inputs, count));
+                }
+                TAG_MARKER => {
+                    let len = u16::from_le_bytes(read_array(&mut r)?);
+                    let mut label = vec![0; len as usize];
+                    r.read_exact(&mut label)?;
+                    let label = String::from_utf8(label)
+                        .map_err(|_| invalid_data("marker label is not UTF-8"))?;
+                    ret.push_marker(label);
+                }
+                other => return Err(invalid_data(format!("unknown record tag {other}"))),
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Replay of a recording, one frame at a time.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    recording: Recording,
+    frame: usize,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            frame: 0,
+        }
+    }
+
+    /// Returns the input for the next frame along with any markers on that
+    /// frame, or `None` if the replay has ended.
+    pub fn next_frame(&mut self) -> Option<(FrameInputs, &[Marker])> {
+        let frame = self.frame;
+        let inputs = *self.recording.frames.get(frame)?;
+        self.frame += 1;
+        let markers = &self.recording.markers;
+        let start = markers.partition_point(|m| m.frame < frame);
+        let end = markers.partition_point(|m| m.frame <= frame);
+        Some((inputs, &markers[start..end]))
+    }
+}
+
+fn write_inputs(w: &mut impl Write, inputs: FrameInputs) -> io::Result<()> {
+    let mask = |f: fn(&Option<ControllerState>) -> bool| {
+        (inputs.iter().enumerate()).fold(0, |bits, (i, input)| bits | (f(input) as u8) << i)
+    };
+    let connected = mask(Option::is_some);
+    let analog = mask(|input| input.is_some_and(|s| s.axes != Axes::default()));
+    w.write_all(&[connected, analog])?;
+    for input in inputs {
+        w.write_all(&input.unwrap_or_default().buttons.to_bits().to_le_bytes())?;
+    }
+    for input in inputs.into_iter().flatten() {
+        if input.axes != Axes::default() {
+            for value in input.axes.to_array() {
+                w.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_inputs(r: &mut impl Read) -> io::Result<FrameInputs> {
+    let [connected, analog] = read_array(r)?;
+    let mut ret: FrameInputs = [None; MAX_PLAYERS];
+    for (i, input) in ret.iter_mut().enumerate() {
+        let buttons = Buttons::from_bits(u16::from_le_bytes(read_array(r)?));
+        *input = (connected & 1 << i != 0).then_some(buttons.into());
+    }
+    for (i, input) in ret.iter_mut().enumerate() {
+        if let Some(input) = input
+            && analog & 1 << i != 0
+        {
+            let mut values = [0.0; 6];
+            for value in &mut values {
+                *value = f32::from_le_bytes(read_array(r)?);
+            }
+            input.axes = Axes::from_array(values);
+        }
+    }
+    Ok(ret)
+}
+
+fn read_array<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Shell;
+    use crate::input_script::InputScript;
+
+    #[test]
+    fn replay() {
+        let script = InputScript::parse("1 .\n1 a\n1 .\n30 y\n10 . .\n1 right\n20 .").unwrap();
+        let mut shell = Shell::with_seed(7).with_size([40, 20]);
+        shell.start_recording().unwrap();
+        for frame in 0..script.len() {
+            if frame == 3 {
+                shell.add_recording_marker("jump");
+            }
+            shell.update(script.get(frame));
+        }
+        let recording = shell.stop_recording().unwrap();
+        assert_eq!(recording.frames.len(), script.len());
+
+        let mut bytes = vec![];
+        recording.write_to(&mut bytes).unwrap();
+        let recording = Recording::read_from(&bytes[..]).unwrap();
+        assert_eq!(recording.size, [40, 20]);
+        let mut markers = vec![];
+        let mut r = Replay::new(recording.clone());
+        while let Some((_, frame_markers)) = r.next_frame() {
+            markers.extend_from_slice(frame_markers);
+        }
+        let jump = Marker {
+            frame: 3,
+            label: "jump".to_string(),
+        };
+        assert_eq!(markers, [jump]);
+        let mut replay = Shell::from_recording(recording);
+        for _ in 0..script.len() {
+            replay.update([None; MAX_PLAYERS]);
+        }
+        assert_eq!(replay.frame_buffer(), shell.frame_buffer());
+
+        // Recording can't start partway through a session.
+        assert!(shell.start_recording().is_err());
+    }
+
+    #[test]
+    fn too_long() {
+        let mut recording = Recording::new(0, [1, 1]);
+        recording.push_frame([None; MAX_PLAYERS]);
+        let mut bytes = vec![];
+        recording.write_to(&mut bytes).unwrap();
+        assert_eq!(Recording::read_from(&bytes[..]).unwrap(), recording);
+
+        // The last record is the frame count followed by the input.
+        let input_len = 2 + 2 * MAX_PLAYERS;
+        let count = bytes.len() - input_len - 4;
+        let mut huge = bytes.clone();
+        huge[count..count + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = Recording::read_from(&huge[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // The limit is on the total across all records.
+        let mut total = bytes.clone();
+        total.push(TAG_FRAMES);
+        total.extend((MAX_FRAMES as u32).to_le_bytes());
+        total.extend(&bytes[count + 4..]);
+        let err = Recording::read_from(&total[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
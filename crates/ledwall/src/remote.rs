@@ -0,0 +1,196 @@
+//! Server for the network remote control protocol (see [`ledwall_remote`]).
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Instant;
+
+use ledwall_remote::{MAX_PACKET_LEN, Message, Packet, TIMEOUT};
+
+use crate::{Buttons, FrameInputs, MAX_PLAYERS};
+
+/// Environment variable that sets the address for the remote control server
+/// to listen on. The server is disabled if it is unset, empty, or `off`.
+#[cfg_attr(feature = "headless", allow(dead_code))] // unused by headless frontend
+pub const ADDR_ENV_VAR: &str = "LEDWALL_REMOTE";
+
+/// Returns the address in `$LEDWALL_REMOTE` for the remote control server to
+/// listen on, or `None` if it is disabled.
+///
+/// The server is off by default because clients don't authenticate, so anyone
+/// who can reach it can act as a controller.
+#[cfg_attr(feature = "headless", allow(dead_code))] // unused by headless frontend
+pub fn addr_from_env() -> Option<String> {
+    std::env::var(ADDR_ENV_VAR)
+        .ok()
+        .filter(|addr| !addr.is_empty() && addr != "off")
+}
+
+/// Server that receives controller input from clients on the network.
+#[derive(Debug)]
+pub struct RemoteServer {
+    socket: UdpSocket,
+    /// State of each player's controller.
+    controllers: [Option<RemoteController>; MAX_PLAYERS],
+}
+
+#[derive(Debug)]
+struct RemoteController {
+    /// Address of the client acting as the controller. Packets from other
+    /// clients are ignored until this one disconnects.
+    addr: SocketAddr,
+    last_seq: u32,
+    last_packet: Instant,
+    /// Buttons currently held.
+    held: Buttons,
+    /// Buttons pressed since the last call to [`RemoteServer::poll()`], so
+    /// that pressing and releasing a button between frames still counts.
+    pressed: Buttons,
+}
+
+impl RemoteServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            controllers: [const { None }; MAX_PLAYERS],
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Processes all pending packets and returns the buttons held on each
+    /// controller, or `None` for controllers with no client.
+    pub fn poll(&mut self, now: Instant) -> FrameInputs {
+        let mut buf = [0; MAX_PACKET_LEN + 1];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, addr)) => match Packet::decode(&buf[..len]) {
+                    Ok(packet) => self.handle_packet(packet, addr, now),
+                    Err(e) => eprintln!("Invalid remote control packet from {addr}: {e}"),
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                // On some platforms, ICMP errors from earlier sends show up here.
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => {
+                    eprintln!("Error receiving remote control packet: {e}");
+                    break;
+                }
+            }
+        }
+
+        self.controllers.each_mut().map(|slot| {
+            if slot
+                .as_ref()
+                .is_some_and(|c| now.saturating_duration_since(c.last_packet) > TIMEOUT)
+            {
+                *slot = None;
+            }
+            let c = slot.as_mut()?;
+            Some((c.held | std::mem::take(&mut c.pressed)).into())
+        })
+    }
+
+    fn handle_packet(&mut self, packet: Packet, addr: SocketAddr, now: Instant) {
+        let slot = &mut self.controllers[packet.controller as usize];
+
+        let c = match slot {
+            Some(c) if c.addr != addr => return, // controller is taken
+            // Ignore old packets, accounting for wraparound.
+            Some(c) if (packet.seq.wrapping_sub(c.last_seq) as i32) <= 0 => return,
+            Some(c) => c,
+            None if packet.message == Message::Disconnect => return,
+            None => slot.insert(RemoteController {
+                addr,
+                last_seq: packet.seq,
+                last_packet: now,
+                held: Buttons::default(),
+                pressed: Buttons::default(),
+            }),
+        };
+        c.last_seq = packet.seq;
+        c.last_packet = now;
+
+        match packet.message {
+            Message::Heartbeat(bits) => c.held = Buttons::from_bits(bits),
+            Message::Press(bits) => {
+                c.held = c.held | Buttons::from_bits(bits);
+                c.pressed = c.pressed | Buttons::from_bits(bits);
+            }
+            Message::Release(bits) => c.held = c.held & !Buttons::from_bits(bits),
+            Message::Disconnect => *slot = None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ledwall_remote::{BUTTON_NAMES, Client, Controller};
+
+    use super::*;
+
+    #[test]
+    fn button_names_match_protocol() {
+        assert_eq!(Buttons::NAMES, BUTTON_NAMES);
+    }
+
+    #[test]
+    fn loopback() {
+        let mut server = RemoteServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let a = "a".parse::<Buttons>().unwrap();
+        let up = "up".parse::<Buttons>().unwrap();
+
+        // Wait for packets to arrive on the loopback interface.
+        let poll = |server: &mut RemoteServer, now| {
+            std::thread::sleep(Duration::from_millis(20));
+            server.poll(now).map(|input| Some(input?.buttons))
+        };
+        let t0 = Instant::now();
+
+        assert_eq!(poll(&mut server, t0), [None, None, None, None]);
+
+        let mut blue = Client::connect(addr, Controller::Blue).unwrap();
+        assert_eq!(
+            poll(&mut server, t0),
+            [Some(Buttons::default()), None, None, None]
+        );
+
+        // A press and release between polls counts for one frame.
+        blue.press(a.to_bits()).unwrap();
+        blue.release(a.to_bits()).unwrap();
+        blue.press(up.to_bits()).unwrap();
+        assert_eq!(poll(&mut server, t0), [Some(a | up), None, None, None]);
+        assert_eq!(poll(&mut server, t0), [Some(up), None, None, None]);
+
+        // Another client can't take the blue controller.
+        let mut other = Client::connect(addr, Controller::Blue).unwrap();
+        other.press(a.to_bits()).unwrap();
+        let mut green = Client::connect(addr, Controller::Green).unwrap();
+        green.press(a.to_bits()).unwrap();
+        let _yellow = Client::connect(addr, Controller::Yellow).unwrap();
+        assert_eq!(
+            poll(&mut server, t0),
+            [Some(up), Some(a), None, Some(Buttons::default())],
+        );
+
+        // Silent clients time out.
+        blue.heartbeat().unwrap();
+        let t1 = t0 + TIMEOUT * 2;
+        assert_eq!(poll(&mut server, t1), [Some(up), None, None, None]);
+        assert_eq!(
+            poll(&mut server, t1 + TIMEOUT * 2),
+            [None, None, None, None]
+        );
+
+        // Disconnecting frees the controller for another client.
+        let mut blue = Client::connect(addr, Controller::Blue).unwrap();
+        blue.disconnect().unwrap();
+        other.heartbeat().unwrap();
+        assert_eq!(poll(&mut server, t1), [Some(a), None, None, None]);
+    }
+}
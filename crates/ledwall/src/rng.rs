@@ -0,0 +1,25 @@
+//! Deterministic random number generation.
+//!
+//! All randomness in activities should come from [`new_rng()`] so that a
+//! session can be reproduced exactly from its seed.
+
+use std::cell::RefCell;
+
+use rand::SeedableRng;
+use rand::rngs::SmallRng;
+
+thread_local! {
+    static SEED_RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_os_rng());
+}
+
+/// Reseeds the random number generator used by [`new_rng()`] on the current
+/// thread.
+pub fn reseed(seed: u64) {
+    SEED_RNG.set(SmallRng::seed_from_u64(seed));
+}
+
+/// Returns a new random number generator, derived deterministically from the
+/// seed most recently passed to [`reseed()`].
+pub fn new_rng() -> SmallRng {
+    SEED_RNG.with_borrow_mut(SmallRng::from_rng)
+}
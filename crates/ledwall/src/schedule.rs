@@ -0,0 +1,360 @@
+//! Time-of-day schedule for brightness, sleep, and activities.
+//!
+//! The schedule is a list of time windows in `schedule.toml` within
+//! [`storage::dir()`]:
+//!
+//! ```toml
+//! [[window]] # dim at night
+//! start = "21:00"
+//! end = "07:00"
+//! brightness = 4
+//!
+//! [[window]] # turn off in the small hours
+//! start = "01:00"
+//! end = "06:00"
+//! sleep = true
+//!
+//! [[window]] # show the rainbow over lunch
+//! start = "12:00"
+//! end = "13:00"
+//! activity = "rainbow"
+//! ```
+//!
+//! A window that ends before it starts wraps around midnight, and a window
+//! that ends when it starts lasts all day. Where windows overlap, later ones
+//! take precedence.
+//!
+//! [`storage::dir()`]: crate::storage::dir
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage;
+
+/// File name of the schedule within [`storage::dir()`].
+pub const FILE_NAME: &str = "schedule.toml";
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Contents of the schedule file.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Schedule {
+    #[serde(rename = "window")]
+    pub windows: Vec<Window>,
+}
+
+impl Schedule {
+    /// Loads the schedule from `dir`, returning an empty schedule if the file
+    /// does not exist.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        storage::load_toml(dir, FILE_NAME)
+    }
+
+    /// Returns what the schedule asks for at `time`.
+    pub fn state_at(&self, time: TimeOfDay) -> ScheduleState {
+        let mut ret = ScheduleState::default();
+        for window in self.windows.iter().filter(|w| w.contains(time)) {
+            ret.brightness = window.brightness.map(u8::from).or(ret.brightness);
+            ret.sleep |= window.sleep;
+            ret.activity = window.activity.clone().or(ret.activity);
+        }
+        ret
+    }
+}
+
+/// Time window in the schedule.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Window {
+    pub start: TimeOfDay,
+    pub end: TimeOfDay,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<Brightness>,
+    /// Whether to turn off the panel.
+    #[serde(default)]
+    pub sleep: bool,
+    /// ID of the activity to switch to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activity: Option<String>,
+}
+
+impl Window {
+    /// Returns whether `time` is within the window.
+    pub fn contains(&self, time: TimeOfDay) -> bool {
+        if self.start <= self.end {
+            self.start == self.end || (self.start..self.end).contains(&time)
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Brightness on a scale from 1 to 20.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct Brightness(u8);
+
+impl TryFrom<u8> for Brightness {
+    type Error = String;
+
+    fn try_from(brightness: u8) -> Result<Self, Self::Error> {
+        match brightness {
+            1..=20 => Ok(Self(brightness)),
+            _ => Err(format!("invalid brightness {brightness}; expected 1 to 20")),
+        }
+    }
+}
+
+impl From<Brightness> for u8 {
+    fn from(brightness: Brightness) -> Self {
+        brightness.0
+    }
+}
+
+/// What the schedule asks for at a particular time.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScheduleState {
+    /// Brightness on a scale from 1 to 20, or `None` to use the brightness
+    /// from the settings.
+    pub brightness: Option<u8>,
+    /// Whether to turn off the panel.
+    pub sleep: bool,
+    /// ID of the activity to show, or `None` to use the activity from the
+    /// settings.
+    pub activity: Option<String>,
+}
+
+/// Time of day, in seconds since midnight.
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(u32);
+
+impl TimeOfDay {
+    /// Constructs a time of day from the number of seconds since midnight,
+    /// wrapping around at the end of the day.
+    pub fn from_seconds(seconds: u32) -> Self {
+        Self(seconds % SECONDS_PER_DAY)
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (h, m, s) = (self.0 / 3600, self.0 / 60 % 60, self.0 % 60);
+        write!(f, "{h:02}:{m:02}")?;
+        if s != 0 {
+            write!(f, ":{s:02}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = String;
+
+    /// Parses `HH:MM` or `HH:MM:SS` in 24-hour time.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid time of day {s:?}; expected HH:MM or HH:MM:SS");
+        let mut parts = s
+            .split(':')
+            .map(|part| part.parse::<u32>().map_err(|_| err()));
+        let h = parts.next().ok_or_else(err)??;
+        let m = parts.next().ok_or_else(err)??;
+        let s = parts.next().transpose()?.unwrap_or(0);
+        if parts.next().is_some() || h >= 24 || m >= 60 || s >= 60 {
+            return Err(err());
+        }
+        Ok(Self(h * 3600 + m * 60 + s))
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        time.to_string()
+    }
+}
+
+/// Source of the current time of day.
+pub trait Clock {
+    fn time_of_day(&self) -> TimeOfDay;
+}
+
+/// Clock that reads the system time in the local time zone.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct LocalClock;
+
+impl Clock for LocalClock {
+    fn time_of_day(&self) -> TimeOfDay {
+        use chrono::Timelike;
+
+        TimeOfDay::from_seconds(chrono::Local::now().num_seconds_from_midnight())
+    }
+}
+
+/// Follows a schedule using a clock.
+pub struct Scheduler {
+    schedule: Schedule,
+    clock: Box<dyn Clock>,
+    state: ScheduleState,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(Schedule::default(), Box::new(LocalClock))
+    }
+}
+
+impl Scheduler {
+    pub fn new(schedule: Schedule, clock: Box<dyn Clock>) -> Self {
+        Self {
+            schedule,
+            clock,
+            state: ScheduleState::default(),
+        }
+    }
+
+    /// Returns what the schedule asked for when the clock was last checked.
+    pub fn state(&self) -> &ScheduleState {
+        &self.state
+    }
+
+    /// Checks the clock and returns whether the schedule asks for something
+    /// different than it did last time.
+    pub fn update(&mut self) -> bool {
+        if self.schedule.windows.is_empty() {
+            return false;
+        }
+        let new_state = self.schedule.state_at(self.clock.time_of_day());
+        if new_state == self.state {
+            return false;
+        }
+        self.state = new_state;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    /// Clock that can be set from outside the scheduler.
+    struct TestClock(Rc<Cell<TimeOfDay>>);
+
+    impl Clock for TestClock {
+        fn time_of_day(&self) -> TimeOfDay {
+            self.0.get()
+        }
+    }
+
+    fn time(s: &str) -> TimeOfDay {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_time() {
+        assert_eq!(time("00:00"), TimeOfDay(0));
+        assert_eq!(time("7:05"), TimeOfDay(7 * 3600 + 5 * 60));
+        assert_eq!(time("23:59:59"), TimeOfDay(SECONDS_PER_DAY - 1));
+        assert_eq!(time("23:59:59").to_string(), "23:59:59");
+        assert_eq!(time("07:30:00").to_string(), "07:30");
+        for s in ["24:00", "12:60", "12", "12:00:00:00", "noon", ""] {
+            assert!(s.parse::<TimeOfDay>().is_err(), "{s:?} should be invalid");
+        }
+    }
+
+    #[test]
+    fn windows() {
+        let schedule: Schedule = toml::from_str(
+            r#"
+            [[window]]
+            start = "21:00"
+            end = "07:00"
+            brightness = 4
+
+            [[window]]
+            start = "01:00"
+            end = "06:00"
+            brightness = 1
+            sleep = true
+
+            [[window]]
+            start = "06:30"
+            end = "12:00"
+            activity = "life"
+            "#,
+        )
+        .unwrap();
+
+        let state = |s| schedule.state_at(time(s));
+        assert_eq!(state("20:59:59"), ScheduleState::default());
+        assert_eq!(state("21:00").brightness, Some(4));
+        assert_eq!(state("00:30").brightness, Some(4));
+        assert!(!state("00:30").sleep);
+        assert_eq!(state("01:00").brightness, Some(1));
+        assert!(state("05:59").sleep);
+        assert_eq!(state("06:45").brightness, Some(4));
+        assert_eq!(state("06:45").activity.as_deref(), Some("life"));
+        assert_eq!(state("07:00").brightness, None);
+        assert_eq!(state("12:00"), ScheduleState::default());
+
+        for brightness in [0, 21] {
+            let window = format!(
+                "[[window]]\nstart = \"00:00\"\nend = \"00:00\"\nbrightness = {brightness}"
+            );
+            assert!(toml::from_str::<Schedule>(&window).is_err());
+        }
+
+        let all_day = Window {
+            start: time("08:00"),
+            end: time("08:00"),
+            brightness: None,
+            sleep: true,
+            activity: None,
+        };
+        assert!(all_day.contains(time("00:00")));
+        assert!(all_day.contains(time("07:59")));
+
+        assert!(toml::from_str::<Schedule>("[[window]]\nstart = \"01:00\"").is_err());
+    }
+
+    #[test]
+    fn scheduler() {
+        let now = Rc::new(Cell::new(time("20:00")));
+        let schedule: Schedule = toml::from_str(
+            r#"
+            [[window]]
+            start = "21:00"
+            end = "07:00"
+            brightness = 4
+            "#,
+        )
+        .unwrap();
+        let mut scheduler = Scheduler::new(schedule, Box::new(TestClock(Rc::clone(&now))));
+
+        assert!(!scheduler.update());
+        assert_eq!(scheduler.state().brightness, None);
+        now.set(time("21:00"));
+        assert!(scheduler.update());
+        assert_eq!(scheduler.state().brightness, Some(4));
+        now.set(time("03:00"));
+        assert!(!scheduler.update());
+        now.set(time("07:00"));
+        assert!(scheduler.update());
+        assert_eq!(scheduler.state().brightness, None);
+    }
+}
@@ -0,0 +1,96 @@
+//! Shell settings that persist across restarts.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::activities::setting::SettingValue;
+use crate::{Activity, DEFAULT_BRIGHTNESS, DEFAULT_VOLUME, storage};
+
+/// File name of the settings file within [`storage::dir()`].
+pub const FILE_NAME: &str = "settings.toml";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// Brightness on a scale from 0 to 20.
+    pub brightness: u8,
+    /// Volume on a scale from 0 to 20.
+    pub volume: u8,
+    /// ID of the selected activity, or `None` for the first one in the menu.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activity: Option<String>,
+    /// Values of activity settings (see [`crate::activities::setting`]) that
+    /// were changed in the menu, by activity ID and setting name.
+    pub activities: BTreeMap<String, BTreeMap<String, toml::Value>>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            brightness: DEFAULT_BRIGHTNESS,
+            volume: DEFAULT_VOLUME,
+            activity: None,
+            activities: BTreeMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Loads settings from `dir`, returning the default settings if the file
+    /// does not exist.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        storage::load_toml(dir, FILE_NAME)
+    }
+
+    /// Records the value of an activity's setting.
+    pub fn set_activity_setting(&mut self, id: &str, name: &str, value: &SettingValue) {
+        (self.activities.entry(id.to_owned()).or_default())
+            .insert(name.to_owned(), value.to_toml());
+    }
+
+    /// Changes an activity's settings to their saved values.
+    pub fn apply_activity_settings(&self, activity: &mut dyn Activity) {
+        let id = activity.id();
+        let Some(saved) = self.activities.get(id) else {
+            return;
+        };
+        for (i, setting) in activity.settings().into_iter().enumerate() {
+            let Some(saved_value) = saved.get(setting.name) else {
+                continue;
+            };
+            match setting.value.parse_toml(saved_value) {
+                Some(value) => activity.set_setting(i, value),
+                None => eprintln!(
+                    "Invalid value {saved_value} for setting {id}.{}",
+                    setting.name
+                ),
+            }
+        }
+    }
+
+    /// Saves settings to `dir`.
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let s = toml::to_string(self).map_err(io::Error::other)?;
+        storage::write_atomic(&dir.join(FILE_NAME), s.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selected_activity() {
+        let settings: Settings = toml::from_str("activity = \"life\"").unwrap();
+        assert_eq!(settings.activity.as_deref(), Some("life"));
+        let reloaded: Settings = toml::from_str(&toml::to_string(&settings).unwrap()).unwrap();
+        assert_eq!(reloaded, settings);
+
+        let settings: Settings = toml::from_str("").unwrap();
+        assert_eq!(settings.activity, None);
+        assert!(!toml::to_string(&settings).unwrap().contains("activity ="));
+    }
+}
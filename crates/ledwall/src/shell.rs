@@ -1,68 +1,216 @@
+use std::io;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use rand::{Rng, SeedableRng};
+
+use crate::activities::MenuConfig;
+use crate::attract::{Attract, AttractConfig, IdleMode};
+use crate::controllers::{ControllerMap, Controllers};
+use crate::recording::{Recording, Replay};
+use crate::remote::RemoteServer;
+use crate::schedule::{Schedule, Scheduler};
+use crate::settings::Settings;
+use crate::sound::{Note, Sound, Synth, Wave};
+use crate::toast::Toasts;
 use crate::{
-    Activity, AnimationFrame, BLACK, Buttons, ControllerInput, DEFAULT_BRIGHTNESS, DEFAULT_VOLUME,
-    FrameBuffer, FrameBufferRect, FullInput, HEIGHT, Rgb, WHITE, WIDTH, Widget, activities,
-    map_range, step_opt_animation, widgets,
+    Activity, AnimationFrame, Axes, BLACK, Buttons, ControllerInput, DEFAULT_BRIGHTNESS,
+    DEFAULT_SIZE, DEFAULT_VOLUME, Deadzones, FPS, FrameBuffer, FrameBufferRect, FrameInputs,
+    FullInput, MAX_PLAYERS, Player, Rgb, WHITE, Widget, activities, map_range, state,
+    step_opt_animation, storage, text, widgets,
 };
 
+/// Width of the menu, which is centered on walls wider than a single panel.
+const MENU_WIDTH: usize = DEFAULT_SIZE[0];
+
 const CONTROLLER_STATUS_BACKGROUND: Rgb = BLACK;
 
-const BLUE_CONTROLLER_COLOR: Rgb = Rgb::from_hex(0x7777FF);
-const GREEN_CONTROLLER_COLOR: Rgb = Rgb::from_hex(0x88DD88);
 const DARKEN_DISCONNECTED_CONTROLLER: f32 = 0.75;
+/// Number of seconds between blinks of free players while an unassigned
+/// controller is connected.
+const CLAIM_BLINK_DURATION: f32 = 1.0;
 
-#[cfg(feature = "gilrs")]
-const BLUE_CONTROLLER_UUID: [u8; 16] = [5, 0, 0, 0, 200, 45, 0, 0, 32, 144, 0, 0, 0, 1, 0, 0];
-#[cfg(feature = "gilrs")]
-const GREEN_CONTROLLER_UUID: [u8; 16] = [3, 0, 0, 0, 200, 45, 0, 0, 32, 144, 0, 0, 0, 1, 0, 0];
-
-const SHOW_VOLUME: bool = false;
 const VOLUME_COLOR: Rgb = Rgb::from_hex(0x69F657);
 const BRIGHTNESS_COLOR: Rgb = Rgb::from_hex(0xFFE400);
+const ACTIVITY_SETTING_COLOR: Rgb = Rgb::from_hex(0x57C8F6);
 
 const BACKGROUND_DIM: f32 = 0.875;
 
+/// Color of notifications that aren't about a particular player.
+const TOAST_COLOR: Rgb = WHITE;
+const POWER_COLOR: Rgb = WHITE;
+/// Color of the estimated draw while the brightness is limited to the power
+/// budget.
+const POWER_LIMITED_COLOR: Rgb = Rgb::from_hex(0xFF5040);
+
+const MENU_MOVE_SOUND: Sound = Sound(&[Note::new(Wave::Pulse, 660.0, 0.03).fade(0.0)]);
+/// Played when the volume changes, to preview the new volume.
+const VOLUME_SOUND: Sound = Sound(&[Note::new(Wave::Square, 880.0, 0.08).fade(0.5)]);
+
+/// Number of seconds to scroll the menu by one row of the settings page.
+const SETTING_SCROLL_DURATION: f32 = 0.125;
+
+/// Number of seconds between steps of brightness when the schedule changes it.
+const BRIGHTNESS_RAMP_INTERVAL: f32 = 1.0;
+
+/// Number of seconds to wait after a settings change before saving, so that
+/// holding a slider button doesn't write the settings file many times.
+const SETTINGS_SAVE_DELAY: f32 = 2.0;
+/// Number of seconds between saves of the current activity's state.
+const ACTIVITY_SAVE_INTERVAL: f32 = 30.0;
+
 #[derive(Debug, Default)]
 pub struct ShellFrameOutput {
     pub new_brightness: Option<u8>,
 }
 
+/// Current drawn by the panel, estimated by the frontend.
+#[cfg_attr(feature = "headless", allow(dead_code))] // only estimated on the panel
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct PowerDraw {
+    /// Milliamps that the frame would draw at the chosen brightness.
+    pub estimate: f32,
+    /// Milliamps that the frame draws once its brightness is limited to the
+    /// power budget.
+    pub limited: f32,
+}
+
 /// Graphical shell that provides access to system settings and multiple apps.
 pub struct Shell {
-    first_frame: std::time::Instant,
-    frame_buffer: Box<FrameBuffer>,
-    last_input_blue: Option<Buttons>,
-    last_input_green: Option<Buttons>,
+    /// Number of frames since the shell was constructed.
+    frame: u64,
+    seed: u64,
+    frame_buffer: FrameBuffer,
+    last_inputs: FrameInputs,
+
+    recording: Option<Recording>,
+    replay: Option<Replay>,
+    remote: Option<RemoteServer>,
+
+    /// Directory for persistent files, or `None` to disable persistence.
+    storage_dir: Option<PathBuf>,
+    /// Settings most recently chosen by the user.
+    settings: Settings,
+    /// Frame on which the settings were changed, if they have not been saved
+    /// since.
+    settings_changed_frame: Option<u64>,
+    /// State of each activity most recently written to disk, used to avoid
+    /// rewriting unchanged state.
+    saved_activity_states: Vec<Option<Vec<u8>>>,
 
     #[cfg(feature = "gilrs")]
     gilrs: gilrs::Gilrs,
+    /// Assignment of gilrs controllers to players.
+    controllers: Controllers,
 
+    /// Which activities are in the menu, and in what order.
+    menu_config: MenuConfig,
+    /// Activities in the menu.
     activities: Vec<Box<dyn Activity>>,
     current_activity: usize,
     activity_reset_animation: Option<ActivityResetAnimation>,
 
+    /// What to show while nobody is playing.
+    attract_config: AttractConfig,
+    /// Frame on which a button was last held or a controller connected.
+    last_input_frame: u64,
+    /// Whether nobody is playing.
+    idle: bool,
+    /// Attract mode, if it is running.
+    attract: Option<Attract>,
+
+    /// Time-of-day schedule for brightness, sleep, and activities.
+    scheduler: Scheduler,
+    /// Brightness chosen in the menu while the schedule sets the brightness,
+    /// which lasts until the schedule changes.
+    brightness_override: Option<u8>,
+    /// Whether the schedule has turned off the panel.
+    asleep: bool,
+
     /// Volume on a scale from 0 to 20
     volume_slider: widgets::LabeledSlider,
     /// Brightness on a scale from 0 to 20
     brightness_slider: widgets::LabeledSlider,
+    /// Synthesizer for the sound effects and music of the current activity
+    /// and the menu.
+    synth: Synth,
+    /// Notifications shown over the current activity or the menu.
+    toasts: Toasts,
+    /// Estimated current drawn by the panel, shown in the menu if the
+    /// frontend provides it.
+    power_draw: Option<PowerDraw>,
 
     in_menu: bool,
     menu_animation: Option<MenuAnimation>,
+    /// Rows of the current activity's settings page, below the brightness
+    /// slider.
+    setting_rows: Vec<widgets::SettingRow>,
+    /// Selected row of the settings page, or `None` if the page is closed.
+    setting_cursor: Option<usize>,
+    /// Number of pixels that the menu is scrolled up to show the settings
+    /// page.
+    setting_scroll: f32,
 }
 impl Default for Shell {
     fn default() -> Self {
+        Self::new(DEFAULT_SIZE)
+    }
+}
+
+impl Shell {
+    /// Constructs a shell that draws frames of `size`, with a random seed and
+    /// the storage in [`storage::dir()`].
+    pub fn new(size: [usize; 2]) -> Self {
+        Self::with_random_seed()
+            .with_size(size)
+            .with_storage_dir(storage::dir())
+    }
+
+    /// Constructs a shell with a random seed and no storage, which is the
+    /// state that recordings start from.
+    pub fn with_random_seed() -> Self {
+        Self::with_seed(rand::rngs::SmallRng::from_os_rng().random())
+    }
+
+    /// Constructs a shell with a specific seed for all randomness, to make the
+    /// session reproducible.
+    pub fn with_seed(seed: u64) -> Self {
+        crate::rng::reseed(seed);
+
         Self {
-            first_frame: std::time::Instant::now(),
-            frame_buffer: Box::new([[BLACK; WIDTH]; HEIGHT]),
-            last_input_blue: None,
-            last_input_green: None,
+            frame: 0,
+            seed,
+            frame_buffer: FrameBuffer::new(DEFAULT_SIZE),
+            last_inputs: [None; MAX_PLAYERS],
+
+            recording: None,
+            replay: None,
+            remote: None,
+
+            storage_dir: None,
+            settings: Settings::default(),
+            settings_changed_frame: None,
+            saved_activity_states: vec![],
 
             #[cfg(feature = "gilrs")]
             gilrs: gilrs::Gilrs::new().expect("error initializing gamepad"),
+            controllers: Controllers::default(),
 
+            menu_config: MenuConfig::default(),
             activities: activities::init_activities(),
             current_activity: 0,
             activity_reset_animation: None,
 
+            attract_config: AttractConfig::default(),
+            last_input_frame: 0,
+            idle: false,
+            attract: None,
+
+            scheduler: Scheduler::default(),
+            brightness_override: None,
+            asleep: false,
+
             volume_slider: widgets::LabeledSlider {
                 slider: widgets::Slider::new(DEFAULT_VOLUME, 0..=20, VOLUME_COLOR),
                 icon: include_rgba_image!("menu/volume.rgba"),
@@ -73,88 +221,449 @@ impl Default for Shell {
                 icon: include_rgba_image!("menu/brightness.rgba"),
                 overlay: include_rgba_image!("menu/l2_r2.rgba"),
             },
+            synth: Synth::default(),
+            toasts: Toasts::new(DEFAULT_SIZE[0]),
+            power_draw: None,
 
             in_menu: true,
             menu_animation: None,
+            setting_rows: vec![],
+            setting_cursor: None,
+            setting_scroll: 0.0,
         }
     }
-}
 
-impl Shell {
+    /// Constructs a fresh shell of the recording's size that replays it,
+    /// ignoring input passed to [`Shell::update()`] until the recording ends.
+    pub fn from_recording(recording: Recording) -> Self {
+        let mut ret = Self::with_seed(recording.seed).with_size(recording.size);
+        ret.replay = Some(Replay::new(recording));
+        ret
+    }
+
+    /// Changes the size of the frames that the shell draws, which is
+    /// [`DEFAULT_SIZE`] unless this is called. This should be called before
+    /// [`Shell::with_storage_dir()`], so that activities restore state saved
+    /// at the same size.
+    pub fn with_size(mut self, size: [usize; 2]) -> Self {
+        self.frame_buffer = FrameBuffer::new(size);
+        self.toasts = Toasts::new(size[0]);
+        for activity in &mut self.activities {
+            activity.set_size(size);
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.size = size;
+        }
+        self
+    }
+
+    /// Loads settings, controller assignments, the menu and attract mode
+    /// configuration, the schedule, activity configuration, and activity state from `dir` and
+    /// saves them there whenever they change.
+    pub fn with_storage_dir(mut self, dir: PathBuf) -> Self {
+        let schedule = Schedule::load(&dir).unwrap_or_else(|e| {
+            eprintln!("Error loading schedule: {e}");
+            Schedule::default()
+        });
+        self.menu_config = MenuConfig::load(&dir).unwrap_or_else(|e| {
+            eprintln!("Error loading menu configuration: {e}");
+            MenuConfig::default()
+        });
+        self.activities =
+            activities::init_menu(&self.menu_config, &dir.join(activities::script::DIR_NAME));
+        // The schedule can only switch to activities in the menu, including
+        // scripts.
+        for id in schedule.windows.iter().filter_map(|w| w.activity.as_ref()) {
+            if !self.activities.iter().any(|a| a.id() == id) {
+                eprintln!("Unknown activity {id:?} in schedule, or it is not in the menu");
+            }
+        }
+        self.scheduler = Scheduler::new(schedule, Box::new(crate::schedule::LocalClock));
+        self.attract_config = AttractConfig::load(&dir).unwrap_or_else(|e| {
+            eprintln!("Error loading attract mode configuration: {e}");
+            AttractConfig::default()
+        });
+        self.controllers = Controllers::new(ControllerMap::load(&dir).unwrap_or_else(|e| {
+            eprintln!("Error loading controller assignments: {e}");
+            ControllerMap::default()
+        }));
+        self.settings = Settings::load(&dir).unwrap_or_else(|e| {
+            eprintln!("Error loading settings: {e}");
+            Settings::default()
+        });
+        self.brightness_slider
+            .slider
+            .set_without_animation(self.settings.brightness);
+        self.volume_slider
+            .slider
+            .set_without_animation(self.settings.volume);
+        self.current_activity = self.saved_activity();
+
+        let size = self.frame_buffer.size();
+        self.saved_activity_states = self
+            .activities
+            .iter_mut()
+            .map(|activity| {
+                let id = activity.id();
+                activity.set_size(size);
+                if let Err(e) = activity.load_config(&dir) {
+                    eprintln!("Error loading config for {id}: {e}");
+                }
+                self.settings.apply_activity_settings(activity.as_mut());
+                let loaded = state::load(&dir, id).and_then(|data| {
+                    if let Some(data) = &data {
+                        activity.load_state(data)?;
+                    }
+                    Ok(data)
+                });
+                loaded.unwrap_or_else(|e| {
+                    eprintln!("Error loading state for {id}: {e}");
+                    activity.reset();
+                    None
+                })
+            })
+            .collect();
+
+        self.storage_dir = Some(dir);
+
+        self
+    }
+
+    /// Returns the brightness on a scale from 0 to 20.
+    pub fn brightness(&self) -> u8 {
+        self.brightness_slider.slider.get()
+    }
+    /// Returns the volume on a scale from 0 to 20.
+    pub fn volume(&self) -> u8 {
+        self.volume_slider.slider.get()
+    }
+
+    /// Records a change to the settings, to be saved after a short delay.
+    fn settings_changed(&mut self) {
+        self.settings.volume = self.volume();
+        let id = self.activities[self.current_activity].id();
+        self.settings.activity = Some(id.to_owned());
+        self.settings_changed_frame = Some(self.frame);
+    }
+
+    /// Saves settings if they were changed more than [`SETTINGS_SAVE_DELAY`]
+    /// seconds ago. Saving is silent unless it fails.
+    fn autosave_settings(&mut self) {
+        let Some(changed_frame) = self.settings_changed_frame else {
+            return;
+        };
+        if self.frame < changed_frame + (SETTINGS_SAVE_DELAY * FPS as f32) as u64 {
+            return;
+        }
+        self.settings_changed_frame = None;
+        if let Some(dir) = &self.storage_dir
+            && let Err(e) = self.settings.save(dir)
+        {
+            eprintln!("Error saving settings: {e}");
+            self.notify("save failed");
+        }
+    }
+
+    /// Saves the state of an activity, if it has changed since it was last
+    /// saved.
+    fn save_activity_state(&mut self, index: usize) {
+        let Some(dir) = &self.storage_dir else {
+            return;
+        };
+        let activity = &self.activities[index];
+        let new_state = activity.save_state();
+        let saved_state = &mut self.saved_activity_states[index];
+        if new_state == *saved_state {
+            return;
+        }
+        match state::save(dir, activity.id(), new_state.as_deref()) {
+            Ok(()) => *saved_state = new_state,
+            Err(e) => eprintln!("Error saving state for {}: {e}", activity.id()),
+        }
+    }
+
+    /// Immediately saves settings and the current activity's state, if they
+    /// have changed. Call this before exiting.
+    pub fn save_now(&mut self) {
+        if self.settings_changed_frame.take().is_some()
+            && let Some(dir) = &self.storage_dir
+            && let Err(e) = self.settings.save(dir)
+        {
+            eprintln!("Error saving settings: {e}");
+        }
+        self.save_activity_state(self.current_activity);
+    }
+
+    /// Returns the seed for all randomness in the shell.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Starts recording input passed to [`Shell::update()`].
+    ///
+    /// Since [`Shell::from_recording()`] replays from a fresh shell, this
+    /// fails unless the shell has no storage and hasn't been updated yet.
+    pub fn start_recording(&mut self) -> io::Result<()> {
+        if self.frame != 0 || self.storage_dir.is_some() {
+            return Err(io::Error::other(
+                "recordings must start on the first frame of a shell without storage",
+            ));
+        }
+        self.recording = Some(Recording::new(self.seed, self.frame_buffer.size()));
+        Ok(())
+    }
+    /// Stops recording input and returns the recording, or `None` if there was
+    /// no recording in progress.
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+    /// Returns whether input is currently being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+    /// Annotates the next frame of the recording in progress, if there is one.
+    pub fn add_recording_marker(&mut self, label: impl Into<String>) {
+        if let Some(recording) = &mut self.recording {
+            recording.push_marker(label);
+        }
+    }
+    /// Returns whether a recording is currently being replayed.
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Starts a server that lets clients on the network act as the controller
+    /// for any of the players.
+    pub fn start_remote_server(&mut self, addr: impl ToSocketAddrs) -> io::Result<()> {
+        let remote = RemoteServer::bind(addr)?;
+        println!(
+            "Listening for remote controllers on {}",
+            remote.local_addr()?
+        );
+        self.remote = Some(remote);
+        Ok(())
+    }
+
+    /// Shows a notification over the current activity or the menu for a few
+    /// seconds.
+    pub fn notify(&mut self, text: impl Into<String>) {
+        self.toasts.post(text, TOAST_COLOR);
+    }
+
+    /// Shows the estimated current drawn by the panel in the menu.
+    #[cfg_attr(feature = "headless", allow(dead_code))] // only estimated on the panel
+    pub fn set_power_draw(&mut self, draw: PowerDraw) {
+        self.power_draw = Some(draw);
+    }
+
     pub fn frame_buffer(&self) -> &FrameBuffer {
         &self.frame_buffer
     }
 
+    /// Returns the audio samples for the last frame, at
+    /// [`crate::sound::SAMPLE_RATE`].
+    pub fn audio_samples(&self) -> &[f32] {
+        self.synth.samples()
+    }
+
     #[cfg(feature = "gilrs")]
-    pub fn read_gilrs_input(&mut self) -> (Option<Buttons>, Option<Buttons>) {
+    pub fn read_gilrs_input(&mut self) -> FrameInputs {
         use gilrs::{Axis, Button};
 
+        use crate::controllers::Uuid;
+        use crate::{Axes, Buttons, ControllerState};
+
         // Process gilrs events
         while let Some(ev) = self.gilrs.next_event() {
             if let gilrs::EventType::Connected = ev.event
                 && let Some(gamepad) = self.gilrs.connected_gamepad(ev.id)
             {
-                let uuid = gamepad.uuid();
-                println!("Connected controller with UUID {uuid:?}");
+                let uuid = Uuid(gamepad.uuid());
+                match self.controllers.map.get(uuid) {
+                    Some(config) => {
+                        println!("Connected {} controller with UUID {uuid}", config.player);
+                    }
+                    None => {
+                        println!("Connected unassigned controller with UUID {uuid}");
+                        println!("Press A on it to claim a player");
+                        self.notify("press a to join");
+                    }
+                }
             }
         }
 
-        let mut blue = None;
-        let mut green = None;
-
-        for (_id, gamepad) in self.gilrs.gamepads() {
-            let x = gamepad.axis_data(Axis::LeftStickX);
-            let y = gamepad.axis_data(Axis::LeftStickY);
-            let is_button_pressed = |b| gamepad.button_data(b).is_some_and(|d| d.is_pressed());
-            let current_button_states = Buttons {
-                up: y.is_some_and(|y| y.value() > 0.5),
-                down: y.is_some_and(|y| y.value() < -0.5),
-                left: x.is_some_and(|x| x.value() < -0.5),
-                right: x.is_some_and(|x| x.value() > 0.5),
-                a: is_button_pressed(Button::East),
-                b: is_button_pressed(Button::South),
-                x: is_button_pressed(Button::North),
-                y: is_button_pressed(Button::West),
-                l: is_button_pressed(Button::LeftTrigger),
-                r: is_button_pressed(Button::RightTrigger),
-                lt: is_button_pressed(Button::LeftTrigger2),
-                rt: is_button_pressed(Button::RightTrigger2),
-                plus: is_button_pressed(Button::Start),
-                minus: is_button_pressed(Button::Select),
-                star: false, // can't access
-                heart: is_button_pressed(Button::Mode),
-            };
+        let pads: Vec<(Uuid, ControllerState)> = self
+            .gilrs
+            .gamepads()
+            .map(|(_id, gamepad)| {
+                let axis = |a| gamepad.axis_data(a).map_or(0.0, |d| d.value());
+                let button_value = |b| gamepad.button_data(b).map_or(0.0, |d| d.value());
+                let is_button_pressed = |b| gamepad.button_data(b).is_some_and(|d| d.is_pressed());
+                let axes = Axes {
+                    left_stick: [axis(Axis::LeftStickX), axis(Axis::LeftStickY)],
+                    right_stick: [axis(Axis::RightStickX), axis(Axis::RightStickY)],
+                    lt: button_value(Button::LeftTrigger2),
+                    rt: button_value(Button::RightTrigger2),
+                };
+                // The D pad is derived from the left stick.
+                let buttons = Buttons {
+                    a: is_button_pressed(Button::East),
+                    b: is_button_pressed(Button::South),
+                    x: is_button_pressed(Button::North),
+                    y: is_button_pressed(Button::West),
+                    l: is_button_pressed(Button::LeftTrigger),
+                    r: is_button_pressed(Button::RightTrigger),
+                    lt: is_button_pressed(Button::LeftTrigger2),
+                    rt: is_button_pressed(Button::RightTrigger2),
+                    plus: is_button_pressed(Button::Start),
+                    minus: is_button_pressed(Button::Select),
+                    star: false, // can't access
+                    heart: is_button_pressed(Button::Mode),
+                    ..Buttons::default()
+                };
+                (Uuid(gamepad.uuid()), ControllerState { buttons, axes })
+            })
+            .collect();
+
+        let inputs = self.controllers.update(&pads);
+        if self.controllers.take_unsaved()
+            && let Some(dir) = &self.storage_dir
+            && let Err(e) = self.controllers.map.save(dir)
+        {
+            eprintln!("Error saving controller assignments: {e}");
+        }
+        inputs
+    }
 
-            *match gamepad.uuid() {
-                BLUE_CONTROLLER_UUID => &mut blue,
-                GREEN_CONTROLLER_UUID => &mut green,
-                _ => &mut green, // fallback for unrecognized controller
-            } = Some(current_button_states);
+    pub fn update(&mut self, inputs: FrameInputs) -> ShellFrameOutput {
+        let mut output = self.step(inputs);
+        if output.new_brightness.is_none() {
+            output.new_brightness = self.step_brightness();
         }
+        self.step_sound();
+        self.step_toasts();
+        output
+    }
 
-        (blue, green)
+    /// Starts the sound effects and music of the current activity and renders
+    /// the audio for this frame.
+    fn step_sound(&mut self) {
+        let activity = &mut self.activities[self.current_activity];
+        let sounds = activity.take_sounds();
+        if self.asleep || self.idle {
+            self.synth.stop();
+        } else {
+            for sound in sounds {
+                self.synth.play(sound);
+            }
+            let running = !self.in_menu && self.menu_animation.is_none();
+            self.synth.set_music(activity.music().filter(|_| running));
+        }
+        self.synth
+            .render_frame(crate::sound::volume_gain(self.volume()));
     }
 
-    pub fn update(&mut self, blue: Option<Buttons>, green: Option<Buttons>) -> ShellFrameOutput {
-        let prev_blue = std::mem::replace(&mut self.last_input_blue, blue);
-        let prev_green = std::mem::replace(&mut self.last_input_green, green);
+    /// Posts notifications from the current activity and draws the
+    /// notifications over the frame.
+    fn step_toasts(&mut self) {
+        for text in self.activities[self.current_activity].take_toasts() {
+            self.notify(text);
+        }
+        self.toasts.step();
+        if !self.asleep {
+            self.toasts
+                .draw(&mut FrameBufferRect::new(&mut self.frame_buffer));
+        }
+    }
 
-        let full_input = FullInput {
-            blue: blue.map(|current| {
-                let previous = prev_blue.unwrap_or_default();
-                ControllerInput { current, previous }
-            }),
-            green: green.map(|current| {
-                let previous = prev_green.unwrap_or_default();
-                ControllerInput { current, previous }
-            }),
-        };
+    fn step(&mut self, mut inputs: FrameInputs) -> ShellFrameOutput {
+        if let Some(remote) = &mut self.remote {
+            let remote_inputs = remote.poll(Instant::now());
+            for (input, remote_input) in std::iter::zip(&mut inputs, remote_inputs) {
+                *input = match (*input, remote_input) {
+                    (Some(a), Some(b)) => Some(a.merge(b)),
+                    (a, b) => a.or(b),
+                };
+            }
+        }
+        if let Some(replay) = &mut self.replay {
+            match replay.next_frame() {
+                Some((replay_inputs, markers)) => {
+                    for marker in markers {
+                        eprintln!("Replay marker on frame {}: {}", marker.frame, marker.label);
+                    }
+                    inputs = replay_inputs;
+                }
+                None => self.replay = None,
+            }
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.push_frame(inputs);
+        }
+        self.frame += 1;
+        self.autosave_settings();
+        if self
+            .frame
+            .is_multiple_of((ACTIVITY_SAVE_INTERVAL * FPS as f32) as u64)
+        {
+            self.save_activity_state(self.current_activity);
+        }
+        // Check the clock once per second.
+        if (self.frame - 1).is_multiple_of(FPS as u64) && self.scheduler.update() {
+            self.schedule_changed();
+        }
+
+        let prev_inputs = std::mem::replace(&mut self.last_inputs, inputs);
+
+        let full_input = FullInput::new(inputs, prev_inputs);
 
-        if blue.is_none() && green.is_none() {
+        if self.scheduler.state().sleep {
+            if !std::mem::replace(&mut self.asleep, true) {
+                self.save_activity_state(self.current_activity);
+                self.in_menu = false;
+                self.menu_animation = None;
+            }
             self.frame_buffer.as_flattened_mut().fill(BLACK);
-            self.current_activity = 0;
-            self.in_menu = false;
-            self.menu_animation = None;
+            return ShellFrameOutput::default();
+        }
+        self.asleep = false;
+
+        // Controllers that are connected on the first frame were connected
+        // before the shell started.
+        if self.frame > 1 {
+            for player in Player::ALL {
+                let color = self.controllers.map.colors.get(player);
+                match (inputs[player as usize], prev_inputs[player as usize]) {
+                    (Some(_), None) => self.toasts.post("joined", color),
+                    (None, Some(_)) => self.toasts.post("left", color),
+                    _ => (),
+                }
+            }
+        }
+
+        let connected =
+            std::iter::zip(&inputs, &prev_inputs).any(|(a, b)| a.is_some() && b.is_none());
+        // Ignore stick drift, even in inputs that didn't come through
+        // `Controllers` and so haven't had the deadzones applied.
+        let held = inputs.iter().flatten().any(|s| {
+            s.buttons != Buttons::default()
+                || s.axes.with_deadzones(Deadzones::default()) != Axes::default()
+        });
+        if connected || held {
+            self.last_input_frame = self.frame;
+        }
+        let timed_out = (self.attract_config.idle_frames())
+            .is_some_and(|idle_frames| self.frame >= self.last_input_frame + idle_frames);
+        if inputs.iter().all(Option::is_none) || timed_out {
+            self.step_idle(full_input);
+            return ShellFrameOutput::default();
+        }
+        if std::mem::take(&mut self.idle) {
+            // Wake up without passing on the input that woke us.
+            self.attract = None;
+            let mut fb = FrameBufferRect::new(&mut self.frame_buffer);
+            fb.fill(BLACK);
+            self.activities[self.current_activity].draw(&mut fb);
             return ShellFrameOutput::default();
         }
 
@@ -170,16 +679,13 @@ impl Shell {
         step_opt_animation(&mut self.menu_animation);
         step_opt_animation(&mut self.activity_reset_animation);
 
-        if self.in_menu {
-            let activity_count = self.activities.len();
+        if self.in_menu && self.setting_cursor.is_none() {
+            let player_count = inputs.iter().flatten().count();
             if pressed_keys.left {
-                self.current_activity =
-                    (self.current_activity + activity_count - 1) % activity_count;
-                self.activity_reset_animation = None;
+                self.select_next_activity(-1, player_count);
             }
             if pressed_keys.right {
-                self.current_activity = (self.current_activity + 1) % activity_count;
-                self.activity_reset_animation = None;
+                self.select_next_activity(1, player_count);
             }
         }
 
@@ -199,8 +705,109 @@ impl Shell {
         }
     }
 
+    /// Selects the next activity in the menu in direction `dir` (1 or -1),
+    /// skipping activities that need more than `player_count` players unless
+    /// they are pinned.
+    fn select_next_activity(&mut self, dir: isize, player_count: usize) {
+        let count = self.activities.len();
+        let next = (1..count)
+            .map(|i| (self.current_activity as isize + dir * i as isize).rem_euclid(count as isize))
+            .map(|i| i as usize)
+            .find(|&i| {
+                let info = self.activities[i].info();
+                info.min_players <= player_count || self.menu_config.is_pinned(info.id)
+            });
+        if let Some(next) = next {
+            self.save_activity_state(self.current_activity);
+            self.current_activity = next;
+            self.activity_reset_animation = None;
+            self.settings_changed();
+            self.synth.play(MENU_MOVE_SOUND);
+        }
+    }
+
+    /// Returns the activity to show when nobody has chosen one: the one
+    /// requested by the schedule, or else the one from the settings.
+    fn default_activity(&self) -> usize {
+        (self.scheduler.state().activity.as_ref())
+            .and_then(|id| self.activities.iter().position(|a| a.id() == id))
+            .unwrap_or_else(|| self.saved_activity())
+    }
+
+    /// Returns the activity selected in the saved settings, or the first one
+    /// if it isn't in the menu.
+    fn saved_activity(&self) -> usize {
+        (self.settings.activity.as_ref())
+            .and_then(|id| self.activities.iter().position(|a| a.id() == id))
+            .unwrap_or(0)
+    }
+
+    /// Clears overrides from the menu and switches to the activity requested
+    /// by the schedule.
+    fn schedule_changed(&mut self) {
+        self.brightness_override = None;
+        let activity = self.default_activity();
+        if activity != self.current_activity {
+            self.save_activity_state(self.current_activity);
+            self.current_activity = activity;
+            self.activity_reset_animation = None;
+        }
+    }
+
+    /// Returns the brightness that the schedule or menu asks for.
+    fn target_brightness(&self) -> u8 {
+        (self.brightness_override)
+            .or(self.scheduler.state().brightness)
+            .unwrap_or(self.settings.brightness)
+    }
+
+    /// Moves the brightness one step toward [`Self::target_brightness()`]
+    /// every [`BRIGHTNESS_RAMP_INTERVAL`] seconds, returning the new
+    /// brightness if it changed.
+    fn step_brightness(&mut self) -> Option<u8> {
+        let target = self.target_brightness();
+        let slider = &mut self.brightness_slider.slider;
+        let old = slider.get();
+        let ramp_frames = (BRIGHTNESS_RAMP_INTERVAL * FPS as f32) as u64;
+        if old == target || !self.frame.is_multiple_of(ramp_frames) {
+            return None;
+        }
+        slider.set_without_animation(if target > old { old + 1 } else { old - 1 });
+        Some(slider.get()).filter(|&new| new != old)
+    }
+
+    fn step_idle(&mut self, input: FullInput) {
+        if !self.idle {
+            self.idle = true;
+            self.save_activity_state(self.current_activity);
+            self.current_activity = self.default_activity();
+            self.in_menu = false;
+            self.menu_animation = None;
+            self.attract = match self.attract_config.mode {
+                IdleMode::Attract => Attract::new(&self.attract_config, &self.frame_buffer),
+                IdleMode::Blank | IdleMode::Keep => None,
+            };
+        }
+
+        let activity = &mut self.activities[self.current_activity];
+        let mut fb = FrameBufferRect::new(&mut self.frame_buffer);
+        if activity.stay_awake() || self.attract_config.mode == IdleMode::Keep {
+            activity.step(input);
+            fb.fill(BLACK);
+            activity.draw(&mut fb);
+        } else if let Some(attract) = &mut self.attract {
+            attract.step_and_draw(&mut self.frame_buffer);
+        } else {
+            fb.fill(BLACK);
+        }
+    }
+
     pub fn toggle_menu(&mut self) {
         self.in_menu ^= true;
+        if self.in_menu {
+            self.setting_cursor = None;
+            self.setting_scroll = 0.0;
+        }
         self.menu_animation = Some(match self.menu_animation {
             Some(a) => a.reverse(),
             None => MenuAnimation::new(),
@@ -209,18 +816,59 @@ impl Shell {
 
     pub fn step_and_draw_menu(&mut self, input: ControllerInput) -> ShellFrameOutput {
         let mut output = ShellFrameOutput::default();
+        let mut settings_changed = false;
 
-        #[allow(unused_mut)]
-        let mut blue = false;
-        #[allow(unused_mut)]
-        let mut green = false;
-        #[cfg(feature = "gilrs")]
-        {
-            for (_id, gamepad) in self.gilrs.gamepads() {
-                blue |= gamepad.uuid() == BLUE_CONTROLLER_UUID;
-                green |= gamepad.uuid() == GREEN_CONTROLLER_UUID;
+        // Players with input this frame, whether from a gamepad, a remote
+        // controller, a replay, or a script
+        let connected = self.last_inputs.map(|input| input.is_some());
+        // Blink free players while a controller is waiting to claim one.
+        let blink_frame = self.frame % (CLAIM_BLINK_DURATION * FPS as f32) as u64;
+        let claim_blink = self.controllers.unassigned() > 0
+            && blink_frame < (CLAIM_BLINK_DURATION / 2.0 * FPS as f32) as u64;
+        let colors = self.controllers.map.colors;
+
+        // Settings page
+        let pressed = input.pressed();
+        if self.in_menu && pressed.down {
+            match self.setting_cursor {
+                None => {
+                    let settings = self.activities[self.current_activity].settings();
+                    self.setting_rows = (settings.into_iter())
+                        .map(|setting| widgets::SettingRow::new(setting, ACTIVITY_SETTING_COLOR))
+                        .collect();
+                    self.setting_cursor = (!self.setting_rows.is_empty()).then_some(0);
+                }
+                Some(i) => self.setting_cursor = Some((i + 1).min(self.setting_rows.len() - 1)),
             }
         }
+        if self.in_menu && pressed.up {
+            self.setting_cursor = self.setting_cursor.and_then(|i| i.checked_sub(1));
+        }
+        for (i, row) in self.setting_rows.iter_mut().enumerate() {
+            row.selected = self.in_menu && self.setting_cursor == Some(i);
+            let old_value = row.setting.value.clone();
+            row.step(match row.selected {
+                true => [pressed.left, pressed.right],
+                false => [false; 2],
+            });
+            if row.setting.value != old_value {
+                let activity = &mut self.activities[self.current_activity];
+                activity.set_setting(i, row.setting.value.clone());
+                (self.settings).set_activity_setting(
+                    activity.id(),
+                    row.setting.name,
+                    &row.setting.value,
+                );
+                settings_changed = true;
+                self.synth.play(MENU_MOVE_SOUND);
+            }
+        }
+        let row_height = widgets::SettingRow::HEIGHT;
+        let target_scroll = self.setting_cursor.map_or(0, |i| (i + 1) * row_height) as f32;
+        let max_scroll_step = row_height as f32 / (SETTING_SCROLL_DURATION * FPS as f32);
+        self.setting_scroll +=
+            (target_scroll - self.setting_scroll).clamp(-max_scroll_step, max_scroll_step);
+        let scroll = self.setting_scroll.round() as isize;
 
         let mut fb = FrameBufferRect::new(&mut self.frame_buffer);
         let mut t = match self.menu_animation {
@@ -234,8 +882,9 @@ impl Shell {
 
         // Dim background
         fb.fill_with_fn(|_, color| color.darken(BACKGROUND_DIM * (1.0 - t)));
+        let mut fb = fb.centered([MENU_WIDTH, fb.height()]);
 
-        let mut upper = fb.with_offset([0, (fb.height() as f32 / 2.0 * -t) as isize]);
+        let mut upper = fb.with_offset([0, (fb.height() as f32 / 2.0 * -t) as isize - scroll]);
 
         // Activity menu image
         let darken = match self.activity_reset_animation {
@@ -252,19 +901,29 @@ impl Shell {
             });
 
         // Activity selection arrows
-        const NANOS_PER_SEC: f32 = 1_000_000_000 as f32;
-        let t2 = (self.first_frame.elapsed().as_nanos()
-            % (ARROW_WIGGLE_DURATION * NANOS_PER_SEC) as u128) as f32
-            / NANOS_PER_SEC;
-        let wiggle = t2 < ARROW_WIGGLE_DUTY_CYCLE;
+        let wiggle_frame = self.frame % (ARROW_WIGGLE_DURATION * FPS as f32) as u64;
+        let wiggle = wiggle_frame < (ARROW_WIGGLE_DUTY_CYCLE * FPS as f32) as u64;
         include_rgba_image!("menu/arrow_left.rgba")
             .draw(&mut upper.with_offset([1 - wiggle as isize, 3]));
         include_rgba_image!("menu/arrow_right.rgba")
             .draw(&mut upper.with_offset([26 + wiggle as isize, 3]));
 
-        let mut y = fb.height() as isize + (fb.height() as f32 / 2.0 * t) as isize;
+        let page_top = fb.height() as isize + (fb.height() as f32 / 2.0 * t) as isize - scroll;
+        let mut y = page_top;
         let slider_height = crate::widgets::LabeledSlider::HEIGHT;
 
+        // Settings page
+        if scroll > 0 {
+            for (i, row) in self.setting_rows.iter().enumerate() {
+                let row_y = page_top + (i * row_height) as isize;
+                row.draw(
+                    &mut fb
+                        .with_offset([0, row_y])
+                        .with_size([MENU_WIDTH, row_height]),
+                );
+            }
+        }
+
         // Brightness slider
         {
             let old_brightness = self.brightness_slider.slider.get();
@@ -273,64 +932,91 @@ impl Shell {
             self.brightness_slider.step(input);
 
             y -= slider_height as isize;
-            self.brightness_slider
-                .draw(&mut fb.with_offset([0, y]).with_size([WIDTH, slider_height]));
+            self.brightness_slider.draw(
+                &mut fb
+                    .with_offset([0, y])
+                    .with_size([MENU_WIDTH, slider_height]),
+            );
 
             let new_brightness = self.brightness_slider.slider.get();
             if new_brightness != old_brightness {
                 output.new_brightness = Some(new_brightness);
+                if self.scheduler.state().brightness.is_some() {
+                    self.brightness_override = Some(new_brightness);
+                } else {
+                    self.brightness_override = None;
+                    self.settings.brightness = new_brightness;
+                    settings_changed = true;
+                }
             }
         }
 
         // Volume slider
-        if SHOW_VOLUME {
+        {
+            let old_volume = self.volume_slider.slider.get();
+
             let input = [input.pressed().l, input.pressed().r];
             self.volume_slider.step(input);
 
+            if self.volume_slider.slider.get() != old_volume {
+                settings_changed = true;
+                self.synth.play(VOLUME_SOUND);
+            }
+
             y -= slider_height as isize;
-            self.volume_slider
-                .draw(&mut fb.with_offset([0, y]).with_size([WIDTH, slider_height]));
+            self.volume_slider.draw(
+                &mut fb
+                    .with_offset([0, y])
+                    .with_size([MENU_WIDTH, slider_height]),
+            );
         }
 
-        let controller_status_height = 9;
+        let controller_status_height = 16;
 
-        // Controller status
+        // Controller status, with two players on each row
         {
             y -= controller_status_height as isize;
             let mut fb = fb.with_offset([0, y]);
-            let mut fb = fb.with_size([WIDTH, controller_status_height]);
+            let mut fb = fb.with_size([MENU_WIDTH, controller_status_height]);
             fb.fill(CONTROLLER_STATUS_BACKGROUND);
-            let blue_darken = if blue {
-                0.0
-            } else {
-                DARKEN_DISCONNECTED_CONTROLLER
-            };
-            include_rgba_image!("menu/controller.rgba").draw_tinted(
-                &mut fb.with_offset([1, 1]),
-                BLUE_CONTROLLER_COLOR.darken(blue_darken),
-            );
-            include_rgba_image!("menu/controller_buttons.rgba")
-                .draw_tinted(&mut fb.with_offset([1, 1]), WHITE.darken(blue_darken));
-
-            let green_darken = if green {
-                0.0
-            } else {
-                DARKEN_DISCONNECTED_CONTROLLER
-            };
-            include_rgba_image!("menu/controller.rgba").draw_tinted(
-                &mut fb.with_offset([17, 1]),
-                GREEN_CONTROLLER_COLOR.darken(green_darken),
-            );
-            include_rgba_image!("menu/controller_buttons.rgba")
-                .draw_tinted(&mut fb.with_offset([17, 1]), WHITE.darken(green_darken));
+            for player in Player::ALL {
+                let i = player as usize;
+                let darken = if connected[i] || claim_blink {
+                    0.0
+                } else {
+                    DARKEN_DISCONNECTED_CONTROLLER
+                };
+                let mut fb = fb.with_offset([1 + 16 * (i % 2) as isize, 1 + 7 * (i / 2) as isize]);
+                include_rgba_image!("menu/controller.rgba")
+                    .draw_tinted(&mut fb, colors.get(player).darken(darken));
+                include_rgba_image!("menu/controller_buttons.rgba")
+                    .draw_tinted(&mut fb, WHITE.darken(darken));
+            }
         }
 
         // Border line
         y -= 1;
         fb.with_offset([0, y])
-            .with_size([WIDTH, 1])
+            .with_size([MENU_WIDTH, 1])
             .fill(Rgb::from_hex(0x666666));
 
+        // Estimated current, above the border line
+        if let Some(draw) = self.power_draw {
+            let mut text = format!("{:.1}a", draw.estimate / 1000.0);
+            let mut color = POWER_COLOR;
+            if draw.limited < draw.estimate {
+                text += &format!(" {:.1}a", draw.limited / 1000.0);
+                color = POWER_LIMITED_COLOR;
+            }
+            let mut fb = fb.with_offset([0, y - 7]);
+            fb.with_size([MENU_WIDTH, 7]).fill(BLACK);
+            text::draw(&text, &mut fb.with_offset([1, 1]), color);
+        }
+
+        if settings_changed {
+            self.settings_changed();
+        }
+
         output
     }
 }
@@ -0,0 +1,279 @@
+//! Golden-frame snapshot tests.
+//!
+//! Each snapshot runs an input script (see [`crate::input_script`]) and
+//! compares the final frame against a reference image in
+//! `crates/ledwall/snapshots/`. On failure, an image showing the expected
+//! frame, the actual frame, and the changed pixels is written to
+//! `target/snapshot-failures/`.
+//!
+//! To update the reference images after an intentional change, run the tests
+//! with `UPDATE_SNAPSHOTS=1`.
+
+use std::path::{Path, PathBuf};
+
+use crate::input_script::InputScript;
+use crate::activities::dmx::{Dmx, DmxConfig};
+use crate::activities::pixel_receiver::PixelReceiver;
+use crate::{
+    Activity, DEFAULT_SIZE, FrameBuffer, FrameBufferRect, FullInput, MAX_PLAYERS, Rgb, Shell,
+    activities,
+};
+
+const SNAPSHOTS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/snapshots");
+const FAILURES_DIR: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../target/snapshot-failures"
+);
+
+const SEED: u64 = 0;
+
+/// Color for changed pixels in the diff image.
+const DIFF_CHANGED: Rgb = Rgb::from_hex(0xFF0000);
+/// Amount to darken unchanged pixels in the diff image.
+const DIFF_DARKEN_UNCHANGED: f32 = 0.75;
+
+/// Snapshots for each activity, in the same order as
+/// [`activities::init_activities()`].
+const ACTIVITY_SNAPSHOTS: &[&[(&str, &str)]] = &[
+    // Rainbow
+    &[("rainbow", "30 .")],
+    // Tetris
+    &[
+        ("tetris_start", "1 ."),
+        (
+            "tetris_moves",
+            "
+            1 a
+            1 .
+            12 left
+            1 up
+            1 .
+            1 b
+            1 .
+            1 right
+            1 up
+            1 l
+            1 .
+            1 down
+            1 .
+            ",
+        ),
+        (
+            "tetris_hard_drop_animation",
+            "
+            1 .
+            1 up
+            10 .
+            ",
+        ),
+        ("tetris_big", "1 minus"),
+    ],
+    // Life
+    &[("life_empty", "1 ."), ("life_random", "1 y\n30 .")],
+    // Flat Hypercube
+    &[
+        ("flat_hypercube_solved", "1 ."),
+        ("flat_hypercube_grip", "1 right\n1 .\n1 up\n1 ."),
+        (
+            "flat_hypercube_turn_animation",
+            "1 right\n1 .\n1 up\n1 .\n1 a\n30 .",
+        ),
+        ("flat_hypercube_turn", "1 right\n1 .\n1 up\n1 .\n1 a\n90 ."),
+        ("flat_hypercube_filters", "1 plus+right"),
+    ],
+    // Pixel Receiver
+    &[("pixel_receiver_waiting", "60 .")],
+    // DMX
+    &[("dmx_waiting", "1 .")],
+];
+
+/// Snapshots of the whole shell, including the menu.
+const SHELL_SNAPSHOTS: &[(&str, &str)] = &[
+    ("shell_menu", "60 ."),
+    ("shell_menu_arrow_wiggle", "10 ."),
+    ("shell_menu_next_activity", "1 .\n1 right\n60 ."),
+    ("shell_menu_both_controllers", "60 . ."),
+    ("shell_menu_closing", "1 .\n1 a\n15 ."),
+    ("shell_activity", "1 .\n1 a\n60 ."),
+    ("shell_reset_animation", "1 .\n1 x\n30 ."),
+    ("shell_disconnected", "60 .\n1 - -"),
+    (
+        "shell_settings_page",
+        "1 .\n1 right\n1 .\n1 right\n1 .\n1 down\n1 .\n1 down\n1 .\n1 left\n60 .",
+    ),
+    ("shell_toast_joined", "1 .\n1 a\n30 .\n20 . ."),
+];
+
+/// Size of the frame for [`WIDE_SHELL_SNAPSHOTS`], as on a wall of two panels
+/// side by side.
+const WIDE_SIZE: [usize; 2] = [64, 64];
+
+/// Snapshots of the shell on a wall of [`WIDE_SIZE`].
+const WIDE_SHELL_SNAPSHOTS: &[(&str, &str)] = &[
+    ("wide_shell_menu", "60 ."),
+    ("wide_shell_activity", "1 .\n1 a\n60 ."),
+    ("wide_shell_toast_joined", "1 .\n1 a\n30 .\n20 . ."),
+];
+
+#[test]
+fn activity_snapshots() {
+    crate::rng::reseed(SEED);
+    assert_eq!(
+        activities::init_activities().len(),
+        ACTIVITY_SNAPSHOTS.len(),
+        "every activity must have snapshots",
+    );
+
+    let mut failures = vec![];
+    for (i, snapshots) in ACTIVITY_SNAPSHOTS.iter().enumerate() {
+        for &(name, script) in *snapshots {
+            crate::rng::reseed(SEED);
+            let mut activity = new_activity(i);
+
+            let script = InputScript::parse(script).expect("error parsing input script");
+            for frame in 0..script.len() {
+                let previous = match frame.checked_sub(1) {
+                    Some(prev_frame) => script.get(prev_frame),
+                    None => [None; MAX_PLAYERS],
+                };
+                activity.step(FullInput::new(script.get(frame), previous));
+            }
+
+            let mut frame_buffer = FrameBuffer::new(DEFAULT_SIZE);
+            activity.draw(&mut FrameBufferRect::new(&mut frame_buffer));
+            failures.extend(check_snapshot(name, &frame_buffer).err());
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// Constructs the `i`th activity, without listening on the network so that
+/// nothing else on the network can affect the snapshot.
+fn new_activity(i: usize) -> Box<dyn Activity> {
+    let activity = activities::init_activities().swap_remove(i);
+    match activity.id() {
+        "pixel_receiver" => Box::new(PixelReceiver::new(None, None)),
+        "dmx" => Box::new(Dmx::new(DmxConfig {
+            sacn_addr: "off".to_string(),
+            artnet_addr: "off".to_string(),
+            ..DmxConfig::default()
+        })),
+        _ => activity,
+    }
+}
+
+#[test]
+fn shell_snapshots() {
+    let mut failures = vec![];
+    let wide_snapshots = WIDE_SHELL_SNAPSHOTS.iter().map(|s| (s, WIDE_SIZE));
+    let snapshots = SHELL_SNAPSHOTS.iter().map(|s| (s, DEFAULT_SIZE));
+    for (&(name, script), size) in snapshots.chain(wide_snapshots) {
+        let mut shell = Shell::with_seed(SEED).with_size(size);
+        let script = InputScript::parse(script).expect("error parsing input script");
+        for frame in 0..script.len() {
+            shell.update(script.get(frame));
+        }
+        failures.extend(check_snapshot(name, shell.frame_buffer()).err());
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// The shell shouldn't panic on tiny walls, including on the settings page.
+#[test]
+fn tiny_shell() {
+    for size in [[1, 1], [1, 64], [32, 4]] {
+        for &(_, script) in SHELL_SNAPSHOTS {
+            let mut shell = Shell::with_seed(SEED).with_size(size);
+            let script = InputScript::parse(script).expect("error parsing input script");
+            for frame in 0..script.len() {
+                shell.update(script.get(frame));
+            }
+        }
+    }
+}
+
+/// Compares `frame_buffer` against the reference image for the snapshot and
+/// returns an error message if they differ.
+fn check_snapshot(name: &str, frame_buffer: &FrameBuffer) -> Result<(), String> {
+    let reference_path = Path::new(SNAPSHOTS_DIR).join(format!("{name}.png"));
+    let actual = frame_buffer.as_flattened();
+    let [width, height] = frame_buffer.size();
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        save_png(&reference_path, width, height, actual);
+        return Ok(());
+    }
+
+    let expected = match image::open(&reference_path) {
+        Ok(img) => img.into_rgb8(),
+        Err(e) => {
+            let actual_path = failure_path(name, "actual");
+            save_png(&actual_path, width, height, actual);
+            return Err(format!(
+                "{name}: error loading reference image {reference_path:?} ({e}); \
+                 actual frame written to {actual_path:?}. \
+                 Run with `UPDATE_SNAPSHOTS=1` to create it.",
+            ));
+        }
+    };
+    if expected.dimensions() != (width as u32, height as u32) {
+        return Err(format!(
+            "{name}: reference image is {:?} but frame is {:?}",
+            expected.dimensions(),
+            (width, height),
+        ));
+    }
+    let expected: &[Rgb] = bytemuck::cast_slice(expected.as_raw());
+
+    let changed_pixels = std::iter::zip(expected, actual)
+        .filter(|(e, a)| e != a)
+        .count();
+    if changed_pixels == 0 {
+        return Ok(());
+    }
+
+    // Draw expected, actual, and diff side by side with a 1-pixel gap.
+    let diff_width = width * 3 + 2;
+    let mut diff = vec![Rgb::from_hex(0x333333); diff_width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let e = expected[y * width + x];
+            let a = actual[y * width + x];
+            let row = &mut diff[y * diff_width..];
+            row[x] = e;
+            row[width + 1 + x] = a;
+            row[(width + 1) * 2 + x] = if e == a {
+                a.darken(DIFF_DARKEN_UNCHANGED)
+            } else {
+                DIFF_CHANGED
+            };
+        }
+    }
+    let diff_path = failure_path(name, "diff");
+    save_png(&diff_path, diff_width, height, &diff);
+
+    Err(format!(
+        "{name}: {changed_pixels} pixels differ from reference image; \
+         see {diff_path:?} (expected | actual | diff)",
+    ))
+}
+
+fn failure_path(name: &str, suffix: &str) -> PathBuf {
+    Path::new(FAILURES_DIR).join(format!("{name}.{suffix}.png"))
+}
+
+fn save_png(path: &Path, width: usize, height: usize, pixels: &[Rgb]) {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).expect("error creating snapshot directory");
+    }
+    image::save_buffer(
+        path,
+        bytemuck::cast_slice(pixels),
+        width as u32,
+        height as u32,
+        image::ExtendedColorType::Rgb8,
+    )
+    .expect("error saving snapshot image");
+}
@@ -0,0 +1,397 @@
+//! Software synthesizer for chiptune-style sound effects and music.
+//!
+//! Activities describe sounds as short sequences of [`Note`]s and music as
+//! looping [`Tune`]s. The shell mixes them with [`Synth`] one frame at a time,
+//! and the frontend passes the samples to an [`AudioOutput`].
+//!
+//! [`AudioOutput`]: output::AudioOutput
+
+pub mod output;
+
+use crate::FPS;
+
+/// Number of samples per second.
+pub const SAMPLE_RATE: u32 = 48_000;
+/// Number of samples rendered for each frame.
+pub const SAMPLES_PER_FRAME: usize = SAMPLE_RATE as usize / FPS;
+
+/// Maximum number of sound effects that can play at once. Starting another
+/// one cuts off the oldest.
+const MAX_VOICES: usize = 8;
+/// Overall volume before the volume setting is applied, leaving headroom for
+/// several voices at once.
+const MASTER_VOLUME: f32 = 0.25;
+/// Number of seconds over which each note fades in and out, to avoid clicks.
+const EDGE_SECONDS: f32 = 0.002;
+/// Fraction of each note of a tune that is played before the next note, so
+/// that repeated notes are distinct.
+const TUNE_LEGATO: f32 = 0.9;
+
+/// Shape of an oscillator's waveform.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Wave {
+    Square,
+    /// Square wave with a 25% duty cycle, which sounds thinner.
+    Pulse,
+    Triangle,
+    Sawtooth,
+    /// Pseudorandom noise, whose pitch is how often it changes.
+    Noise,
+}
+
+/// Single note of a sound effect, which can slide in pitch and fade in
+/// volume.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Note {
+    pub wave: Wave,
+    /// Frequency in hertz at the start and end of the note.
+    pub hz: [f32; 2],
+    pub seconds: f32,
+    /// Volume from 0 to 1 at the start and end of the note.
+    pub volume: [f32; 2],
+}
+
+impl Note {
+    pub const fn new(wave: Wave, hz: f32, seconds: f32) -> Self {
+        Self {
+            wave,
+            hz: [hz, hz],
+            seconds,
+            volume: [1.0, 1.0],
+        }
+    }
+
+    /// Returns a silent note.
+    pub const fn rest(seconds: f32) -> Self {
+        Self {
+            volume: [0.0, 0.0],
+            ..Self::new(Wave::Square, 0.0, seconds)
+        }
+    }
+
+    /// Returns the note with its pitch sliding to `hz` by the end.
+    pub const fn slide(self, hz: f32) -> Self {
+        Self {
+            hz: [self.hz[0], hz],
+            ..self
+        }
+    }
+
+    /// Returns the note with its volume multiplied by `volume`.
+    pub const fn volume(self, volume: f32) -> Self {
+        Self {
+            volume: [self.volume[0] * volume, self.volume[1] * volume],
+            ..self
+        }
+    }
+
+    /// Returns the note fading to `volume` times its starting volume by the
+    /// end.
+    pub const fn fade(self, volume: f32) -> Self {
+        Self {
+            volume: [self.volume[0], self.volume[0] * volume],
+            ..self
+        }
+    }
+}
+
+/// Sound effect, played from start to end.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sound(pub &'static [Note]);
+
+/// Piece of music that loops until it is stopped.
+#[derive(Debug, PartialEq)]
+pub struct Tune {
+    /// Number of seconds per beat.
+    pub beat_seconds: f32,
+    /// Parts played at the same time, which should all have the same length.
+    pub parts: &'static [Part],
+}
+
+/// One part of a [`Tune`], which plays one note at a time.
+#[derive(Debug, PartialEq)]
+pub struct Part {
+    pub wave: Wave,
+    pub volume: f32,
+    /// MIDI note numbers (where 60 is middle C), or 0 for a rest, and their
+    /// lengths in beats.
+    pub notes: &'static [(u8, f32)],
+}
+
+/// Returns the frequency in hertz of a MIDI note number.
+pub fn midi_hz(key: u8) -> f32 {
+    440.0 * 2.0_f32.powf((key as f32 - 69.0) / 12.0)
+}
+
+/// Converts a volume setting on a scale from 0 to 20 into a gain.
+pub fn volume_gain(volume: u8) -> f32 {
+    let t = volume.min(20) as f32 / 20.0;
+    t * t
+}
+
+/// Mixes sound effects and music into samples.
+#[derive(Debug, Default)]
+pub struct Synth {
+    voices: Vec<Voice>,
+    music: Option<Music>,
+    /// Samples rendered by the last call to [`Synth::render_frame()`].
+    samples: Vec<f32>,
+}
+
+impl Synth {
+    /// Starts playing a sound effect.
+    pub fn play(&mut self, sound: Sound) {
+        if self.voices.len() >= MAX_VOICES {
+            self.voices.remove(0);
+        }
+        self.voices.push(Voice {
+            notes: sound.0,
+            index: 0,
+            elapsed: 0,
+            osc: Oscillator::default(),
+        });
+    }
+
+    /// Starts looping `tune` from the beginning, unless it is already
+    /// playing, or stops the music if `tune` is `None`.
+    pub fn set_music(&mut self, tune: Option<&'static Tune>) {
+        let current = self.music.as_ref().map(|m| m.tune);
+        if current.map(std::ptr::from_ref) == tune.map(std::ptr::from_ref) {
+            return;
+        }
+        self.music = tune.map(|tune| Music {
+            tune,
+            parts: tune.parts.iter().map(|_| Voice::default()).collect(),
+        });
+    }
+
+    /// Stops all sound effects and music.
+    pub fn stop(&mut self) {
+        self.voices.clear();
+        self.music = None;
+    }
+
+    /// Renders the next frame's worth of samples, multiplied by `gain`, and
+    /// returns them.
+    pub fn render_frame(&mut self, gain: f32) -> &[f32] {
+        self.samples.clear();
+        self.samples.resize(SAMPLES_PER_FRAME, 0.0);
+        for voice in &mut self.voices {
+            voice.render(&mut self.samples);
+        }
+        self.voices.retain(|voice| voice.index < voice.notes.len());
+        if let Some(music) = &mut self.music {
+            music.render(&mut self.samples);
+        }
+        for sample in &mut self.samples {
+            *sample = (*sample * MASTER_VOLUME * gain).clamp(-1.0, 1.0);
+        }
+        &self.samples
+    }
+
+    /// Returns the samples rendered by the last call to
+    /// [`Synth::render_frame()`].
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+}
+
+/// Sound effect in progress.
+#[derive(Debug, Default)]
+struct Voice {
+    notes: &'static [Note],
+    /// Index of the current note.
+    index: usize,
+    /// Number of samples played of the current note.
+    elapsed: u32,
+    osc: Oscillator,
+}
+
+impl Voice {
+    fn render(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            let Some(note) = self.notes.get(self.index) else {
+                return;
+            };
+            let len = (note.seconds * SAMPLE_RATE as f32) as u32;
+            if self.elapsed < len {
+                let t = self.elapsed as f32 / len as f32;
+                let hz = crate::mix(note.hz[0]..note.hz[1], t);
+                let volume =
+                    crate::mix(note.volume[0]..note.volume[1], t) * edge(self.elapsed, len);
+                *sample += self.osc.next(note.wave, hz) * volume;
+                self.elapsed += 1;
+            }
+            if self.elapsed >= len {
+                self.index += 1;
+                self.elapsed = 0;
+            }
+        }
+    }
+}
+
+/// Music in progress, with a [`Voice`] for each part of the tune whose notes
+/// are unused.
+#[derive(Debug)]
+struct Music {
+    tune: &'static Tune,
+    parts: Vec<Voice>,
+}
+
+impl Music {
+    fn render(&mut self, samples: &mut [f32]) {
+        let beat_samples = self.tune.beat_seconds * SAMPLE_RATE as f32;
+        for (part, voice) in std::iter::zip(self.tune.parts, &mut self.parts) {
+            if part.notes.is_empty() {
+                continue;
+            }
+            for sample in samples.iter_mut() {
+                let (key, beats) = part.notes[voice.index];
+                let len = (beats * beat_samples) as u32;
+                if voice.elapsed >= len {
+                    voice.index = (voice.index + 1) % part.notes.len();
+                    voice.elapsed = 0;
+                }
+                let played = (len as f32 * TUNE_LEGATO) as u32;
+                if key != 0 && voice.elapsed < played {
+                    let t = voice.elapsed as f32 / played as f32;
+                    let volume = part.volume * (1.0 - t / 2.0) * edge(voice.elapsed, played);
+                    *sample += voice.osc.next(part.wave, midi_hz(key)) * volume;
+                }
+                voice.elapsed += 1;
+            }
+        }
+    }
+}
+
+/// Returns the volume envelope that fades in and out at the edges of a note
+/// `len` samples long.
+fn edge(elapsed: u32, len: u32) -> f32 {
+    let edge_samples = EDGE_SECONDS * SAMPLE_RATE as f32;
+    let distance = elapsed.min(len - elapsed) as f32;
+    (distance / edge_samples).min(1.0)
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Oscillator {
+    /// Position within the current cycle, from 0 to 1.
+    phase: f32,
+    /// State of the linear-feedback shift register for noise.
+    lfsr: u16,
+}
+
+impl Default for Oscillator {
+    fn default() -> Self {
+        Self {
+            phase: 0.0,
+            lfsr: 1,
+        }
+    }
+}
+
+impl Oscillator {
+    fn next(&mut self, wave: Wave, hz: f32) -> f32 {
+        let phase = self.phase;
+        self.phase += hz / SAMPLE_RATE as f32;
+        if self.phase >= 1.0 {
+            self.phase = self.phase.fract();
+            let bit = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+            self.lfsr = (self.lfsr >> 1) | (bit << 14);
+        }
+        match wave {
+            Wave::Square => square(phase, 0.5),
+            Wave::Pulse => square(phase, 0.25),
+            Wave::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Wave::Sawtooth => 2.0 * phase - 1.0,
+            Wave::Noise => square((self.lfsr & 1) as f32, 0.5),
+        }
+    }
+}
+
+fn square(phase: f32, duty: f32) -> f32 {
+    if phase < duty { 1.0 } else { -1.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEEP: Sound = Sound(&[
+        Note::new(Wave::Square, 440.0, 0.05),
+        Note::rest(0.05),
+        Note::new(Wave::Triangle, 880.0, 0.05)
+            .slide(440.0)
+            .fade(0.0),
+    ]);
+
+    const TUNE: Tune = Tune {
+        beat_seconds: 0.1,
+        parts: &[Part {
+            wave: Wave::Pulse,
+            volume: 0.5,
+            notes: &[(69, 1.0), (0, 1.0)],
+        }],
+    };
+
+    fn is_playing(synth: &Synth) -> bool {
+        !synth.voices.is_empty() || synth.music.is_some()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |max, s| s.abs().max(max))
+    }
+
+    #[test]
+    fn sound_effects() {
+        let mut synth = Synth::default();
+        assert_eq!(peak(synth.render_frame(1.0)), 0.0);
+        assert!(!is_playing(&synth));
+
+        synth.play(BEEP);
+        let mut frames = vec![];
+        while is_playing(&synth) {
+            frames.push(peak(synth.render_frame(1.0)));
+        }
+        // 0.15 seconds, rounded up to a whole frame
+        assert_eq!(frames.len(), (0.15 * FPS as f32).ceil() as usize);
+        assert!(frames[0] > 0.0 && frames[0] <= MASTER_VOLUME);
+        assert_eq!(frames[frames.len() / 2], 0.0, "rest should be silent");
+
+        synth.play(BEEP);
+        assert_eq!(peak(synth.render_frame(0.0)), 0.0, "volume should be 0");
+
+        for _ in 0..MAX_VOICES * 2 {
+            synth.play(BEEP);
+        }
+        assert_eq!(synth.voices.len(), MAX_VOICES);
+        assert!(peak(synth.render_frame(1.0)) <= 1.0);
+    }
+
+    #[test]
+    fn music() {
+        let mut synth = Synth::default();
+        synth.set_music(Some(&TUNE));
+        let beat_frames = (TUNE.beat_seconds * FPS as f32) as usize;
+        let mut loud = vec![];
+        for _ in 0..beat_frames * 3 + 1 {
+            loud.push(peak(synth.render_frame(1.0)) > 0.0);
+        }
+        assert!(loud[0] && !loud[beat_frames + 1]);
+        assert!(loud[2 * beat_frames + 1], "tune should loop");
+        assert!(!loud[3 * beat_frames]);
+
+        // Setting the same tune again doesn't restart it.
+        synth.set_music(Some(&TUNE));
+        assert_eq!(peak(synth.render_frame(1.0)), 0.0);
+        synth.set_music(None);
+        assert!(!is_playing(&synth));
+    }
+
+    #[test]
+    fn pitch() {
+        assert_eq!(midi_hz(69), 440.0);
+        assert!((midi_hz(60) - 261.63).abs() < 0.01);
+        assert_eq!(volume_gain(0), 0.0);
+        assert_eq!(volume_gain(20), 1.0);
+    }
+}
@@ -0,0 +1,220 @@
+//! Destinations for the synthesizer's samples.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+
+use super::SAMPLE_RATE;
+
+/// Environment variable that chooses the audio output (see [`open()`]).
+#[cfg_attr(feature = "headless", allow(dead_code))] // unused by headless frontend
+pub const AUDIO_ENV_VAR: &str = "LEDWALL_AUDIO";
+
+/// Command that plays raw audio, used if [`AUDIO_ENV_VAR`] is not set.
+#[cfg_attr(feature = "headless", allow(dead_code))] // unused by headless frontend
+const DEFAULT_COMMAND: &str = "aplay -q -t raw -f S16_LE -c 1 -r 48000 -B 50000";
+
+/// Number of frames of samples that [`PipeOutput`] buffers before dropping
+/// samples, which bounds the latency if the player falls behind.
+const PIPE_BUFFER_FRAMES: usize = 4;
+
+/// Destination for mono samples between -1 and 1 at [`SAMPLE_RATE`].
+///
+/// Dropping the output finishes writing.
+pub trait AudioOutput {
+    fn write(&mut self, samples: &[f32]) -> io::Result<()>;
+}
+
+/// Opens the audio output chosen by `$LEDWALL_AUDIO`, or returns `None` if it
+/// is `off` or could not be opened.
+#[cfg_attr(feature = "headless", allow(dead_code))] // unused by headless frontend
+pub fn output_from_env() -> Option<Box<dyn AudioOutput>> {
+    let dest = std::env::var(AUDIO_ENV_VAR).unwrap_or_else(|_| DEFAULT_COMMAND.to_owned());
+    open(&dest)
+        .inspect_err(|e| eprintln!("Error opening audio output {dest:?}: {e}"))
+        .ok()
+        .flatten()
+}
+
+/// Opens an audio output, which is `off` (in which case this returns
+/// `None`), a file name ending in `.wav`, or a command that plays raw audio
+/// from its standard input.
+pub fn open(dest: &str) -> io::Result<Option<Box<dyn AudioOutput>>> {
+    Ok(Some(match dest {
+        "" | "off" => return Ok(None),
+        path if path.ends_with(".wav") => Box::new(WavOutput::create(Path::new(path))?),
+        command => Box::new(PipeOutput::spawn(command)?),
+    }))
+}
+
+fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+/// Writes samples to a 16-bit mono WAV file. The header is completed when the
+/// output is dropped.
+#[derive(Debug)]
+pub struct WavOutput<W: Write + Seek> {
+    writer: W,
+    sample_count: u32,
+}
+
+impl WavOutput<BufWriter<File>> {
+    /// Creates a WAV file at `path`, replacing any existing file.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write + Seek> WavOutput<W> {
+    /// Writes a WAV header to `writer`, whose sizes are filled in when the
+    /// output is dropped.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        write_header(&mut writer, 0)?;
+        Ok(Self {
+            writer,
+            sample_count: 0,
+        })
+    }
+}
+
+impl<W: Write + Seek> AudioOutput for WavOutput<W> {
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for &sample in samples {
+            self.writer.write_all(&to_i16(sample).to_le_bytes())?;
+        }
+        self.sample_count += samples.len() as u32;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Drop for WavOutput<W> {
+    fn drop(&mut self) {
+        let result = (self.writer.seek(SeekFrom::Start(0)))
+            .and_then(|_| write_header(&mut self.writer, self.sample_count))
+            .and_then(|()| self.writer.flush());
+        if let Err(e) = result {
+            eprintln!("Error finishing WAV file: {e}");
+        }
+    }
+}
+
+fn write_header(w: &mut impl Write, sample_count: u32) -> io::Result<()> {
+    let data_len = sample_count * 2;
+    w.write_all(b"RIFF")?;
+    w.write_all(&(36 + data_len).to_le_bytes())?;
+    w.write_all(b"WAVEfmt ")?;
+    w.write_all(&16_u32.to_le_bytes())?; // size of format chunk
+    w.write_all(&1_u16.to_le_bytes())?; // PCM
+    w.write_all(&1_u16.to_le_bytes())?; // channels
+    w.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    w.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?; // bytes per second
+    w.write_all(&2_u16.to_le_bytes())?; // bytes per sample
+    w.write_all(&16_u16.to_le_bytes())?; // bits per sample
+    w.write_all(b"data")?;
+    w.write_all(&data_len.to_le_bytes())
+}
+
+/// Pipes 16-bit little-endian samples to a command, such as `aplay`, that
+/// plays them.
+///
+/// Samples are written from a separate thread so that a slow player doesn't
+/// slow down the frame loop. If the player falls behind, samples are dropped.
+#[derive(Debug)]
+pub struct PipeOutput {
+    child: Child,
+    sender: Option<mpsc::SyncSender<Vec<u8>>>,
+}
+
+impl PipeOutput {
+    /// Runs `command`, split at whitespace, with samples on its standard
+    /// input.
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut args = command.split_whitespace();
+        let program = args
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(PIPE_BUFFER_FRAMES);
+        std::thread::spawn(move || {
+            for bytes in receiver {
+                if let Err(e) = stdin.write_all(&bytes) {
+                    eprintln!("Error writing audio: {e}");
+                    return;
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            sender: Some(sender),
+        })
+    }
+}
+
+impl AudioOutput for PipeOutput {
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        let Some(sender) = &self.sender else {
+            return Ok(());
+        };
+        let bytes = samples
+            .iter()
+            .flat_map(|&s| to_i16(s).to_le_bytes())
+            .collect();
+        match sender.try_send(bytes) {
+            Ok(()) | Err(mpsc::TrySendError::Full(_)) => Ok(()),
+            Err(mpsc::TrySendError::Disconnected(_)) => {
+                self.sender = None;
+                Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "audio player exited",
+                ))
+            }
+        }
+    }
+}
+
+impl Drop for PipeOutput {
+    fn drop(&mut self) {
+        // Closing the channel closes the player's input, so it exits after
+        // playing what it has.
+        self.sender = None;
+        if let Err(e) = self.child.wait() {
+            eprintln!("Error waiting for audio player: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn wav() {
+        let mut cursor = Cursor::new(vec![]);
+        let mut output = WavOutput::new(&mut cursor).unwrap();
+        output.write(&[0.0, 1.0]).unwrap();
+        output.write(&[-1.0, 2.0, 0.5]).unwrap();
+        drop(output);
+        let bytes = cursor.into_inner();
+
+        assert_eq!(bytes.len(), 44 + 5 * 2);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(bytes[4..8], (36 + 10_u32).to_le_bytes());
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(bytes[24..28], SAMPLE_RATE.to_le_bytes());
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(bytes[40..44], 10_u32.to_le_bytes());
+        let samples: Vec<i16> = (bytes[44..].chunks(2))
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(samples, [0, i16::MAX, -i16::MAX, i16::MAX, i16::MAX / 2]);
+    }
+}
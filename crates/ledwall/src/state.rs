@@ -0,0 +1,295 @@
+//! Saved activity state.
+//!
+//! Each activity's state is a byte blob produced by
+//! [`Activity::save_state()`] and stored in `activities/<id>.state` within
+//! [`storage::dir()`]. The blob starts with a `u16` version chosen by the
+//! activity, followed by activity-specific data. All integers are
+//! little-endian.
+//!
+//! [`Activity::save_state()`]: crate::Activity::save_state
+//! [`storage::dir()`]: crate::storage::dir
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::storage;
+
+/// Name of the directory within [`storage::dir()`] that contains saved
+/// activity state.
+pub const DIR_NAME: &str = "activities";
+
+/// Returns the path of the saved state for the activity with ID `id`.
+pub fn path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(DIR_NAME).join(format!("{id}.state"))
+}
+
+/// Loads saved state for the activity with ID `id`, returning `None` if there
+/// is no saved state.
+pub fn load(dir: &Path, id: &str) -> io::Result<Option<Vec<u8>>> {
+    match std::fs::read(path(dir, id)) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Saves state for the activity with ID `id`, or deletes the saved state if
+/// `data` is `None`.
+pub fn save(dir: &Path, id: &str, data: Option<&[u8]>) -> io::Result<()> {
+    let path = path(dir, id);
+    match data {
+        Some(data) => storage::write_atomic(&path, data),
+        None => match std::fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Encoder for saved state.
+#[derive(Debug, Clone)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    /// Constructs a writer for state with the given format version.
+    pub fn new(version: u16) -> Self {
+        let mut ret = Self { buf: vec![] };
+        ret.u16(version);
+        ret
+    }
+
+    /// Returns the encoded state.
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn bool(&mut self, b: bool) {
+        self.u8(b as u8);
+    }
+    pub fn u8(&mut self, x: u8) {
+        self.buf.push(x);
+    }
+    pub fn i8(&mut self, x: i8) {
+        self.bytes(&x.to_le_bytes());
+    }
+    pub fn u16(&mut self, x: u16) {
+        self.bytes(&x.to_le_bytes());
+    }
+    pub fn u32(&mut self, x: u32) {
+        self.bytes(&x.to_le_bytes());
+    }
+    pub fn u64(&mut self, x: u64) {
+        self.bytes(&x.to_le_bytes());
+    }
+    /// Writes the length of a sequence as a `u32`.
+    pub fn len(&mut self, len: usize) {
+        self.u32(len.try_into().expect("sequence too long"));
+    }
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+}
+
+/// Decoder for saved state.
+#[derive(Debug, Clone)]
+pub struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    /// Constructs a reader for `data`, returning an error if it was not saved
+    /// with the given format version.
+    pub fn new(data: &'a [u8], version: u16) -> io::Result<Self> {
+        let mut ret = Self { data };
+        let saved_version = ret.u16()?;
+        if saved_version != version {
+            return Err(invalid_data(format!(
+                "unsupported state version {saved_version}"
+            )));
+        }
+        Ok(ret)
+    }
+
+    /// Returns an error if there is unread data.
+    pub fn finish(self) -> io::Result<()> {
+        match self.data.is_empty() {
+            true => Ok(()),
+            false => Err(invalid_data("unexpected data at end of state")),
+        }
+    }
+
+    pub fn bool(&mut self) -> io::Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(invalid_data(format!("invalid bool {other}"))),
+        }
+    }
+    pub fn u8(&mut self) -> io::Result<u8> {
+        Ok(u8::from_le_bytes(self.array()?))
+    }
+    pub fn i8(&mut self) -> io::Result<i8> {
+        Ok(i8::from_le_bytes(self.array()?))
+    }
+    pub fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+    pub fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+    pub fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+    /// Reads the length of a sequence written by [`StateWriter::len()`].
+    pub fn len(&mut self) -> io::Result<usize> {
+        let len = self.u32()? as usize;
+        // Every element takes at least one byte, so this prevents huge
+        // allocations from corrupted data.
+        if len > self.data.len() {
+            return Err(invalid_data("sequence length exceeds state size"));
+        }
+        Ok(len)
+    }
+    pub fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if len > self.data.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+    pub fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into().expect("wrong length"))
+    }
+}
+
+pub fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input_script::InputScript;
+    use crate::{Activity, FullInput, MAX_PLAYERS, activities};
+
+    /// Input scripts to run on activities before saving their state.
+    const SCRIPTS: &[(&str, &str)] = &[
+        (
+            "tetris",
+            "1 a\n1 .\n12 left\n1 up\n1 .\n1 l\n1 .\n1 down\n1 right",
+        ),
+        ("life", "1 y\n30 ."),
+        (
+            "flat_hypercube",
+            "1 right\n1 .\n1 up\n1 .\n1 a\n1 .\n1 l\n1 .\n1 plus+right\n1 plus\n1 plus+up\n1 .",
+        ),
+    ];
+
+    fn activity(id: &str) -> Box<dyn Activity> {
+        activities::init_activities()
+            .into_iter()
+            .find(|a| a.id() == id)
+            .expect("no activity with ID")
+    }
+
+    fn run(activity: &mut dyn Activity, script: &InputScript, frames: std::ops::Range<usize>) {
+        for frame in frames {
+            let previous = match frame.checked_sub(1) {
+                Some(prev_frame) => script.get(prev_frame),
+                None => [None; MAX_PLAYERS],
+            };
+            activity.step(FullInput::new(script.get(frame), previous));
+        }
+    }
+
+    #[test]
+    fn activity_state_roundtrip() {
+        for &(id, script) in SCRIPTS {
+            crate::rng::reseed(0);
+            let script = InputScript::parse(script).expect("error parsing input script");
+            let mut original = activity(id);
+            run(&mut *original, &script, 0..script.len());
+            let state = original.save_state().expect("no state saved");
+
+            let mut restored = activity(id);
+            restored.load_state(&state).expect("error loading state");
+            assert_eq!(restored.save_state().as_ref(), Some(&state), "{id}");
+
+            // Both should behave the same afterwards.
+            let end = script.len() + 60;
+            run(&mut *original, &script, script.len()..end);
+            run(&mut *restored, &script, script.len()..end);
+            assert_eq!(restored.save_state(), original.save_state(), "{id}");
+        }
+    }
+
+    #[test]
+    fn invalid_activity_state() {
+        for &(id, _) in SCRIPTS {
+            let state = activity(id).save_state().expect("no state saved");
+
+            let mut wrong_version = state.clone();
+            wrong_version[0] ^= 0xFF;
+            assert!(activity(id).load_state(&wrong_version).is_err(), "{id}");
+
+            for len in 0..state.len() {
+                assert!(activity(id).load_state(&state[..len]).is_err(), "{id}");
+            }
+
+            let mut extra = state.clone();
+            extra.push(0);
+            assert!(activity(id).load_state(&extra).is_err(), "{id}");
+        }
+    }
+
+    #[test]
+    fn flat_hypercube_running_timer() {
+        // Scramble, then turn twice to start the timer.
+        let script = "1 lt+y\n1 .\n1 right\n1 .\n1 up\n1 .\n1 a\n1 .\n1 a\n30 .";
+        let script = InputScript::parse(script).expect("error parsing input script");
+        let mut activity = activity("flat_hypercube");
+        run(&mut *activity, &script, 0..script.len());
+        let state = activity.save_state();
+
+        // The state doesn't change while the timer runs, so it isn't saved
+        // again and again.
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        run(&mut *activity, &script, script.len()..script.len() + 60);
+        assert_eq!(activity.save_state(), state);
+    }
+
+    #[test]
+    fn tetris_state_outside_playfield() {
+        // A new game clears row 0 on its first step, so its state ends with
+        // the falling piece position (2 bytes), its last move frame (8), the
+        // held piece (1), whether hold was used (1), the number of rows to
+        // clear (4), row 0 (1), and whether the game is over (1).
+        let state = activity("tetris").save_state().expect("no state saved");
+        let n = state.len();
+        assert_eq!(state[n - 6..n - 1], [1, 0, 0, 0, 0]);
+
+        let corrupt = |i: usize, byte: i8| {
+            let mut data = state.clone();
+            data[i] = byte as u8;
+            activity("tetris").load_state(&data)
+        };
+        assert!(corrupt(n - 2, 100).is_err(), "row above playfield");
+        assert!(corrupt(n - 2, -1).is_err(), "row below playfield");
+        assert!(corrupt(n - 18, 100).is_err(), "piece right of playfield");
+        assert!(corrupt(n - 17, -5).is_err(), "piece below playfield");
+        let mut corner = state.clone();
+        corner[n - 18..n - 16].copy_from_slice(&[127, 127]);
+        assert!(
+            activity("tetris").load_state(&corner).is_err(),
+            "piece at (127, 127)"
+        );
+        corner[n - 18..n - 16].copy_from_slice(&[-128_i8 as u8; 2]);
+        assert!(
+            activity("tetris").load_state(&corner).is_err(),
+            "piece at (-128, -128)"
+        );
+        assert!(corrupt(n - 2, 5).is_ok());
+    }
+}
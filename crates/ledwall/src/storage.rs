@@ -0,0 +1,94 @@
+//! Persistent files on disk.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+
+/// Environment variable that overrides the directory for persistent files.
+pub const DIR_ENV_VAR: &str = "LEDWALL_DIR";
+
+/// Returns the directory for persistent files.
+///
+/// This is `$LEDWALL_DIR` if set, otherwise `$XDG_CONFIG_HOME/ledwall` or
+/// `~/.config/ledwall`.
+pub fn dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(DIR_ENV_VAR) {
+        return dir.into();
+    }
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => match std::env::var_os("HOME") {
+            Some(home) => Path::new(&home).join(".config"),
+            None => PathBuf::from("."),
+        },
+    };
+    config_dir.join("ledwall")
+}
+
+/// Writes a file such that a crash or power loss leaves either the old
+/// contents or the new contents, never a partial file.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir)?;
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut f = std::fs::File::create(&tmp_path)?;
+    f.write_all(contents)?;
+    f.sync_all()?;
+    drop(f);
+    std::fs::rename(&tmp_path, path)?;
+
+    // Make sure the rename itself is persisted.
+    #[cfg(unix)]
+    std::fs::File::open(dir)?.sync_all()?;
+
+    Ok(())
+}
+
+/// Loads a TOML file from `dir`, returning the default value if the file does
+/// not exist.
+pub fn load_toml<T: DeserializeOwned + Default>(dir: &Path, file_name: &str) -> io::Result<T> {
+    match std::fs::read_to_string(dir.join(file_name)) {
+        Ok(s) => toml::from_str(&s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Directory for a test, which is removed when dropped.
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    /// Creates an empty directory with a name unique to this call.
+    pub fn new(name: &str) -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!(
+            "ledwall-test-{}-{count}-{name}",
+            std::process::id(),
+        ));
+        // Clear out anything left by an earlier process with the same ID.
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,182 @@
+//! Server for streaming frames to viewers on the network (see
+//! [`ledwall_stream`]).
+
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+use ledwall_stream::{Encoder, Header};
+
+use crate::{FPS, FrameBuffer};
+
+/// Environment variable that sets the address for the stream server to listen
+/// on. The stream server is disabled if this is not set.
+pub const ADDR_ENV_VAR: &str = "LEDWALL_STREAM";
+
+/// Maximum number of bytes waiting to be sent to a viewer before it is
+/// disconnected.
+const MAX_PENDING_LEN: usize = 1 << 20;
+
+/// Starts a stream server on the address in `$LEDWALL_STREAM`, or returns
+/// `None` if it is not set or the server could not be started.
+pub fn start_from_env() -> Option<StreamServer> {
+    let addr = std::env::var(ADDR_ENV_VAR).ok().filter(|a| !a.is_empty())?;
+    match StreamServer::bind(&addr) {
+        Ok(server) => {
+            match server.local_addr() {
+                Ok(local_addr) => println!("Streaming frames on {local_addr}"),
+                Err(_) => println!("Streaming frames on {addr}"),
+            }
+            Some(server)
+        }
+        Err(e) => {
+            eprintln!("Error starting stream server on {addr}: {e}");
+            None
+        }
+    }
+}
+
+/// Server that sends every frame to viewers on the network.
+#[derive(Debug)]
+pub struct StreamServer {
+    listener: TcpListener,
+    viewers: Vec<Viewer>,
+}
+
+#[derive(Debug)]
+struct Viewer {
+    stream: TcpStream,
+    addr: SocketAddr,
+    /// Bytes not yet sent.
+    pending: Vec<u8>,
+    encoder: Encoder,
+}
+
+impl Viewer {
+    /// Sends as much of the pending data as possible without blocking.
+    fn flush(&mut self) -> io::Result<()> {
+        let mut sent = 0;
+        while sent < self.pending.len() {
+            match self.stream.write(&self.pending[sent..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => sent += n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        self.pending.drain(..sent);
+        Ok(())
+    }
+}
+
+impl StreamServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            viewers: vec![],
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts new viewers and sends a frame to each one.
+    ///
+    /// Viewers that haven't received the previous frame yet skip this one.
+    pub fn publish(&mut self, frame_buffer: &FrameBuffer) {
+        self.accept_viewers(frame_buffer.size());
+
+        let frame: &[u8] = bytemuck::cast_slice(frame_buffer.as_flattened());
+        self.viewers.retain_mut(|viewer| {
+            let result = viewer.flush().and_then(|()| {
+                if viewer.pending.is_empty() {
+                    viewer.encoder.encode(frame, &mut viewer.pending);
+                    viewer.flush()?;
+                }
+                if viewer.pending.len() > MAX_PENDING_LEN {
+                    return Err(io::Error::other("too far behind"));
+                }
+                Ok(())
+            });
+            match result {
+                Ok(()) => true,
+                Err(e) => {
+                    println!("Stream viewer {} disconnected: {e}", viewer.addr);
+                    false
+                }
+            }
+        });
+    }
+
+    fn accept_viewers(&mut self, [width, height]: [usize; 2]) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    if let Err(e) = stream
+                        .set_nonblocking(true)
+                        .and_then(|()| stream.set_nodelay(true))
+                    {
+                        eprintln!("Error configuring stream viewer {addr}: {e}");
+                        continue;
+                    }
+                    println!("Stream viewer {addr} connected");
+                    let header = Header {
+                        width: width as u16,
+                        height: height as u16,
+                        fps: FPS as u16,
+                    };
+                    self.viewers.push(Viewer {
+                        stream,
+                        addr,
+                        pending: header.encode().to_vec(),
+                        encoder: Encoder::new(true),
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    eprintln!("Error accepting stream viewer: {e}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use ledwall_stream::Decoder;
+
+    use super::*;
+    use crate::{DEFAULT_SIZE, Rgb};
+
+    #[test]
+    fn loopback() {
+        let mut server = StreamServer::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+
+        let mut frames = vec![];
+        let mut frame_buffer = FrameBuffer::new(DEFAULT_SIZE);
+        let [width, height] = DEFAULT_SIZE;
+        for i in 0..10_u8 {
+            frame_buffer[i as usize][3] = Rgb([i, 2 * i, 3 * i]);
+            frame_buffer[height - 1][i as usize] = Rgb([255, i, 0]);
+            server.publish(&frame_buffer);
+            frames.push(bytemuck::cast_slice::<_, u8>(frame_buffer.as_flattened()).to_vec());
+        }
+        drop(server);
+
+        let mut r = BufReader::new(client);
+        let mut decoder = Decoder::new(&mut r).unwrap();
+        assert_eq!(decoder.header().width as usize, width);
+        assert_eq!(decoder.header().height as usize, height);
+        for frame in &frames {
+            assert_eq!(decoder.read_frame(&mut r).unwrap(), Some(frame.as_slice()));
+        }
+        assert_eq!(decoder.read_frame(&mut r).unwrap(), None);
+    }
+}
@@ -12,6 +12,33 @@ pub fn layout(s: &str) -> impl Iterator<Item = StaticImage> {
         '7' => include_rgba_image!("font/7.rgba"),
         '8' => include_rgba_image!("font/8.rgba"),
         '9' => include_rgba_image!("font/9.rgba"),
+        'a' | 'A' => include_rgba_image!("font/a.rgba"),
+        'b' | 'B' => include_rgba_image!("font/b.rgba"),
+        'c' | 'C' => include_rgba_image!("font/c.rgba"),
+        'd' | 'D' => include_rgba_image!("font/d.rgba"),
+        'e' | 'E' => include_rgba_image!("font/e.rgba"),
+        'f' | 'F' => include_rgba_image!("font/f.rgba"),
+        'g' | 'G' => include_rgba_image!("font/g.rgba"),
+        'h' | 'H' => include_rgba_image!("font/h.rgba"),
+        'i' | 'I' => include_rgba_image!("font/i.rgba"),
+        'j' | 'J' => include_rgba_image!("font/j.rgba"),
+        'k' | 'K' => include_rgba_image!("font/k.rgba"),
+        'l' | 'L' => include_rgba_image!("font/l.rgba"),
+        'm' | 'M' => include_rgba_image!("font/m.rgba"),
+        'n' | 'N' => include_rgba_image!("font/n.rgba"),
+        'o' | 'O' => include_rgba_image!("font/o.rgba"),
+        'p' | 'P' => include_rgba_image!("font/p.rgba"),
+        'q' | 'Q' => include_rgba_image!("font/q.rgba"),
+        'r' | 'R' => include_rgba_image!("font/r.rgba"),
+        's' | 'S' => include_rgba_image!("font/s.rgba"),
+        't' | 'T' => include_rgba_image!("font/t.rgba"),
+        'u' | 'U' => include_rgba_image!("font/u.rgba"),
+        'v' | 'V' => include_rgba_image!("font/v.rgba"),
+        'w' | 'W' => include_rgba_image!("font/w.rgba"),
+        'x' | 'X' => include_rgba_image!("font/x.rgba"),
+        'y' | 'Y' => include_rgba_image!("font/y.rgba"),
+        'z' | 'Z' => include_rgba_image!("font/z.rgba"),
+        ' ' => include_rgba_image!("font/space.rgba"),
         ':' => include_rgba_image!("font/colon.rgba"),
         '.' => include_rgba_image!("font/period.rgba"),
         _ => include_rgba_image!("font/unknown.rgba"),
@@ -19,7 +46,7 @@ pub fn layout(s: &str) -> impl Iterator<Item = StaticImage> {
 }
 
 pub fn width(s: &str) -> usize {
-    layout(s).map(|img| img.width() + 1).sum::<usize>() - 1
+    (layout(s).map(|img| img.width() + 1).sum::<usize>()).saturating_sub(1)
 }
 
 pub fn draw(s: &str, fb: &mut FrameBufferRect<'_>, tint: Rgb) {
@@ -0,0 +1,80 @@
+//! Fixed-timestep scheduling for the frontends that run in real time.
+//!
+//! Everything in the shell counts time in steps of `1 / FPS` seconds, so the
+//! shell must be updated exactly [`FPS`] times per second regardless of how
+//! fast the display refreshes. Each time the display is ready for a frame,
+//! the frontend asks [`Timestep`] how many steps are due, runs that many, and
+//! shows the last frame.
+
+use std::time::{Duration, Instant};
+
+use crate::FPS;
+
+/// Number of seconds of steps to run at once to catch up after the frontend
+/// falls behind. Steps beyond this are dropped, so that a long stall slows
+/// down the simulation instead of fast-forwarding it.
+const MAX_CATCH_UP: f32 = 0.25;
+
+/// Schedule of steps at a fixed rate.
+#[derive(Debug, Clone)]
+pub struct Timestep {
+    step_duration: Duration,
+    max_steps: u32,
+    /// Time at which the next step is due.
+    next_step: Instant,
+}
+
+impl Timestep {
+    /// Constructs a schedule of [`FPS`] steps per second whose first step is
+    /// due at `start`.
+    pub fn new(start: Instant) -> Self {
+        Self {
+            step_duration: Duration::from_secs_f64(1.0 / FPS as f64),
+            max_steps: (MAX_CATCH_UP * FPS as f32) as u32,
+            next_step: start,
+        }
+    }
+
+    /// Returns the number of steps to run at `now`, which is zero if the
+    /// display refreshes faster than the steps. If more than
+    /// [`MAX_CATCH_UP`] seconds of steps are due, the rest are dropped.
+    pub fn steps_due(&mut self, now: Instant) -> u32 {
+        let Some(behind) = now.checked_duration_since(self.next_step) else {
+            return 0;
+        };
+        let due = (behind.as_nanos() / self.step_duration.as_nanos()) as u32 + 1;
+        if due > self.max_steps {
+            self.next_step = now + self.step_duration;
+            return self.max_steps;
+        }
+        self.next_step += self.step_duration * due;
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_due() {
+        let start = Instant::now();
+        let step = Duration::from_secs_f64(1.0 / FPS as f64);
+        let mut timestep = Timestep::new(start);
+
+        assert_eq!(timestep.steps_due(start), 1);
+        assert_eq!(timestep.steps_due(start), 0);
+        assert_eq!(timestep.steps_due(start + step / 2), 0);
+        assert_eq!(timestep.next_step, start + step);
+
+        // Catch up after falling behind.
+        assert_eq!(timestep.steps_due(start + step * 3 + step / 2), 3);
+        assert_eq!(timestep.next_step, start + step * 4);
+
+        // Drop steps after a long stall.
+        let now = start + Duration::from_secs(10);
+        assert_eq!(timestep.steps_due(now), timestep.max_steps);
+        assert_eq!(timestep.next_step, now + step);
+        assert_eq!(timestep.steps_due(now + step), 1);
+    }
+}
@@ -0,0 +1,197 @@
+//! Short notifications shown as banners over the current activity.
+//!
+//! The newest banner slides in at the top of the panel and pushes older ones
+//! down. Each banner fades out after a few seconds, and text that doesn't fit
+//! scrolls horizontally before then.
+
+use crate::{BLACK, FPS, FrameBufferRect, Rgb, text};
+
+/// Height of a banner, including a row above the text and a border below.
+const HEIGHT: usize = 7;
+/// Maximum number of banners shown at once. Posting another removes the
+/// oldest.
+const MAX_TOASTS: usize = 3;
+
+/// Number of seconds to show a banner whose text fits.
+const DURATION: f32 = 2.5;
+/// Number of seconds to fade out at the end.
+const FADE_DURATION: f32 = 0.25;
+/// Number of seconds for a banner to slide into place.
+const SLIDE_DURATION: f32 = 0.125;
+/// Number of seconds to wait before and after scrolling long text.
+const SCROLL_PAUSE: f32 = 0.75;
+/// Number of pixels per second to scroll long text.
+const SCROLL_SPEED: f32 = 24.0;
+
+const BACKGROUND: Rgb = BLACK;
+const DARKEN_BORDER: f32 = 0.5;
+
+/// Banner in the queue.
+#[derive(Debug, Clone, PartialEq)]
+struct Toast {
+    text: String,
+    color: Rgb,
+    /// Number of pixels that the text is wider than the banner.
+    overflow: usize,
+    /// Number of frames since the banner was posted.
+    frame: u32,
+    /// Number of frames to show the banner, including fading out.
+    duration: u32,
+    /// Vertical position of the top of the banner, which moves toward its
+    /// place in the stack.
+    y: f32,
+}
+
+impl Toast {
+    /// Returns how far the text has scrolled to the left.
+    fn scroll(&self) -> usize {
+        let seconds = self.frame as f32 / FPS as f32 - SCROLL_PAUSE;
+        ((seconds * SCROLL_SPEED).max(0.0) as usize).min(self.overflow)
+    }
+
+    /// Returns the opacity of the banner, which fades out at the end.
+    fn opacity(&self) -> f32 {
+        let remaining = self.duration.saturating_sub(self.frame) as f32 / FPS as f32;
+        (remaining / FADE_DURATION).min(1.0)
+    }
+}
+
+/// Queue of notifications.
+#[derive(Debug)]
+pub struct Toasts {
+    /// Width of the frame, which the banners span.
+    width: usize,
+    /// Banners being shown, from newest to oldest.
+    toasts: Vec<Toast>,
+}
+
+impl Toasts {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            toasts: vec![],
+        }
+    }
+
+    /// Shows a notification. Posting the same text in the same color as a
+    /// banner that is already shown moves it to the top and restarts its
+    /// timer.
+    pub fn post(&mut self, text: impl Into<String>, color: Rgb) {
+        let text = text.into();
+        let existing = (self.toasts.iter()).position(|t| t.text == text && t.color == color);
+        let y = match existing {
+            Some(i) => self.toasts.remove(i).y,
+            None => -(HEIGHT as f32),
+        };
+        let overflow = text::width(&text).saturating_sub(self.width.saturating_sub(2));
+        let mut toast = Toast {
+            text,
+            color,
+            overflow,
+            frame: 0,
+            duration: 0,
+            y,
+        };
+        let scroll_seconds = toast.overflow as f32 / SCROLL_SPEED + SCROLL_PAUSE * 2.0;
+        toast.duration = (DURATION.max(scroll_seconds) * FPS as f32) as u32;
+        self.toasts.insert(0, toast);
+        self.toasts.truncate(MAX_TOASTS);
+    }
+
+    /// Advances the banners by one frame and removes expired ones.
+    pub fn step(&mut self) {
+        self.toasts.retain_mut(|toast| {
+            toast.frame += 1;
+            toast.frame < toast.duration
+        });
+        let max_step = HEIGHT as f32 / (SLIDE_DURATION * FPS as f32);
+        for (i, toast) in self.toasts.iter_mut().enumerate() {
+            let target = (i * HEIGHT) as f32;
+            toast.y += (target - toast.y).clamp(-max_step, max_step);
+        }
+    }
+
+    /// Draws the banners over what is already in `fb`.
+    pub fn draw(&self, fb: &mut FrameBufferRect<'_>) {
+        // Draw the oldest first, so that a new banner slides in over the rest.
+        for toast in self.toasts.iter().rev() {
+            let opacity = toast.opacity();
+            let y = toast.y.round() as isize;
+            for dy in 0..HEIGHT {
+                let color = match dy {
+                    _ if dy == HEIGHT - 1 => toast.color.darken(DARKEN_BORDER),
+                    _ => BACKGROUND,
+                };
+                let Ok(row) = usize::try_from(y + dy as isize) else {
+                    continue;
+                };
+                for x in 0..fb.width() {
+                    if let Some(pixel) = fb.get_mut(x, row) {
+                        *pixel = pixel.mix(color, opacity);
+                    }
+                }
+            }
+            let mut x = 1 - toast.scroll() as isize;
+            for img in text::layout(&toast.text) {
+                let mut fb = fb.with_offset([x, y + 1]);
+                img.draw_with_custom_blend(&mut fb, |pixel, _, alpha| {
+                    pixel.mix(toast.color, alpha as f32 / 255.0 * opacity)
+                });
+                x += img.width() as isize + 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DEFAULT_SIZE, FrameBuffer, WHITE};
+
+    fn step_frames(toasts: &mut Toasts, seconds: f32) {
+        for _ in 0..(seconds * FPS as f32) as usize {
+            toasts.step();
+        }
+    }
+
+    #[test]
+    fn stack_and_time_out() {
+        let mut toasts = Toasts::new(DEFAULT_SIZE[0]);
+        toasts.post("one", WHITE);
+        step_frames(&mut toasts, 1.0);
+        toasts.post("two", WHITE);
+        step_frames(&mut toasts, 0.5);
+        let ys: Vec<f32> = toasts.toasts.iter().map(|t| t.y).collect();
+        assert_eq!(ys, [0.0, HEIGHT as f32]);
+
+        // Reposting moves the banner to the top.
+        toasts.post("one", WHITE);
+        assert_eq!(toasts.toasts[0].text, "one");
+        assert_eq!(toasts.toasts.len(), 2);
+
+        for text in ["three", "four", "five"] {
+            toasts.post(text, WHITE);
+        }
+        assert_eq!(toasts.toasts.len(), MAX_TOASTS);
+        assert_eq!(toasts.toasts[MAX_TOASTS - 1].text, "three");
+
+        step_frames(&mut toasts, DURATION);
+        assert!(toasts.toasts.is_empty());
+    }
+
+    #[test]
+    fn long_text() {
+        let mut toasts = Toasts::new(DEFAULT_SIZE[0]);
+        toasts.post("this does not fit", WHITE);
+        let toast = &toasts.toasts[0];
+        assert!(toast.overflow > 0);
+        assert!(toast.duration > (DURATION * FPS as f32) as u32);
+
+        let mut fb = FrameBuffer::filled(DEFAULT_SIZE, Rgb([9, 9, 9]));
+        step_frames(&mut toasts, 1.0);
+        toasts.draw(&mut FrameBufferRect::new(&mut fb));
+        assert_eq!(fb[0][0], BACKGROUND);
+        assert_eq!(fb[HEIGHT][0], Rgb([9, 9, 9]), "below the banner");
+        assert!((1..HEIGHT - 1).any(|y| fb[y].contains(&WHITE)));
+    }
+}
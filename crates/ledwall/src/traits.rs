@@ -1,3 +1,9 @@
+use std::io;
+use std::path::Path;
+
+use crate::activities::ActivityInfo;
+use crate::activities::setting::{Setting, SettingValue};
+use crate::sound::{Sound, Tune};
 use crate::{FrameBufferRect, FullInput, StaticImage};
 
 pub trait Widget<I> {
@@ -7,12 +13,86 @@ pub trait Widget<I> {
 }
 
 pub trait Activity: Widget<FullInput> {
+    /// Returns metadata about the activity, which is also listed in
+    /// [`crate::activities::REGISTRY`].
+    fn info(&self) -> &'static ActivityInfo;
+
+    /// Returns a unique name for the activity, used to identify its saved
+    /// state.
+    fn id(&self) -> &'static str {
+        self.info().id
+    }
+
     fn menu_image(&self) -> StaticImage;
 
     fn reset(&mut self);
 
+    /// Loads configuration from files in `dir` (see [`crate::storage::dir()`]).
+    /// This is called once on startup, before the saved state is loaded, and
+    /// not at all if persistence is disabled.
+    fn load_config(&mut self, _dir: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Changes the size of the frame that the activity draws, which is
+    /// [`crate::DEFAULT_SIZE`] until this is called. The shell calls this
+    /// before the first step and before loading saved state. Layouts should
+    /// follow the size passed to [`Widget::draw()`] instead; this is only for
+    /// activities whose state covers the whole frame.
+    fn set_size(&mut self, _size: [usize; 2]) {}
+
     /// Returns whether to stay awake even if all controllers disconnect.
     fn stay_awake(&self) -> bool {
         false
     }
+
+    /// Returns the sound effects that the activity started since this was
+    /// last called. This is called after every step.
+    fn take_sounds(&mut self) -> Vec<Sound> {
+        vec![]
+    }
+
+    /// Returns the notifications that the activity posted since this was last
+    /// called, which the shell shows over the activity. This is called after
+    /// every step.
+    fn take_toasts(&mut self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Returns the music to loop while the activity is running, if any.
+    fn music(&self) -> Option<&'static Tune> {
+        None
+    }
+
+    /// Returns the settings that the menu shows on the activity's page, with
+    /// their current values. Settings should survive [`Activity::reset()`].
+    fn settings(&self) -> Vec<Setting> {
+        vec![]
+    }
+
+    /// Changes the setting at `index` in [`Activity::settings()`], which takes
+    /// effect immediately.
+    fn set_setting(&mut self, _index: usize, _value: SettingValue) {}
+
+    /// Prepares a fresh instance of the activity to run unattended in attract
+    /// mode. This is only called if [`ActivityInfo::attract`] is set.
+    fn start_attract_mode(&mut self) {}
+
+    /// Returns the state of the activity so that it can be restored after a
+    /// restart, or `None` if there is nothing worth saving.
+    ///
+    /// The state should start with a version number (see
+    /// [`crate::state::StateWriter::new()`]) so that state saved by an older
+    /// version of the activity is rejected instead of misinterpreted.
+    fn save_state(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Restores state returned by [`Activity::save_state()`].
+    ///
+    /// If this returns an error, the activity may be left in an inconsistent
+    /// state and should be reset.
+    fn load_state(&mut self, _state: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
 }
@@ -1,3 +1,5 @@
+mod setting_row;
 mod slider;
 
+pub use setting_row::SettingRow;
 pub use slider::{LabeledSlider, Slider};
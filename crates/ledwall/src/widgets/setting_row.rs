@@ -0,0 +1,66 @@
+use crate::activities::setting::Setting;
+use crate::widgets::Slider;
+use crate::{FrameBufferRect, Rgb, WHITE, Widget, text};
+
+const DARKEN_BACKGROUND: f32 = 0.85;
+const DARKEN_UNSELECTED_BACKGROUND: f32 = 0.95;
+const DARKEN_UNSELECTED_TEXT: f32 = 0.5;
+
+/// Row on an activity's settings page, showing the setting's name above its
+/// value and a slider.
+pub struct SettingRow {
+    pub setting: Setting,
+    /// Whether the row responds to input and is highlighted.
+    pub selected: bool,
+    /// Slider whose value is the setting's position (see
+    /// [`crate::activities::setting::SettingValue::position()`]).
+    slider: Slider,
+}
+
+impl SettingRow {
+    pub const HEIGHT: usize = 15;
+
+    pub fn new(setting: Setting, color: Rgb) -> Self {
+        let max_position = setting.value.max_position();
+        let slider = Slider::new(setting.value.position(), 0..=max_position, color);
+        Self {
+            setting,
+            selected: false,
+            slider,
+        }
+    }
+}
+
+impl Widget<[bool; 2]> for SettingRow {
+    fn step(&mut self, input: [bool; 2]) {
+        self.slider.step(input);
+        if self.slider.get() != self.setting.value.position() {
+            self.setting.value = self.setting.value.with_position(self.slider.get());
+        }
+    }
+
+    fn draw(&self, fb: &mut FrameBufferRect<'_>) {
+        let color = self.slider.color;
+        let (background, text_darken) = match self.selected {
+            true => (color.darken(DARKEN_BACKGROUND), 0.0),
+            false => (
+                color.darken(DARKEN_UNSELECTED_BACKGROUND),
+                DARKEN_UNSELECTED_TEXT,
+            ),
+        };
+        fb.fill(background);
+        text::draw(
+            self.setting.name,
+            &mut fb.with_offset([1, 1]),
+            color.darken(text_darken),
+        );
+        text::draw(
+            &self.setting.value.label(),
+            &mut fb.with_offset([1, 7]),
+            WHITE.darken(text_darken),
+        );
+        let width = fb.width().saturating_sub(2);
+        self.slider
+            .draw(&mut fb.with_offset([1, 13]).with_size([width, 1]));
+    }
+}
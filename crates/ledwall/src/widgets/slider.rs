@@ -43,9 +43,12 @@ impl Slider {
         self.flash = Some(FlashAnimation { frame: 0 });
     }
     pub fn set(&mut self, new_value: u8) {
-        self.value = new_value.clamp(*self.range.start(), *self.range.end());
+        self.set_without_animation(new_value);
         self.trigger_animation();
     }
+    pub fn set_without_animation(&mut self, new_value: u8) {
+        self.value = new_value.clamp(*self.range.start(), *self.range.end());
+    }
 }
 
 impl Widget<[bool; 2]> for Slider {
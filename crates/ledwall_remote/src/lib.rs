@@ -0,0 +1,265 @@
+//! Protocol for controlling the LED wall over the network.
+//!
+//! A client acts as one player's controller by sending UDP packets to the
+//! LED wall, which listens on the address in `$LEDWALL_REMOTE` if it is set,
+//! conventionally on [`DEFAULT_PORT`]. The client sends
+//! [`Message::Press`] and [`Message::Release`] when buttons change and
+//! [`Message::Heartbeat`] at least every [`HEARTBEAT_INTERVAL`]. The LED wall
+//! considers the controller disconnected if it receives nothing for
+//! [`TIMEOUT`] or if it receives [`Message::Disconnect`].
+//!
+//! Because UDP packets may be lost or reordered, each heartbeat includes all
+//! buttons currently held and each packet includes a sequence number. Packets
+//! with a sequence number no greater than the last one received are ignored.
+//!
+//! # Packet format
+//!
+//! All integers are little-endian.
+//!
+//! - 4 bytes: magic number [`MAGIC`]
+//! - `u8`: protocol version ([`VERSION`])
+//! - `u8`: controller (`0` = blue, `1` = green, `2` = red, `3` = yellow)
+//! - `u32`: sequence number
+//! - `u8`: message type, followed by the message
+//!   - `0` (heartbeat): `u16` buttons held
+//!   - `1` (press): `u16` buttons pressed
+//!   - `2` (release): `u16` buttons released
+//!   - `3` (disconnect)
+//!
+//! Buttons are a bitmask with bits in the same order as [`BUTTON_NAMES`],
+//! starting from the least significant bit.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// Magic number at the start of every packet.
+pub const MAGIC: &[u8; 4] = b"LWRC";
+/// Current version of the protocol.
+pub const VERSION: u8 = 1;
+/// Default UDP port for the LED wall to listen on.
+pub const DEFAULT_PORT: u16 = 7777;
+
+/// Maximum time between packets from a client.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(100);
+/// Time after the last packet from a client before the LED wall considers it
+/// disconnected.
+pub const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Maximum length of a packet.
+pub const MAX_PACKET_LEN: usize = 13;
+
+/// Name of each button, in the same order as the bits of a button bitmask.
+pub const BUTTON_NAMES: [&str; 16] = [
+    "up", "down", "left", "right", "a", "b", "x", "y", "l", "r", "lt", "rt", "plus", "minus",
+    "star", "heart",
+];
+
+const MSG_HEARTBEAT: u8 = 0;
+const MSG_PRESS: u8 = 1;
+const MSG_RELEASE: u8 = 2;
+const MSG_DISCONNECT: u8 = 3;
+
+/// Controller that a client acts as.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Controller {
+    Blue = 0,
+    Green = 1,
+    Red = 2,
+    Yellow = 3,
+}
+
+/// Message from a client.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Message {
+    /// Buttons currently held.
+    Heartbeat(u16),
+    /// Buttons that were pressed.
+    Press(u16),
+    /// Buttons that were released.
+    Release(u16),
+    /// The client is disconnecting.
+    Disconnect,
+}
+
+/// Packet sent from a client to the LED wall.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Packet {
+    pub controller: Controller,
+    pub seq: u32,
+    pub message: Message,
+}
+
+impl Packet {
+    pub fn encode(self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(MAX_PACKET_LEN);
+        ret.extend_from_slice(MAGIC);
+        ret.push(VERSION);
+        ret.push(self.controller as u8);
+        ret.extend_from_slice(&self.seq.to_le_bytes());
+        let (msg_type, buttons) = match self.message {
+            Message::Heartbeat(buttons) => (MSG_HEARTBEAT, Some(buttons)),
+            Message::Press(buttons) => (MSG_PRESS, Some(buttons)),
+            Message::Release(buttons) => (MSG_RELEASE, Some(buttons)),
+            Message::Disconnect => (MSG_DISCONNECT, None),
+        };
+        ret.push(msg_type);
+        if let Some(buttons) = buttons {
+            ret.extend_from_slice(&buttons.to_le_bytes());
+        }
+        ret
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let Some((header, rest)) = bytes.split_first_chunk::<10>() else {
+            return Err("packet too short".to_string());
+        };
+        let [m0, m1, m2, m3, version, controller, s0, s1, s2, s3] = *header;
+        if &[m0, m1, m2, m3] != MAGIC {
+            return Err("not a remote control packet".to_string());
+        }
+        if version != VERSION {
+            return Err(format!("unsupported protocol version {version}"));
+        }
+        let controller = match controller {
+            0 => Controller::Blue,
+            1 => Controller::Green,
+            2 => Controller::Red,
+            3 => Controller::Yellow,
+            _ => return Err(format!("invalid controller {controller}")),
+        };
+        let seq = u32::from_le_bytes([s0, s1, s2, s3]);
+
+        let buttons = |rest: &[u8]| match rest {
+            &[b0, b1] => Ok(u16::from_le_bytes([b0, b1])),
+            _ => Err("wrong packet length".to_string()),
+        };
+        let message = match rest {
+            [] => return Err("missing message".to_string()),
+            [MSG_HEARTBEAT, rest @ ..] => Message::Heartbeat(buttons(rest)?),
+            [MSG_PRESS, rest @ ..] => Message::Press(buttons(rest)?),
+            [MSG_RELEASE, rest @ ..] => Message::Release(buttons(rest)?),
+            [MSG_DISCONNECT] => Message::Disconnect,
+            [MSG_DISCONNECT, ..] => return Err("wrong packet length".to_string()),
+            [other, ..] => return Err(format!("unknown message type {other}")),
+        };
+
+        Ok(Self {
+            controller,
+            seq,
+            message,
+        })
+    }
+}
+
+/// Parses a list of button names separated by `+` into a bitmask, or `.` for
+/// no buttons.
+pub fn parse_buttons(s: &str) -> Result<u16, String> {
+    let mut bits = 0;
+    if s != "." {
+        for name in s.split('+') {
+            let i = BUTTON_NAMES
+                .iter()
+                .position(|&n| n.eq_ignore_ascii_case(name.trim()))
+                .ok_or_else(|| format!("unknown button {name:?}"))?;
+            bits |= 1 << i;
+        }
+    }
+    Ok(bits)
+}
+
+/// Client that acts as a controller.
+///
+/// The caller is responsible for calling [`Client::heartbeat()`] at least every
+/// [`HEARTBEAT_INTERVAL`].
+#[derive(Debug)]
+pub struct Client {
+    socket: UdpSocket,
+    controller: Controller,
+    seq: u32,
+    buttons: u16,
+}
+
+impl Client {
+    /// Constructs a client that sends packets to `addr` and immediately sends a
+    /// heartbeat.
+    pub fn connect(addr: impl ToSocketAddrs, controller: Controller) -> io::Result<Self> {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to")
+        })?;
+        let bind_addr: SocketAddr = match addr {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0; 8], 0).into(),
+        };
+        let socket = UdpSocket::bind(bind_addr)?;
+        socket.connect(addr)?;
+        let mut ret = Self {
+            socket,
+            controller,
+            seq: 0,
+            buttons: 0,
+        };
+        ret.heartbeat()?;
+        Ok(ret)
+    }
+
+    /// Returns the buttons currently held.
+    pub fn buttons(&self) -> u16 {
+        self.buttons
+    }
+
+    /// Presses buttons.
+    pub fn press(&mut self, buttons: u16) -> io::Result<()> {
+        self.buttons |= buttons;
+        self.send(Message::Press(buttons))
+    }
+    /// Releases buttons.
+    pub fn release(&mut self, buttons: u16) -> io::Result<()> {
+        self.buttons &= !buttons;
+        self.send(Message::Release(buttons))
+    }
+    /// Sends the buttons currently held.
+    pub fn heartbeat(&mut self) -> io::Result<()> {
+        self.send(Message::Heartbeat(self.buttons))
+    }
+    /// Tells the LED wall that the controller is disconnecting. Sending any
+    /// other message reconnects.
+    pub fn disconnect(&mut self) -> io::Result<()> {
+        self.send(Message::Disconnect)
+    }
+
+    fn send(&mut self, message: Message) -> io::Result<()> {
+        self.seq = self.seq.wrapping_add(1);
+        let packet = Packet {
+            controller: self.controller,
+            seq: self.seq,
+            message,
+        };
+        self.socket.send(&packet.encode())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn invalid_packets() {
+        let packet = Packet {
+            controller: Controller::Green,
+            seq: 7,
+            message: Message::Press(0x1234),
+        };
+        let bytes = packet.encode();
+        assert_eq!(Packet::decode(&bytes), Ok(packet));
+        for len in 0..bytes.len() {
+            assert!(Packet::decode(&bytes[..len]).is_err());
+        }
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] += 1;
+        assert!(Packet::decode(&wrong_version).is_err());
+        let mut wrong_controller = bytes.clone();
+        wrong_controller[5] = 4;
+        assert!(Packet::decode(&wrong_controller).is_err());
+    }
+}
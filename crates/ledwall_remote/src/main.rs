@@ -0,0 +1,98 @@
+//! Reference client for the remote control protocol.
+//!
+//! Reads commands from standard input, one per line:
+//!
+//! - `press <buttons>`
+//! - `release <buttons>`
+//! - `tap <buttons>` (press and immediately release)
+//! - `hold <buttons> <milliseconds>`
+//! - `wait <milliseconds>`
+//! - `quit`
+//!
+//! Buttons are separated by `+`, such as `a+up`.
+
+use std::io::BufRead;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ledwall_remote::{Client, Controller, DEFAULT_PORT, HEARTBEAT_INTERVAL, parse_buttons};
+
+fn main() {
+    let mut addr = format!("127.0.0.1:{DEFAULT_PORT}");
+    let mut controller = Controller::Blue;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--blue" => controller = Controller::Blue,
+            "--green" => controller = Controller::Green,
+            "--red" => controller = Controller::Red,
+            "--yellow" => controller = Controller::Yellow,
+            "--help" | "-h" => {
+                println!(
+                    "usage: ledwall_remote [--blue | --green | --red | --yellow] [<host>:<port>]"
+                );
+                println!("Reads commands from stdin: press, release, tap, hold, wait, quit");
+                return;
+            }
+            _ => addr = arg,
+        }
+    }
+
+    let client = Client::connect(&addr, controller).expect("error connecting");
+    let client = Arc::new(Mutex::new(client));
+    println!("Acting as {controller:?} controller on {addr}");
+
+    std::thread::spawn({
+        let client = Arc::clone(&client);
+        move || {
+            loop {
+                std::thread::sleep(HEARTBEAT_INTERVAL / 2);
+                if let Err(e) = client.lock().unwrap().heartbeat() {
+                    eprintln!("Error sending heartbeat: {e}");
+                }
+            }
+        }
+    });
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line.expect("error reading stdin");
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let result = match words.as_slice() {
+            [] => continue,
+            ["quit"] => break,
+            ["press", buttons] => parse_buttons(buttons)
+                .and_then(|b| client.lock().unwrap().press(b).map_err(|e| e.to_string())),
+            ["release", buttons] => parse_buttons(buttons)
+                .and_then(|b| client.lock().unwrap().release(b).map_err(|e| e.to_string())),
+            ["tap", buttons] => parse_buttons(buttons).and_then(|b| {
+                let mut client = client.lock().unwrap();
+                client
+                    .press(b)
+                    .and_then(|()| client.release(b))
+                    .map_err(|e| e.to_string())
+            }),
+            ["hold", buttons, ms] => parse_buttons(buttons).and_then(|b| {
+                let duration = parse_millis(ms)?;
+                client.lock().unwrap().press(b).map_err(|e| e.to_string())?;
+                std::thread::sleep(duration);
+                client.lock().unwrap().release(b).map_err(|e| e.to_string())
+            }),
+            ["wait", ms] => parse_millis(ms).map(std::thread::sleep),
+            _ => Err(format!("unknown command {line:?}")),
+        };
+        if let Err(e) = result {
+            eprintln!("{e}");
+        }
+    }
+
+    // Keep the lock so that the heartbeat thread can't reconnect.
+    let mut client = client.lock().unwrap();
+    if let Err(e) = client.disconnect() {
+        eprintln!("Error disconnecting: {e}");
+    }
+}
+
+fn parse_millis(s: &str) -> Result<Duration, String> {
+    s.parse()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("invalid number of milliseconds {s:?}"))
+}
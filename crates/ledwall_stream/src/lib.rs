@@ -0,0 +1,319 @@
+//! Protocol for streaming the LED wall's frames over the network.
+//!
+//! A client connects to the LED wall over TCP (port [`DEFAULT_PORT`] by
+//! default). The LED wall sends a header followed by a message for each frame.
+//! If the client falls behind, frames are skipped rather than buffered.
+//!
+//! # Format
+//!
+//! All integers are little-endian. Pixels are 3 bytes each (RGB) in row-major
+//! order, starting from the top left.
+//!
+//! - Header
+//!   - 4 bytes: magic number [`MAGIC`]
+//!   - `u8`: protocol version ([`VERSION`])
+//!   - `u16`: width
+//!   - `u16`: height
+//!   - `u16`: frames per second
+//! - Any number of frames, each with:
+//!   - `u8`: frame type
+//!   - `u32`: byte length of the rest of the frame
+//!   - If the frame type is `0` (keyframe): every pixel
+//!   - If the frame type is `1` (delta): any number of runs of changed pixels
+//!     relative to the previous frame, each with:
+//!     - `u16`: number of unchanged pixels before the run
+//!     - `u16`: number of pixels in the run
+//!     - each pixel in the run
+//!
+//! The first frame is always a keyframe.
+
+use std::io::{self, Read};
+
+/// Magic number at the start of every stream.
+pub const MAGIC: &[u8; 4] = b"LWFS";
+/// Current version of the protocol.
+pub const VERSION: u8 = 1;
+/// Default TCP port for the LED wall to listen on.
+pub const DEFAULT_PORT: u16 = 7778;
+
+/// Byte length of the header.
+pub const HEADER_LEN: usize = 11;
+
+const FRAME_KEY: u8 = 0;
+const FRAME_DELTA: u8 = 1;
+
+/// Maximum number of unchanged pixels to include in a run of changed pixels
+/// rather than starting a new run, since each run has 4 bytes of overhead.
+const MAX_RUN_GAP: usize = 1;
+
+/// Stream header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Header {
+    pub width: u16,
+    pub height: u16,
+    pub fps: u16,
+}
+
+impl Header {
+    /// Returns the byte length of a frame.
+    pub fn frame_len(self) -> usize {
+        self.width as usize * self.height as usize * 3
+    }
+
+    pub fn encode(self) -> [u8; HEADER_LEN] {
+        let mut ret = [0; HEADER_LEN];
+        ret[0..4].copy_from_slice(MAGIC);
+        ret[4] = VERSION;
+        ret[5..7].copy_from_slice(&self.width.to_le_bytes());
+        ret[7..9].copy_from_slice(&self.height.to_le_bytes());
+        ret[9..11].copy_from_slice(&self.fps.to_le_bytes());
+        ret
+    }
+
+    pub fn read_from(mut r: impl Read) -> io::Result<Self> {
+        let mut buf = [0; HEADER_LEN];
+        r.read_exact(&mut buf)?;
+        if &buf[0..4] != MAGIC {
+            return Err(invalid_data("not a frame stream"));
+        }
+        if buf[4] != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported stream version {}",
+                buf[4],
+            )));
+        }
+        Ok(Self {
+            width: u16::from_le_bytes([buf[5], buf[6]]),
+            height: u16::from_le_bytes([buf[7], buf[8]]),
+            fps: u16::from_le_bytes([buf[9], buf[10]]),
+        })
+    }
+}
+
+/// Encoder for the frames of a stream.
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    delta: bool,
+    previous: Option<Vec<u8>>,
+}
+
+impl Encoder {
+    /// Constructs an encoder, which uses delta frames when they are smaller
+    /// than keyframes if `delta` is `true`.
+    pub fn new(delta: bool) -> Self {
+        Self {
+            delta,
+            previous: None,
+        }
+    }
+
+    /// Appends an encoded frame to `out`.
+    pub fn encode(&mut self, frame: &[u8], out: &mut Vec<u8>) {
+        let start = out.len();
+        match &self.previous {
+            Some(previous) if self.delta && previous.len() == frame.len() => {
+                out.push(FRAME_DELTA);
+                out.extend_from_slice(&[0; 4]); // length placeholder
+                encode_delta(previous, frame, out);
+                // Fall back to a keyframe if the delta isn't any smaller.
+                if out.len() - start - 5 >= frame.len() {
+                    out.truncate(start);
+                    encode_keyframe(frame, out);
+                }
+            }
+            _ => encode_keyframe(frame, out),
+        }
+        let len = (out.len() - start - 5) as u32;
+        out[start + 1..start + 5].copy_from_slice(&len.to_le_bytes());
+
+        match &mut self.previous {
+            Some(previous) => {
+                previous.clear();
+                previous.extend_from_slice(frame);
+            }
+            None => self.previous = Some(frame.to_vec()),
+        }
+    }
+}
+
+fn encode_keyframe(frame: &[u8], out: &mut Vec<u8>) {
+    out.push(FRAME_KEY);
+    out.extend_from_slice(&[0; 4]); // length placeholder
+    out.extend_from_slice(frame);
+}
+
+fn encode_delta(previous: &[u8], frame: &[u8], out: &mut Vec<u8>) {
+    let pixel_count = frame.len() / 3;
+    let changed = |i: usize| previous[i * 3..i * 3 + 3] != frame[i * 3..i * 3 + 3];
+    let max_len = u16::MAX as usize;
+
+    let mut pos = 0; // pixels before this have been encoded
+    let mut i = 0;
+    while i < pixel_count {
+        if !changed(i) {
+            i += 1;
+            continue;
+        }
+
+        // Extend the run until more than `MAX_RUN_GAP` unchanged pixels in a
+        // row.
+        let start = i;
+        let mut end = i + 1;
+        let mut j = end;
+        while j < pixel_count && j - start < max_len {
+            if changed(j) {
+                end = j + 1;
+            } else if j - end >= MAX_RUN_GAP {
+                break;
+            }
+            j += 1;
+        }
+
+        // Skip long gaps using empty runs.
+        while start - pos > max_len {
+            out.extend_from_slice(&u16::MAX.to_le_bytes());
+            out.extend_from_slice(&0_u16.to_le_bytes());
+            pos += max_len;
+        }
+        out.extend_from_slice(&((start - pos) as u16).to_le_bytes());
+        out.extend_from_slice(&((end - start) as u16).to_le_bytes());
+        out.extend_from_slice(&frame[start * 3..end * 3]);
+
+        pos = end;
+        i = end;
+    }
+}
+
+/// Decoder for the frames of a stream.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    header: Header,
+    frame: Vec<u8>,
+    buf: Vec<u8>,
+}
+
+impl Decoder {
+    /// Reads the header of a stream and constructs a decoder for its frames.
+    pub fn new(r: impl Read) -> io::Result<Self> {
+        let header = Header::read_from(r)?;
+        Ok(Self {
+            header,
+            frame: vec![0; header.frame_len()],
+            buf: vec![],
+        })
+    }
+
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Reads the next frame, returning `None` at the end of the stream.
+    pub fn read_frame(&mut self, mut r: impl Read) -> io::Result<Option<&[u8]>> {
+        let mut frame_header = [0; 5];
+        match r.read_exact(&mut frame_header) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let [frame_type, l0, l1, l2, l3] = frame_header;
+        let len = u32::from_le_bytes([l0, l1, l2, l3]) as usize;
+        // Delta frames are never larger than keyframes.
+        if len > self.frame.len() {
+            return Err(invalid_data("frame too large"));
+        }
+        self.buf.resize(len, 0);
+        r.read_exact(&mut self.buf)?;
+
+        match frame_type {
+            FRAME_KEY => {
+                if len != self.frame.len() {
+                    return Err(invalid_data("wrong keyframe length"));
+                }
+                self.frame.copy_from_slice(&self.buf);
+            }
+            FRAME_DELTA => {
+                let mut data = self.buf.as_slice();
+                let mut pos = 0;
+                while let Some((run_header, rest)) = data.split_first_chunk::<4>() {
+                    let [s0, s1, n0, n1] = *run_header;
+                    pos += u16::from_le_bytes([s0, s1]) as usize * 3;
+                    let run_len = u16::from_le_bytes([n0, n1]) as usize * 3;
+                    if rest.len() < run_len || pos + run_len > self.frame.len() {
+                        return Err(invalid_data("delta frame out of bounds"));
+                    }
+                    let (pixels, rest) = rest.split_at(run_len);
+                    self.frame[pos..pos + run_len].copy_from_slice(pixels);
+                    pos += run_len;
+                    data = rest;
+                }
+                if !data.is_empty() {
+                    return Err(invalid_data("trailing data in delta frame"));
+                }
+            }
+            other => return Err(invalid_data(format!("unknown frame type {other}"))),
+        }
+
+        Ok(Some(&self.frame))
+    }
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: Header = Header {
+        width: 4,
+        height: 3,
+        fps: 60,
+    };
+
+    #[test]
+    fn roundtrip() {
+        let mut frames = vec![vec![0; HEADER.frame_len()]];
+        for i in 0..HEADER.frame_len() {
+            let mut frame = frames.last().unwrap().clone();
+            frame[i] = i as u8 + 1;
+            frame[(i * 7) % HEADER.frame_len()] ^= 0x55;
+            frames.push(frame);
+        }
+        frames.push(vec![0xFF; HEADER.frame_len()]);
+        frames.push(vec![0xFF; HEADER.frame_len()]);
+
+        for delta in [false, true] {
+            let mut stream = HEADER.encode().to_vec();
+            let mut encoder = Encoder::new(delta);
+            for frame in &frames {
+                encoder.encode(frame, &mut stream);
+            }
+
+            let mut r = stream.as_slice();
+            let mut decoder = Decoder::new(&mut r).unwrap();
+            assert_eq!(decoder.header(), HEADER);
+            for frame in &frames {
+                assert_eq!(decoder.read_frame(&mut r).unwrap(), Some(frame.as_slice()));
+            }
+            assert_eq!(decoder.read_frame(&mut r).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn delta_is_smaller() {
+        let mut frame = vec![0; HEADER.frame_len()];
+        let mut encoder = Encoder::new(true);
+        let mut out = vec![];
+        encoder.encode(&frame, &mut out);
+        assert_eq!(out.len(), 5 + HEADER.frame_len());
+
+        frame[4] = 1;
+        out.clear();
+        encoder.encode(&frame, &mut out);
+        assert_eq!(out.len(), 5 + 4 + 3);
+
+        out.clear();
+        encoder.encode(&frame, &mut out);
+        assert_eq!(out.len(), 5);
+    }
+}
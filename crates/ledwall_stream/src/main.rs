@@ -0,0 +1,94 @@
+//! Viewer for the LED wall's frame stream.
+//!
+//! Draws frames in the terminal using 24-bit color and optionally records them
+//! to a file of raw RGB frames, which can be converted to a video using
+//! `ffmpeg -f rawvideo -pix_fmt rgb24 -s <width>x<height> -r <fps> -i <file>`.
+
+use std::io::{BufReader, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use ledwall_stream::{DEFAULT_PORT, Decoder};
+
+/// Maximum rate at which to redraw the terminal.
+const MAX_DISPLAY_FPS: f64 = 30.0;
+
+fn main() {
+    let mut addr = format!("127.0.0.1:{DEFAULT_PORT}");
+    let mut out_path: Option<PathBuf> = None;
+    let mut display = true;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out_path = Some(args.next().expect("missing value for --out").into()),
+            "--no-display" => display = false,
+            "--help" | "-h" => {
+                println!("usage: ledwall_stream [--out <file.rgb>] [--no-display] [<host>:<port>]");
+                return;
+            }
+            _ => addr = arg,
+        }
+    }
+
+    let stream = TcpStream::connect(&addr).expect("error connecting");
+    stream.set_nodelay(true).expect("error configuring socket");
+    let mut r = BufReader::new(stream);
+    let mut decoder = Decoder::new(&mut r).expect("error reading stream header");
+    let header = decoder.header();
+    eprintln!(
+        "Streaming {}x{} at {} FPS from {addr}",
+        header.width, header.height, header.fps,
+    );
+
+    let mut out = out_path.map(|path| {
+        std::io::BufWriter::new(std::fs::File::create(path).expect("error creating output file"))
+    });
+
+    let stdout = std::io::stdout();
+    let mut last_display = None::<Instant>;
+    let display_interval = Duration::from_secs_f64(1.0 / MAX_DISPLAY_FPS);
+    if display {
+        print!("\x1b[2J"); // clear screen
+    }
+
+    let mut frame_count = 0_u64;
+    while let Some(frame) = decoder.read_frame(&mut r).expect("error reading frame") {
+        frame_count += 1;
+        if let Some(out) = &mut out {
+            out.write_all(frame).expect("error writing frame");
+        }
+        if display && last_display.is_none_or(|t| t.elapsed() >= display_interval) {
+            last_display = Some(Instant::now());
+            let mut stdout = stdout.lock();
+            _ = stdout.write_all(render(frame, header.width.into()).as_bytes());
+            _ = stdout.flush();
+        }
+    }
+
+    if let Some(out) = &mut out {
+        out.flush().expect("error writing frame");
+    }
+    eprintln!("Stream ended after {frame_count} frames");
+}
+
+/// Renders a frame using half-block characters, with two rows of pixels per
+/// line of text.
+fn render(frame: &[u8], width: usize) -> String {
+    let rows = frame.chunks(width * 3).collect::<Vec<_>>();
+    let mut s = String::from("\x1b[H"); // move cursor to top left
+    for pair in rows.chunks(2) {
+        for x in 0..width {
+            let [r, g, b] = pixel(pair[0], x);
+            s += &format!("\x1b[38;2;{r};{g};{b}m");
+            let [r, g, b] = pair.get(1).map_or([0; 3], |row| pixel(row, x));
+            s += &format!("\x1b[48;2;{r};{g};{b}m\u{2580}");
+        }
+        s += "\x1b[0m\n";
+    }
+    s
+}
+
+fn pixel(row: &[u8], x: usize) -> [u8; 3] {
+    [row[x * 3], row[x * 3 + 1], row[x * 3 + 2]]
+}
@@ -18,7 +18,7 @@ mod time;
 
 pub use config::{Config, Das, LockDown};
 pub use error::{Blocked, Error, GameOver, HoldUsed};
-pub use input::{FrameInput, InputState};
+pub use input::{DasState, FrameInput, InputState};
 pub use output::FrameOutput;
 pub use piece::Tetromino;
 pub use playfield::Playfield;
@@ -87,6 +87,54 @@ impl<Time: GameTime> Game<Time> {
         ret
     }
 
+    /// Constructs a game from a snapshot returned by [`Game::state()`].
+    ///
+    /// `rng` is used to generate pieces after the ones already in the queue.
+    /// Returns `None` if the playfield size does not match `config`.
+    pub fn from_state(
+        config: Config<Time>,
+        state: GameState<Time>,
+        rng: Box<dyn RngCore>,
+    ) -> Option<Self> {
+        let expected_height = config.height + config.buffer_height;
+        if state.playfield.width() != config.width || state.playfield.height() != expected_height {
+            return None;
+        }
+        Some(Self {
+            config,
+            playfield: state.playfield,
+            queue: Queue::with_pieces(state.queue, rng),
+
+            input_state: state.input_state,
+
+            frame: state.frame,
+
+            falling_piece: state.falling_piece,
+
+            held_piece: state.held_piece,
+            hold_used: state.hold_used,
+
+            rows_to_clear: state.rows_to_clear,
+
+            game_over: state.game_over,
+        })
+    }
+
+    /// Returns a snapshot of the game state, excluding the config and RNG.
+    pub fn state(&self) -> GameState<Time> {
+        GameState {
+            playfield: self.playfield.clone(),
+            queue: self.queue.generated_pieces().collect(),
+            input_state: self.input_state.clone(),
+            frame: self.frame,
+            falling_piece: self.falling_piece,
+            held_piece: self.held_piece,
+            hold_used: self.hold_used,
+            rows_to_clear: self.rows_to_clear.clone(),
+            game_over: self.game_over,
+        }
+    }
+
     fn next_piece(&mut self) {
         let new_piece = self.queue.pop_piece();
         self.init_falling_piece(new_piece);
@@ -156,6 +204,10 @@ impl<Time: GameTime> Game<Time> {
     pub fn config(&self) -> &Config<Time> {
         &self.config
     }
+    /// Changes the DAS behavior in the middle of a game.
+    pub fn set_das(&mut self, das: Option<Das<Time>>) {
+        self.config.das = das;
+    }
     pub fn queue(&mut self) -> &mut Queue {
         &mut self.queue
     }
@@ -165,6 +217,9 @@ impl<Time: GameTime> Game<Time> {
     pub fn hold_used(&self) -> bool {
         self.hold_used
     }
+    pub fn is_game_over(&self) -> bool {
+        self.game_over
+    }
 
     pub fn can_soft_drop(&self) -> bool {
         let mut new_falling_piece = self.falling_piece;
@@ -323,6 +378,25 @@ impl<Time: GameTime> Game<Time> {
     }
 }
 
+/// Snapshot of a game's state, excluding its config and RNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameState<Time: GameTime = DefaultTime> {
+    pub playfield: Playfield,
+    /// Pieces already generated for the queue.
+    pub queue: Vec<Tetromino>,
+    pub input_state: InputState<Time>,
+    /// Time of last frame.
+    pub frame: Time,
+    pub falling_piece: FallingPiece<Time>,
+    pub held_piece: Option<Tetromino>,
+    /// Whether the falling piece has already been held.
+    pub hold_used: bool,
+    /// Rows to delete at the start of the next frame.
+    pub rows_to_clear: Vec<i8>,
+    /// Whether the game has ended.
+    pub game_over: bool,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct FallingPiece<Time: GameTime = DefaultTime> {
     pub piece: Tetromino,
@@ -12,6 +12,17 @@ pub enum Tetromino {
 }
 
 impl Tetromino {
+    /// All tetrominos, in the same order as their declaration.
+    pub const ALL: [Self; 7] = [
+        Self::I,
+        Self::J,
+        Self::L,
+        Self::O,
+        Self::S,
+        Self::T,
+        Self::Z,
+    ];
+
     /// Offsets for computing SRS kick tables, taken from [How Guideline SRS
     /// _Really_ Works][guideline-srs].
     ///
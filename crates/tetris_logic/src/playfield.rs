@@ -1,6 +1,7 @@
 use crate::{Blocked, Pos, Rot, Tetromino};
 
 /// Grid in which tetrominos fall.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Playfield {
     width: u8,
     height: u8,
@@ -18,6 +18,20 @@ impl Queue {
         }
     }
 
+    /// Constructs a queue that starts with `pieces` before generating more.
+    pub fn with_pieces(pieces: impl IntoIterator<Item = Tetromino>, rng: Box<dyn RngCore>) -> Self {
+        Self {
+            queue: pieces.into_iter().collect(),
+            rng,
+        }
+    }
+
+    /// Returns the pieces that have already been generated, without generating
+    /// more.
+    pub fn generated_pieces(&self) -> impl Iterator<Item = Tetromino> {
+        self.queue.iter().copied()
+    }
+
     pub fn pop_piece(&mut self) -> Tetromino {
         let piece = self.nth_next_piece(0);
         self.queue.pop_front();
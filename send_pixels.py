@@ -0,0 +1,74 @@
+#!/usr/bin/env python3
+
+"""Sends a test pattern to the LED wall's pixel receiver or DMX activity.
+
+Usage: send_pixels.py [opc|tpm2|sacn|artnet] [host[:port]]
+
+sACN and Art-Net use the default mapping, with 170 pixels in each universe
+starting from universe 1, and send a sync packet after each frame.
+"""
+
+import colorsys
+import socket
+import struct
+import sys
+import time
+
+WIDTH, HEIGHT = 32, 64
+FPS = 30
+
+protocol = sys.argv[1] if len(sys.argv) > 1 else 'opc'
+host, _, port = (sys.argv[2] if len(sys.argv) > 2 else '127.0.0.1').partition(':')
+if protocol == 'opc':
+    sock = socket.create_connection((host, int(port or 7890)))
+    def send(pixels):
+        sock.sendall(struct.pack('>BBH', 0, 0, len(pixels)) + pixels)
+elif protocol == 'tpm2':
+    sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+    addr = (host, int(port or 65506))
+    packet_len = WIDTH * 3 * 8  # 8 rows per packet
+    def send(pixels):
+        packets = [pixels[i:i + packet_len] for i in range(0, len(pixels), packet_len)]
+        for i, data in enumerate(packets):
+            header = struct.pack('>BBHBB', 0x9C, 0xDA, len(data), i + 1, len(packets))
+            sock.sendto(header + data + b'\x36', addr)
+elif protocol == 'sacn':
+    sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+    addr = (host, int(port or 5568))
+    cid = bytes(range(16))
+    def root_layer(vector, rest):
+        length = 0x7000 | (22 + len(rest))
+        return (struct.pack('>HH12sH', 0x10, 0, b'ASC-E1.17\0\0\0', length)
+                + struct.pack('>I', vector) + cid + rest)
+    def send(pixels):
+        universes = [pixels[i:i + 510] for i in range(0, len(pixels), 510)]
+        for i, data in enumerate(universes):
+            dmp = struct.pack('>HBBHHHB', 0x7000 | (11 + len(data)), 2, 0xA1, 0, 1, len(data) + 1, 0) + data
+            framing = (struct.pack('>HI', 0x7000 | (77 + len(dmp)), 2) + b'send_pixels'.ljust(64, b'\0')
+                       + struct.pack('>BHBBH', 100, 1, frame % 256, 0, i + 1) + dmp)
+            sock.sendto(root_layer(4, framing), addr)
+        sync = struct.pack('>HIBHH', 0x7000 | 11, 1, frame % 256, 1, 0)
+        sock.sendto(root_layer(8, sync), addr)
+elif protocol == 'artnet':
+    sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+    addr = (host, int(port or 6454))
+    def send(pixels):
+        universes = [pixels[i:i + 510] for i in range(0, len(pixels), 510)]
+        for i, data in enumerate(universes):
+            data = data.ljust(len(data) + len(data) % 2, b'\0')  # length must be even
+            header = b'Art-Net\0' + struct.pack('<H', 0x5000) + struct.pack('>HBB', 14, frame % 255 + 1, 0)
+            sock.sendto(header + struct.pack('<H', i + 1) + struct.pack('>H', len(data)) + data, addr)
+        sock.sendto(b'Art-Net\0' + struct.pack('<H', 0x5200) + struct.pack('>HBB', 14, 0, 0), addr)
+else:
+    sys.exit(__doc__)
+
+frame = 0
+while True:
+    pixels = bytearray()
+    for y in range(HEIGHT):
+        for x in range(WIDTH):
+            hue = ((x + y) / 64 + frame / FPS / 4) % 1
+            pixels += bytes(int(c * 255) for c in colorsys.hsv_to_rgb(hue, 1, 1))
+    send(bytes(pixels))
+    frame += 1
+    time.sleep(1 / FPS)